@@ -0,0 +1,96 @@
+use crate::models::device::{Capabilities, DeviceProbe};
+
+/// Firmware can hold a keymap in RAM without persisting it.
+pub const FEATURE_VOLATILE_APPLY: u32 = 1 << 0;
+/// Firmware persists the keymap only on an explicit commit.
+pub const FEATURE_EEPROM_COMMIT: u32 = 1 << 1;
+/// Firmware exposes selectable layout options.
+pub const FEATURE_LAYOUT_OPTIONS: u32 = 1 << 2;
+
+// Lowest `protocol_version` the studio trusts for each feature. These are
+// the studio firmware's own protocol numbers, checked by the tests below.
+
+/// Lowest protocol with the dynamic keymap.
+pub const PROTOCOL_DYNAMIC_KEYMAP: u16 = 7;
+/// Lowest protocol with dynamic macros.
+pub const PROTOCOL_MACROS: u16 = 8;
+/// Lowest protocol with encoder mapping.
+pub const PROTOCOL_ENCODERS: u16 = 10;
+
+/// Derives what the studio may offer for a device from its probe answers.
+pub fn negotiate(probe: &DeviceProbe) -> Capabilities {
+  let keymap = probe.protocol_version >= PROTOCOL_DYNAMIC_KEYMAP && probe.layer_count > 0;
+  let macros = probe.protocol_version >= PROTOCOL_MACROS && probe.macro_count > 0 && probe.macro_buffer_bytes > 0;
+  let has = |flag: u32| probe.feature_flags & flag != 0;
+  Capabilities {
+    volatile_apply: keymap && has(FEATURE_VOLATILE_APPLY),
+    commit: keymap && has(FEATURE_EEPROM_COMMIT),
+    layouts: has(FEATURE_LAYOUT_OPTIONS),
+    keymap,
+    scripts: macros,
+    encoders: probe.protocol_version >= PROTOCOL_ENCODERS && probe.encoder_count > 0,
+    max_layers: keymap.then_some(probe.layer_count),
+    macro_buffer_bytes: macros.then_some(probe.macro_buffer_bytes),
+    macro_count: macros.then_some(probe.macro_count),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{seed_root, TempDir};
+  use crate::backends::{mock::MockBackend, operation::OperationContext, r#trait::DeviceBackend};
+
+  #[test]
+  fn negotiates_from_protocol_and_probes_on_open() {
+    let old = DeviceProbe {
+      protocol_version: 9,
+      feature_flags: FEATURE_EEPROM_COMMIT,
+      layer_count: 4,
+      macro_count: 16,
+      macro_buffer_bytes: 512,
+      encoder_count: 2,
+    };
+    let caps = negotiate(&old);
+    assert!(caps.keymap && caps.commit && caps.scripts);
+    assert!(!caps.volatile_apply && !caps.layouts);
+    assert!(!caps.encoders, "encoders need protocol 10");
+    assert_eq!((caps.max_layers, caps.macro_count), (Some(4), Some(16)));
+
+    let seed_root = seed_root();
+    let data_root = TempDir::new("capabilities-test");
+    let backend = MockBackend::new(seed_root, data_root.to_path_buf());
+    let probe = backend.probe("mock-pad9-enc".to_string()).expect("probe").expect("probe answer");
+    let bundle = backend
+      .open_session("mock-pad9-enc".to_string(), &OperationContext::new())
+      .expect("open session");
+    assert!(bundle.capabilities.encoders);
+    assert_eq!(bundle.capabilities.macro_buffer_bytes, Some(512));
+    assert_eq!(bundle.probe.as_ref().map(|p| p.protocol_version), Some(probe.protocol_version));
+    assert_eq!(
+      serde_json::to_value(&bundle.capabilities).unwrap(),
+      serde_json::to_value(negotiate(&probe)).unwrap()
+    );
+  }
+
+  #[test]
+  fn protocol_thresholds_gate_each_feature() {
+    let at = |protocol_version: u16| {
+      negotiate(&DeviceProbe {
+        protocol_version,
+        feature_flags: 0,
+        layer_count: 4,
+        macro_count: 16,
+        macro_buffer_bytes: 512,
+        encoder_count: 2,
+      })
+    };
+    assert!(!at(PROTOCOL_DYNAMIC_KEYMAP - 1).keymap);
+    assert!(at(PROTOCOL_DYNAMIC_KEYMAP).keymap);
+    assert!(!at(PROTOCOL_MACROS - 1).scripts);
+    assert!(at(PROTOCOL_MACROS).scripts);
+    assert!(!at(PROTOCOL_ENCODERS - 1).encoders);
+    assert!(at(PROTOCOL_ENCODERS).encoders);
+    assert_eq!((PROTOCOL_DYNAMIC_KEYMAP, PROTOCOL_MACROS, PROTOCOL_ENCODERS), (7, 8, 10));
+  }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::models::state::{MutationGuard, SessionState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictField {
+  /// Committed revision, from the session base or `expectedRevision`.
+  Revision,
+  /// Staged checksum from `expectedChecksum`.
+  Checksum,
+}
+
+/// Raised when a mutation was based on state that has since changed. Carries
+/// the current state so the caller can reconcile instead of retrying blindly.
+#[derive(Debug, Clone)]
+pub struct ConflictError {
+  pub device_id: String,
+  pub field: ConflictField,
+  pub expected: i64,
+  pub actual: i64,
+  pub current: Box<SessionState>,
+}
+
+impl fmt::Display for ConflictError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let field = match self.field {
+      ConflictField::Revision => "revision",
+      ConflictField::Checksum => "staged checksum",
+    };
+    write!(
+      f,
+      "Conflict on {}: expected {} {} but found {}",
+      self.device_id, field, self.expected, self.actual
+    )
+  }
+}
+
+impl std::error::Error for ConflictError {}
+
+impl ConflictError {
+  pub fn new(device_id: &str, field: ConflictField, expected: i64, actual: i64, current: &SessionState) -> Self {
+    Self {
+      device_id: device_id.to_string(),
+      field,
+      expected,
+      actual,
+      current: Box::new(current.clone()),
+    }
+  }
+}
+
+/// Checks `guard` against the current state before a mutation.
+pub fn check_guard(guard: &MutationGuard, device_id: &str, state: &SessionState) -> Result<(), ConflictError> {
+  if let Some(expected) = guard.expected_revision {
+    let actual = state.committed_revision();
+    if expected != actual {
+      return Err(ConflictError::new(device_id, ConflictField::Revision, expected.into(), actual.into(), state));
+    }
+  }
+  if let Some(expected) = guard.expected_checksum {
+    let actual = state.staged.as_ref().and_then(|s| s.checksum).unwrap_or(0);
+    if expected != actual {
+      return Err(ConflictError::new(device_id, ConflictField::Checksum, expected.into(), actual.into(), state));
+    }
+  }
+  Ok(())
+}
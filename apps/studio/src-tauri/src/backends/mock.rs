@@ -1,46 +1,184 @@
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::{
   models::{
     binding::BindingEntry,
     bundle::ProfileBundle,
-    device::DeviceInfo,
-    state::SessionState,
+    device::{Capabilities, CommitMeta, DeviceInfo, DeviceProbe, DeviceState},
+    script::{DelayClass, ExecutionTarget, Script},
+    state::{MutationGuard, SessionState},
+    verify::{MemoryRegion, VerifyReport},
   },
-  store::{MockStore, store::{update_binding_in_layer, compute_checksum}},
+  scripts::{
+    delays::check_classes,
+    device::compile_macro_buffer,
+    host::{run_host, HostInjector, LogInjector},
+    inline::{inline_script_ref, inline_scripts, is_inline_id, promote_inline},
+    library::{self, BindingLocation, DanglingRefs, ScriptInUse},
+  },
+  store::{MockStore, Store, cache::SessionCache, store::{update_binding_in_layer, compute_checksum, SeedBundle}},
 };
 
-use super::r#trait::DeviceBackend;
+use super::{
+  capabilities::negotiate,
+  conflict::{check_guard, ConflictError, ConflictField},
+  operation::OperationContext,
+  r#trait::{DeviceBackend, UnknownSession},
+  verify::{compare, VerifyFailed},
+};
 use anyhow::anyhow;
 use uuid::Uuid;
 
+/// An open session and the committed revision its edits are based on.
+struct SessionHandle {
+  device_id: String,
+  base_revision: i32,
+}
+
 pub struct MockBackend {
-  store: MockStore,
-  sessions: Mutex<HashMap<String, String>>,
+  store: Arc<dyn Store + Send + Sync>,
+  cache: SessionCache,
+  sessions: Mutex<HashMap<String, SessionHandle>>,
+  /// Simulated per-layer read time, to exercise progress and timeouts.
+  latency: Duration,
+  injector: Arc<dyn HostInjector + Send + Sync>,
+  /// Host scripts in flight per session, for `stop_all`.
+  running: Mutex<HashMap<String, Vec<OperationContext>>>,
+  /// Targets whose writes "don't stick": they read back as unbound.
+  lost_writes: Mutex<HashSet<String>>,
 }
 
 impl MockBackend {
   pub fn new(seed_root: std::path::PathBuf, data_root: std::path::PathBuf) -> Self {
-    let store = MockStore::new(seed_root, data_root);
+    Self::with_store(Arc::new(MockStore::new(seed_root, data_root)))
+  }
+
+  pub fn with_store(store: Arc<dyn Store + Send + Sync>) -> Self {
     Self {
+      cache: SessionCache::new(store.clone()),
       store,
       sessions: Mutex::new(HashMap::new()),
+      latency: Duration::ZERO,
+      injector: Arc::new(LogInjector),
+      running: Mutex::new(HashMap::new()),
+      lost_writes: Mutex::new(HashSet::new()),
+    }
+  }
+
+  pub fn with_injector(mut self, injector: Arc<dyn HostInjector + Send + Sync>) -> Self {
+    self.injector = injector;
+    self
+  }
+
+  pub fn with_latency(mut self, latency: Duration) -> Self {
+    self.latency = latency;
+    self
+  }
+
+  /// Makes writes to `target_id` read back empty, as if they were lost.
+  pub fn lose_writes_to(&self, target_id: &str) {
+    let mut guard = self.lost_writes.lock().unwrap();
+    guard.insert(target_id.to_string());
+  }
+
+  pub fn clear_faults(&self) {
+    let mut guard = self.lost_writes.lock().unwrap();
+    guard.clear();
+  }
+
+  /// What the mock device holds after `state` was written to it.
+  fn stored_on_device(&self, state: &DeviceState) -> DeviceState {
+    let lost = self.lost_writes.lock().unwrap();
+    let mut stored = state.clone();
+    for layer in &mut stored.layers {
+      layer.bindings.retain(|b| !lost.contains(&b.target_id));
     }
+    stored
   }
 
   fn device_for_session(&self, session_id: &str) -> anyhow::Result<String> {
     let guard = self.sessions.lock().unwrap();
     guard
       .get(session_id)
-      .cloned()
-      .ok_or_else(|| anyhow!("Unknown session"))
+      .map(|h| h.device_id.clone())
+      .ok_or_else(|| UnknownSession.into())
+  }
+
+  fn base_revision(&self, session_id: &str) -> anyhow::Result<i32> {
+    let guard = self.sessions.lock().unwrap();
+    guard
+      .get(session_id)
+      .map(|h| h.base_revision)
+      .ok_or_else(|| UnknownSession.into())
+  }
+
+  fn rebase(&self, session_id: &str, revision: i32) {
+    let mut guard = self.sessions.lock().unwrap();
+    if let Some(handle) = guard.get_mut(session_id) {
+      handle.base_revision = revision;
+    }
   }
 
-  fn save_session(&self, device_id: &str, session: &SessionState) -> anyhow::Result<()> {
-    self.store.save_session_state(device_id, session)?;
+  /// Stand-in for moving a keymap to or from the device one layer at a time.
+  fn transfer_layers(&self, ctx: &OperationContext, phase: &str, state: Option<&DeviceState>) -> anyhow::Result<()> {
+    let layers = state.map_or(0, |s| s.layers.len()) as u32;
+    for done in 1..=layers {
+      ctx.check_cancelled()?;
+      if !self.latency.is_zero() {
+        std::thread::sleep(self.latency);
+      }
+      ctx.report(phase, done, layers);
+    }
+    ctx.check_cancelled()?;
     Ok(())
   }
+
+  /// Seed capabilities, or negotiated ones for devices that answer a probe.
+  fn capabilities(seeds: &SeedBundle, probe: Option<&DeviceProbe>) -> Capabilities {
+    probe.map_or_else(|| seeds.capabilities.clone(), negotiate)
+  }
+
+  /// The device's stored scripts, falling back to the seed's.
+  fn scripts(&self, device_id: &str, seeds: &SeedBundle) -> anyhow::Result<Vec<Script>> {
+    Ok(self.store.load_scripts(device_id)?.unwrap_or_else(|| seeds.scripts.clone()))
+  }
+
+  /// The profile a session edits: its staged keymap's, else the seed's.
+  fn profile_id(state: &SessionState, seeds: &SeedBundle) -> String {
+    state
+      .staged
+      .as_ref()
+      .map(|s| s.profile_id.clone())
+      .filter(|id| !id.is_empty())
+      .unwrap_or_else(|| seeds.profile.id.clone())
+  }
+
+  /// The profile's stored delay classes, falling back to the seed's.
+  fn delay_classes(&self, state: &SessionState, seeds: &SeedBundle) -> anyhow::Result<Vec<DelayClass>> {
+    let profile_id = Self::profile_id(state, seeds);
+    Ok(self.store.load_delay_classes(&profile_id)?.unwrap_or_else(|| seeds.profile.delay_classes.clone()))
+  }
+
+  /// A named script, or an inline sequence of the applied keymap by its inline id.
+  fn find_script(&self, session_id: &str, device_id: &str, seeds: &SeedBundle, script_id: &str) -> anyhow::Result<Script> {
+    let found = if is_inline_id(script_id) {
+      let state = self.session_state(session_id.to_string())?;
+      let applied = state.applied.or(state.staged).ok_or_else(|| anyhow!("No applied state"))?;
+      inline_scripts(&applied.profile_id, &applied.layers)
+        .into_iter()
+        .find(|s| s.id == script_id)
+    } else {
+      self.scripts(device_id, seeds)?.into_iter().find(|s| s.id == script_id)
+    };
+    found.ok_or_else(|| anyhow!("Unknown script {}", script_id))
+  }
+
+  fn has_sessions_for(&self, device_id: &str) -> bool {
+    let guard = self.sessions.lock().unwrap();
+    guard.values().any(|h| h.device_id == device_id)
+  }
 }
 
 impl DeviceBackend for MockBackend {
@@ -49,127 +187,394 @@ impl DeviceBackend for MockBackend {
     Ok(devices)
   }
 
-  fn open_session(&self, _device_id: String) -> tauri::Result<ProfileBundle> {
+  fn probe(&self, device_id: String) -> tauri::Result<Option<DeviceProbe>> {
+    self.store.prepare()?;
+    Ok(self.store.load_bundle(&device_id)?.probe)
+  }
+
+  fn open_session(&self, _device_id: String, ctx: &OperationContext) -> tauri::Result<ProfileBundle> {
     let device_id = _device_id;
-    self.store.init_dirs()?;
-    self.store.copy_seeds_if_needed()?;
-    let seeds = self.store.load_bundle(&device_id)?;
-    let mut session_state = if let Some(existing) = self.store.load_session_state(&device_id)? {
-      existing
-    } else {
-      self.store.initial_state_from_bundle(&device_id, &seeds)
-    };
+    self.store.prepare()?;
+    let probe = self.probe(device_id.clone())?;
+    let mut seeds = self.store.load_bundle(&device_id)?;
+    seeds.scripts = self.scripts(&device_id, &seeds)?;
     let session_id = Uuid::new_v4().to_string();
-    session_state.session_id = session_id.clone();
-
+    // Read back under the device lock, so edits from other sessions on this
+    // device wait for the read instead of being overwritten by it.
+    let session_state = self.cache.open(&device_id, &|| seeds.initial_state(&device_id), |state| {
+      self.transfer_layers(ctx, "read-keymap", state.staged.as_ref())?;
+      state.session_id = session_id.clone();
+      Ok(state.clone())
+    })?;
+
+    // A caller that timed out never learns the session id, so don't register it.
+    ctx.check_cancelled().map_err(anyhow::Error::from)?;
     {
       let mut guard = self.sessions.lock().unwrap();
-      guard.insert(session_id.clone(), device_id.clone());
+      guard.insert(
+        session_id.clone(),
+        SessionHandle {
+          device_id: device_id.clone(),
+          base_revision: session_state.committed_revision(),
+        },
+      );
     }
 
-    self.save_session(&device_id, &session_state)?;
-
-    Ok(seeds.to_profile_bundle(session_id, &session_state))
+    seeds.profile.delay_classes = self.delay_classes(&session_state, &seeds)?;
+    let mut bundle = seeds.to_profile_bundle(session_id, &session_state);
+    bundle.recovery = self.store.take_recovery(&device_id);
+    bundle.capabilities = Self::capabilities(&seeds, probe.as_ref());
+    bundle.probe = probe;
+    Ok(bundle)
   }
 
   fn close_session(&self, _session_id: String) -> tauri::Result<()> {
     let session_id = _session_id;
-    let mut guard = self.sessions.lock().unwrap();
-    guard.remove(&session_id);
+    let device_id = {
+      let mut guard = self.sessions.lock().unwrap();
+      guard.remove(&session_id).map(|h| h.device_id)
+    };
+    if let Some(device_id) = device_id {
+      if !self.has_sessions_for(&device_id) {
+        self.cache.evict(&device_id)?;
+      }
+    }
     Ok(())
   }
 
-  fn set_binding(&self, _session_id: String, _req: BindingEntry) -> tauri::Result<()> {
+  fn set_binding(&self, _session_id: String, _req: BindingEntry, guard: MutationGuard) -> tauri::Result<()> {
     let session_id = _session_id;
-    let req = _req;
+    let mut req = _req;
     let device_id = self.device_for_session(&session_id)?;
-    let mut session = self
-      .store
-      .load_session_state(&device_id)?
-      .ok_or_else(|| anyhow!("No session state found"))?;
-
-    let staged = session
-      .staged
-      .as_mut()
-      .ok_or_else(|| anyhow!("No staged state"))?;
-
-    let target_layer_id = req.layer_id.unwrap_or_else(|| staged.layers.get(0).map(|l| l.id).unwrap_or(1));
-    let mut layer = staged
-      .layers
-      .iter_mut()
-      .find(|l| l.id == target_layer_id)
-      .ok_or_else(|| anyhow!("Layer {} not found", target_layer_id))?;
-    update_binding_in_layer(&mut layer, &req);
-    staged.checksum = Some(compute_checksum(staged));
-
-    self.save_session(&device_id, &session)?;
+    self.cache.update(&device_id, |session| {
+      check_guard(&guard, &device_id, session)?;
+      let staged = session
+        .staged
+        .as_mut()
+        .ok_or_else(|| anyhow!("No staged state"))?;
+
+      let target_layer_id = req.layer_id.unwrap_or_else(|| staged.layers.first().map(|l| l.id).unwrap_or(1));
+      let layer = staged
+        .layers
+        .iter_mut()
+        .find(|l| l.id == target_layer_id)
+        .ok_or_else(|| anyhow!("Layer {} not found", target_layer_id))?;
+      req.layer_id = Some(target_layer_id);
+      update_binding_in_layer(layer, &req);
+      staged.checksum = Some(compute_checksum(staged));
+      Ok(())
+    })?;
     Ok(())
   }
 
-  fn apply_to_ram(&self, _session_id: String) -> tauri::Result<()> {
+  fn apply_to_ram(&self, _session_id: String, guard: MutationGuard, ctx: &OperationContext) -> tauri::Result<()> {
     let session_id = _session_id;
     let device_id = self.device_for_session(&session_id)?;
-    let mut session = self
-      .store
-      .load_session_state(&device_id)?
-      .ok_or_else(|| anyhow!("No session state found"))?;
+    let seeds = self.store.load_bundle(&device_id)?;
+    let caps = Self::capabilities(&seeds, seeds.probe.as_ref());
+    let scripts = self.scripts(&device_id, &seeds)?;
+    self.cache.update(&device_id, |session| {
+      check_guard(&guard, &device_id, session)?;
+      let delays = self.delay_classes(session, &seeds)?;
+      let layers = session.staged.as_ref().map(|s| s.layers.as_slice()).unwrap_or_default();
+      let macros = compile_macro_buffer(&scripts, layers, &caps, &delays)?;
+      self.transfer_layers(ctx, "write-keymap", session.staged.as_ref())?;
+      if !macros.is_empty() {
+        log::debug!("Wrote {} byte macro buffer to {}", macros.len(), device_id);
+      }
+      if let Some(staged) = session.staged.clone() {
+        session.applied = Some(staged);
+        if let Some(applied) = session.applied.as_mut() {
+          applied.revision = Some(applied.revision.unwrap_or(0));
+          applied.checksum = Some(compute_checksum(applied));
+        }
+      }
+      Ok(())
+    })?;
+    Ok(())
+  }
 
-    if let Some(staged) = session.staged.clone() {
-      session.applied = Some(staged);
-      if let Some(applied) = session.applied.as_mut() {
-        applied.revision = Some(applied.revision.unwrap_or(0));
-        applied.checksum = Some(compute_checksum(applied));
+  fn revert_ram(&self, _session_id: String, guard: MutationGuard) -> tauri::Result<()> {
+    let session_id = _session_id;
+    let device_id = self.device_for_session(&session_id)?;
+    let revision = self.cache.update(&device_id, |session| {
+      check_guard(&guard, &device_id, session)?;
+      if let Some(committed) = session.committed.clone() {
+        session.applied = Some(committed.clone());
+        session.staged = Some(committed);
       }
-      self.save_session(&device_id, &session)?;
-    }
+      Ok(session.committed_revision())
+    })?;
+    // Reverting adopts whatever is committed now, including other sessions' commits.
+    self.rebase(&session_id, revision);
     Ok(())
   }
 
-  fn revert_ram(&self, _session_id: String) -> tauri::Result<()> {
+  fn commit(
+    &self,
+    _session_id: String,
+    message: Option<String>,
+    guard: MutationGuard,
+    verify: bool,
+    ctx: &OperationContext,
+  ) -> tauri::Result<()> {
     let session_id = _session_id;
     let device_id = self.device_for_session(&session_id)?;
-    let mut session = self
-      .store
-      .load_session_state(&device_id)?
+    let base_revision = self.base_revision(&session_id)?;
+    let mut new_revision = base_revision;
+    self.cache.commit(&device_id, |session| {
+      check_guard(&guard, &device_id, session)?;
+      let actual = session.committed_revision();
+      if actual != base_revision {
+        return Err(ConflictError::new(&device_id, ConflictField::Revision, base_revision.into(), actual.into(), session).into());
+      }
+      let source = session
+        .applied
+        .clone()
+        .or_else(|| session.staged.clone())
+        .ok_or_else(|| anyhow!("Nothing to commit"))?;
+      self.transfer_layers(ctx, "commit-eeprom", Some(&source))?;
+      if verify {
+        // Checked before anything is recorded, so a failed write leaves the
+        // revision and history as they were.
+        let actual = self.stored_on_device(&source);
+        self.transfer_layers(ctx, "verify-eeprom", Some(&actual))?;
+        let mismatches = compare(&source, &actual);
+        if !mismatches.is_empty() {
+          let report = VerifyReport { region: MemoryRegion::Eeprom, mismatches };
+          return Err(VerifyFailed { report }.into());
+        }
+      }
+      // A caller that timed out has been told the commit failed.
+      ctx.check_cancelled()?;
+
+      let mut committed = source.clone();
+      new_revision = actual + 1;
+      committed.revision = Some(new_revision);
+      committed.meta = Some(CommitMeta::capture(message));
+      committed.checksum = Some(compute_checksum(&committed));
+      session.committed = Some(committed.clone());
+      session.applied = Some(committed.clone());
+      session.staged = Some(committed.clone());
+      Ok(committed)
+    })?;
+    self.rebase(&session_id, new_revision);
+    Ok(())
+  }
+
+  fn commit_history(&self, session_id: String) -> tauri::Result<Vec<DeviceState>> {
+    let device_id = self.device_for_session(&session_id)?;
+    let history = self.store.load_history(&device_id)?;
+    Ok(history)
+  }
+
+  fn session_state(&self, session_id: String) -> tauri::Result<SessionState> {
+    let device_id = self.device_for_session(&session_id)?;
+    let state = self
+      .cache
+      .get(&device_id)?
       .ok_or_else(|| anyhow!("No session state found"))?;
+    Ok(state)
+  }
 
-    if let Some(committed) = session.committed.clone() {
-      session.applied = Some(committed.clone());
-      session.staged = Some(committed);
-      self.save_session(&device_id, &session)?;
+  fn read_back(&self, session_id: String, region: MemoryRegion, ctx: &OperationContext) -> tauri::Result<DeviceState> {
+    // The mock device holds what was last applied or committed, minus lost writes.
+    let state = self.session_state(session_id)?;
+    let written = match region {
+      MemoryRegion::Ram => state.applied,
+      MemoryRegion::Eeprom => state.committed,
+    }
+    .ok_or_else(|| anyhow!("Nothing written to {:?} yet", region))?;
+    let held = self.stored_on_device(&written);
+    self.transfer_layers(ctx, "read-keymap", Some(&held))?;
+    Ok(held)
+  }
+
+  fn run(&self, _session_id: String, _script_id: String, ctx: &OperationContext) -> tauri::Result<()> {
+    let (session_id, script_id) = (_session_id, _script_id);
+    let device_id = self.device_for_session(&session_id)?;
+    let seeds = self.store.load_bundle(&device_id)?;
+    let script = self.find_script(&session_id, &device_id, &seeds, &script_id)?;
+    let delays = self.delay_classes(&self.session_state(session_id.clone())?, &seeds)?;
+
+    match script.target {
+      ExecutionTarget::Device => {
+        // The mock firmware "plays" the macro once it is known to fit.
+        let state = self.session_state(session_id)?;
+        let layers = state.applied.as_ref().map(|s| s.layers.as_slice()).unwrap_or_default();
+        let named = if is_inline_id(&script.id) { Vec::new() } else { vec![script.clone()] };
+        compile_macro_buffer(&named, layers, &Self::capabilities(&seeds, seeds.probe.as_ref()), &delays)?;
+        log::info!("Mock device {} played macro {}", device_id, script.id);
+      }
+      ExecutionTarget::Host => {
+        {
+          let mut guard = self.running.lock().unwrap();
+          guard.entry(session_id.clone()).or_default().push(ctx.clone());
+        }
+        let result = run_host(&script, &delays, self.injector.as_ref(), ctx);
+        {
+          let mut guard = self.running.lock().unwrap();
+          if let Some(list) = guard.get_mut(&session_id) {
+            list.retain(|c| !c.same_as(ctx));
+          }
+        }
+        result?;
+      }
     }
     Ok(())
   }
 
-  fn commit(&self, _session_id: String) -> tauri::Result<()> {
+  fn stop_all(&self, _session_id: String) -> tauri::Result<()> {
     let session_id = _session_id;
+    let guard = self.running.lock().unwrap();
+    for ctx in guard.get(&session_id).into_iter().flatten() {
+      ctx.cancel();
+    }
+    Ok(())
+  }
+
+  fn promote_inline(
+    &self,
+    session_id: String,
+    layer_id: i32,
+    target_id: String,
+    name: String,
+    guard: MutationGuard,
+  ) -> tauri::Result<Script> {
     let device_id = self.device_for_session(&session_id)?;
-    let mut session = self
-      .store
-      .load_session_state(&device_id)?
-      .ok_or_else(|| anyhow!("No session state found"))?;
+    let script = self.cache.update(&device_id, |session| {
+      check_guard(&guard, &device_id, session)?;
+      let seeds = self.store.load_bundle(&device_id)?;
+      let scripts = self.scripts(&device_id, &seeds)?;
+      let staged = session.staged.as_mut().ok_or_else(|| anyhow!("No staged state"))?;
+      let script_id = Uuid::new_v4().to_string();
+      let script = promote_inline(&mut staged.layers, &scripts, layer_id, &target_id, script_id, name, &staged.profile_id)?;
+      // Store the script before the binding stops carrying the steps.
+      let mut updated = scripts.clone();
+      updated.push(script.clone());
+      self.store.save_scripts(&device_id, &updated)?;
+      staged.checksum = Some(compute_checksum(staged));
+      Ok(script)
+    })?;
+    Ok(script)
+  }
 
-    let source = session
-      .applied
-      .clone()
-      .or_else(|| session.staged.clone())
-      .ok_or_else(|| anyhow!("Nothing to commit"))?;
-
-    let mut committed = source.clone();
-    committed.revision = Some(committed.revision.unwrap_or(0) + 1);
-    committed.checksum = Some(compute_checksum(&committed));
-    session.committed = Some(committed.clone());
-    session.applied = Some(committed.clone());
-    session.staged = Some(committed);
-    self.save_session(&device_id, &session)?;
+  fn inline_script_ref(&self, session_id: String, layer_id: i32, target_id: String, guard: MutationGuard) -> tauri::Result<usize> {
+    let device_id = self.device_for_session(&session_id)?;
+    let seeds = self.store.load_bundle(&device_id)?;
+    let scripts = self.scripts(&device_id, &seeds)?;
+    let remaining = self.cache.update(&device_id, |session| {
+      check_guard(&guard, &device_id, session)?;
+      let staged = session.staged.as_mut().ok_or_else(|| anyhow!("No staged state"))?;
+      let remaining = inline_script_ref(&mut staged.layers, &scripts, layer_id, &target_id)?;
+      staged.checksum = Some(compute_checksum(staged));
+      Ok(remaining)
+    })?;
+    Ok(remaining)
+  }
+
+  fn list_scripts(&self, session_id: String) -> tauri::Result<Vec<Script>> {
+    let device_id = self.device_for_session(&session_id)?;
+    let seeds = self.store.load_bundle(&device_id)?;
+    Ok(self.scripts(&device_id, &seeds)?)
+  }
+
+  // Script and delay class edits load, change and save under the device's
+  // cache lock, so concurrent edits can't overwrite each other.
+
+  fn create_script(&self, session_id: String, mut script: Script) -> tauri::Result<Script> {
+    let device_id = self.device_for_session(&session_id)?;
+    let created = self.cache.with_state(&device_id, |_| {
+      let seeds = self.store.load_bundle(&device_id)?;
+      let mut scripts = self.scripts(&device_id, &seeds)?;
+      if script.profile_id.is_empty() {
+        script.profile_id = seeds.profile.id.clone();
+      }
+      let created = library::create(&mut scripts, script)?;
+      self.store.save_scripts(&device_id, &scripts)?;
+      Ok(created)
+    })?;
+    Ok(created)
+  }
+
+  fn update_script(&self, session_id: String, script: Script) -> tauri::Result<()> {
+    let device_id = self.device_for_session(&session_id)?;
+    self.cache.with_state(&device_id, |_| {
+      let seeds = self.store.load_bundle(&device_id)?;
+      let mut scripts = self.scripts(&device_id, &seeds)?;
+      library::update(&mut scripts, script)?;
+      self.store.save_scripts(&device_id, &scripts)
+    })?;
     Ok(())
   }
 
-  fn run(&self, _session_id: String, _script_id: String) -> tauri::Result<()> {
+  fn delete_script(
+    &self,
+    session_id: String,
+    script_id: String,
+    dangling: DanglingRefs,
+    guard: MutationGuard,
+  ) -> tauri::Result<Vec<BindingLocation>> {
+    let device_id = self.device_for_session(&session_id)?;
+    let repaired = self.cache.update(&device_id, |session| {
+      check_guard(&guard, &device_id, session)?;
+      // Only staged bindings can be repaired; the device must not lose a script it still plays.
+      let mut on_device: Vec<BindingLocation> = Vec::new();
+      for state in [&session.applied, &session.committed].into_iter().flatten() {
+        for location in library::references(&state.layers, &script_id) {
+          if !on_device.contains(&location) {
+            on_device.push(location);
+          }
+        }
+      }
+      if !on_device.is_empty() {
+        return Err(ScriptInUse { script_id: script_id.clone(), used_by: on_device }.into());
+      }
+      let seeds = self.store.load_bundle(&device_id)?;
+      let mut scripts = self.scripts(&device_id, &seeds)?;
+      let staged = session.staged.as_mut().ok_or_else(|| anyhow!("No staged state"))?;
+      let repaired = library::delete(&mut scripts, &mut staged.layers, &script_id, dangling)?;
+      staged.checksum = Some(compute_checksum(staged));
+      // Saved inside the update so a failed write leaves the staged keymap untouched.
+      self.store.save_scripts(&device_id, &scripts)?;
+      Ok(repaired)
+    })?;
+    Ok(repaired)
+  }
+
+  fn list_delay_classes(&self, session_id: String) -> tauri::Result<Vec<DelayClass>> {
+    let device_id = self.device_for_session(&session_id)?;
+    let seeds = self.store.load_bundle(&device_id)?;
+    Ok(self.delay_classes(&self.session_state(session_id)?, &seeds)?)
+  }
+
+  fn set_delay_classes(&self, session_id: String, classes: Vec<DelayClass>) -> tauri::Result<()> {
+    let device_id = self.device_for_session(&session_id)?;
+    check_classes(&classes)?;
+    let seeds = self.store.load_bundle(&device_id)?;
+    self.cache.with_state(&device_id, |state| {
+      self.store.save_delay_classes(&Self::profile_id(state, &seeds), &classes)
+    })?;
     Ok(())
   }
 
-  fn stop_all(&self, _session_id: String) -> tauri::Result<()> {
+  fn invalidate_device(&self, device_id: String) -> tauri::Result<Vec<String>> {
+    let mut guard = self.sessions.lock().unwrap();
+    let dropped: Vec<String> = guard
+      .iter()
+      .filter(|(_, h)| h.device_id == device_id)
+      .map(|(s, _)| s.clone())
+      .collect();
+    for session_id in &dropped {
+      guard.remove(session_id);
+    }
+    drop(guard);
+    self.cache.evict(&device_id)?;
+    Ok(dropped)
+  }
+
+  fn flush(&self) -> tauri::Result<()> {
+    self.cache.flush()?;
     Ok(())
   }
 }
@@ -177,19 +582,20 @@ impl DeviceBackend for MockBackend {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::testing::{seed_root, TempDir};
   use crate::models::binding::{Binding, BindingEntry};
   use crate::store::MockStore;
 
   #[test]
   fn session_flow_commits_state() {
-    let seed_root = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("mock");
-    let data_root = std::env::temp_dir().join(format!("mock-backend-test-{}", Uuid::new_v4()));
-    let backend = MockBackend::new(seed_root.clone(), data_root.clone());
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = MockBackend::new(seed_root.clone(), data_root.to_path_buf());
 
     let devices = backend.list_devices().expect("devices");
     assert!(!devices.is_empty());
     let bundle = backend
-      .open_session(devices[0].id.clone())
+      .open_session(devices[0].id.clone(), &OperationContext::new())
       .expect("open session");
 
     let session_id = bundle.session_id.clone();
@@ -203,12 +609,14 @@ mod tests {
       },
     };
     backend
-      .set_binding(session_id.clone(), binding)
+      .set_binding(session_id.clone(), binding, MutationGuard::default())
       .expect("set binding");
-    backend.apply_to_ram(session_id.clone()).expect("apply");
-    backend.commit(session_id.clone()).expect("commit");
+    backend.apply_to_ram(session_id.clone(), MutationGuard::default(), &OperationContext::new()).expect("apply");
+    backend
+      .commit(session_id.clone(), Some("Enter on 1,1".to_string()), MutationGuard::default(), false, &OperationContext::new())
+      .expect("commit");
 
-    let store = MockStore::new(seed_root, data_root.clone());
+    let store = MockStore::new(seed_root, data_root.to_path_buf());
     let state = store
       .load_session_state(&devices[0].id)
       .expect("load state")
@@ -226,8 +634,286 @@ mod tests {
         "new binding persisted to committed"
       );
       assert!(committed.revision.unwrap_or(0) >= 1);
+      let meta = committed.meta.expect("commit meta recorded");
+      assert_eq!(meta.message.as_deref(), Some("Enter on 1,1"));
+      assert!(meta.app_version.is_some());
+    }
+
+    let history = backend.commit_history(session_id).expect("history");
+    assert_eq!(history.len(), 1);
+  }
+
+  #[test]
+  fn edits_stay_in_memory_until_flushed() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = MockBackend::new(seed_root.clone(), data_root.to_path_buf());
+    let store = MockStore::new(seed_root, data_root.to_path_buf());
+
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let session_id = backend.open_session(device_id.clone(), &OperationContext::new()).expect("open session").session_id;
+    let binding = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:0,0".to_string(),
+      binding: Binding::SimpleAction {
+        action: "TAP".to_string(),
+        arg: Some("KC_ESC".to_string()),
+        meta: None,
+      },
+    };
+    backend.set_binding(session_id.clone(), binding, MutationGuard::default()).expect("set binding");
+
+    let has_binding = |store: &MockStore| {
+      let state = store.load_session_state(&device_id).unwrap().unwrap();
+      state.staged.unwrap().layers[0].bindings.iter().any(|b| {
+        matches!(&b.binding, Binding::SimpleAction { arg: Some(arg), .. } if arg == "KC_ESC")
+      })
+    };
+    assert!(!has_binding(&store), "edit written before flush");
+    backend.flush().expect("flush");
+    assert!(has_binding(&store), "edit missing after flush");
+  }
+
+  #[test]
+  fn concurrent_edits_survive_and_stale_commit_conflicts() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = Arc::new(MockBackend::new(seed_root, data_root.to_path_buf()));
+
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let first = backend.open_session(device_id.clone(), &OperationContext::new()).expect("open first").session_id;
+    let second = backend.open_session(device_id.clone(), &OperationContext::new()).expect("open second").session_id;
+
+    let workers: Vec<_> = (0..8)
+      .map(|i| {
+        let backend = backend.clone();
+        let session_id = first.clone();
+        std::thread::spawn(move || {
+          let binding = BindingEntry {
+            layer_id: Some(1),
+            target_id: format!("race:{}", i),
+            binding: Binding::SimpleAction {
+              action: "TAP".to_string(),
+              arg: Some("KC_A".to_string()),
+              meta: None,
+            },
+          };
+          backend.set_binding(session_id, binding, MutationGuard::default()).expect("set binding");
+        })
+      })
+      .collect();
+    for worker in workers {
+      worker.join().unwrap();
+    }
+
+    backend.apply_to_ram(first.clone(), MutationGuard::default(), &OperationContext::new()).expect("apply");
+    backend.commit(first.clone(), None, MutationGuard::default(), false, &OperationContext::new()).expect("commit first");
+    let history = backend.commit_history(first.clone()).expect("history");
+    let layer = history[0].layers.iter().find(|l| l.id == 1).unwrap();
+    assert_eq!(layer.bindings.iter().filter(|b| b.target_id.starts_with("race:")).count(), 8);
+
+    let stale_commit = backend.commit(second.clone(), None, MutationGuard::default(), false, &OperationContext::new());
+    let tauri::Error::Anyhow(err) = stale_commit.expect_err("stale commit") else {
+      panic!("expected an anyhow error");
+    };
+    let conflict = err.downcast::<ConflictError>().expect("typed conflict");
+    assert_eq!((conflict.field, conflict.expected, conflict.actual), (ConflictField::Revision, 0, 1));
+    assert_eq!(conflict.current.committed_revision(), 1);
+
+    let stale = MutationGuard {
+      expected_checksum: None,
+      expected_revision: Some(0),
+    };
+    assert!(backend.revert_ram(second.clone(), stale).is_err(), "expected revision checked");
+    let current = MutationGuard {
+      expected_checksum: None,
+      expected_revision: Some(1),
+    };
+    backend.revert_ram(second.clone(), current.clone()).expect("revert");
+    backend.commit(second, None, current, false, &OperationContext::new()).expect("commit after rebase");
+  }
+
+  #[test]
+  fn open_session_read_back_keeps_concurrent_edits() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = Arc::new(MockBackend::new(seed_root, data_root.to_path_buf()).with_latency(Duration::from_millis(20)));
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let first = backend.open_session(device_id.clone(), &OperationContext::new()).expect("open first").session_id;
+
+    let opening = {
+      let (backend, device_id) = (backend.clone(), device_id.clone());
+      std::thread::spawn(move || backend.open_session(device_id, &OperationContext::new()).expect("open second"))
+    };
+    // Lands while the second session is still reading the keymap back.
+    std::thread::sleep(Duration::from_millis(10));
+    let entry = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:0,3".to_string(),
+      binding: Binding::SimpleAction {
+        action: "TAP".to_string(),
+        arg: Some("KC_F13".to_string()),
+        meta: None,
+      },
+    };
+    backend.set_binding(first.clone(), entry, MutationGuard::default()).expect("set binding");
+    let second = opening.join().unwrap().session_id;
+
+    for session_id in [first, second] {
+      let staged = backend.session_state(session_id).expect("state").staged.expect("staged");
+      let layer = staged.layers.iter().find(|l| l.id == 1).unwrap();
+      assert!(layer.bindings.iter().any(|b| b.target_id == "key:0,3"), "edit lost to the read-back");
     }
+  }
 
-    let _ = std::fs::remove_dir_all(&data_root);
+  #[test]
+  fn failed_eeprom_verify_records_nothing() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = MockBackend::new(seed_root, data_root.to_path_buf());
+    let ctx = OperationContext::new();
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let session_id = backend.open_session(device_id, &ctx).expect("open").session_id;
+
+    let entry = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:0,1".to_string(),
+      binding: Binding::SimpleAction {
+        action: "TAP".to_string(),
+        arg: Some("KC_TAB".to_string()),
+        meta: None,
+      },
+    };
+    backend.set_binding(session_id.clone(), entry, MutationGuard::default()).expect("set binding");
+    backend.apply_to_ram(session_id.clone(), MutationGuard::default(), &ctx).expect("apply");
+    let before = backend.session_state(session_id.clone()).expect("state").committed_revision();
+
+    backend.lose_writes_to("key:0,1");
+    let tauri::Error::Anyhow(err) = backend
+      .commit(session_id.clone(), None, MutationGuard::default(), true, &ctx)
+      .expect_err("verify should fail")
+    else {
+      panic!("expected an anyhow error");
+    };
+    let failed = err.downcast::<VerifyFailed>().expect("typed verify failure");
+    assert_eq!(failed.report.mismatches.len(), 1);
+    assert_eq!(backend.session_state(session_id.clone()).expect("state").committed_revision(), before);
+    assert!(backend.commit_history(session_id.clone()).expect("history").is_empty());
+
+    backend.clear_faults();
+    backend.commit(session_id.clone(), None, MutationGuard::default(), true, &ctx).expect("verified commit");
+    assert_eq!(backend.session_state(session_id.clone()).expect("state").committed_revision(), before + 1);
+    assert_eq!(backend.commit_history(session_id).expect("history").len(), 1);
+  }
+
+  #[test]
+  fn script_edits_serialize_and_keep_scripts_the_device_uses() {
+    use crate::models::script::{ExecutionTarget, Step};
+    use crate::scripts::library::ScriptInUse;
+
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = Arc::new(MockBackend::new(seed_root, data_root.to_path_buf()));
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let session_id = backend.open_session(device_id, &OperationContext::new()).expect("open").session_id;
+    let before = backend.list_scripts(session_id.clone()).expect("scripts").len();
+
+    let script = |name: String| Script {
+      id: String::new(),
+      profile_id: String::new(),
+      name,
+      target: ExecutionTarget::Host,
+      steps: vec![Step { id: 1, name: "Tap".into(), op: "TAP".into(), arg: Some("KC_A".into()), ..Default::default() }],
+      meta: None,
+    };
+    let workers: Vec<_> = (0..8)
+      .map(|i| {
+        let (backend, session_id) = (backend.clone(), session_id.clone());
+        let script = script(format!("Script {}", i));
+        std::thread::spawn(move || backend.create_script(session_id, script).expect("create"))
+      })
+      .collect();
+    let created: Vec<Script> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_eq!(backend.list_scripts(session_id.clone()).expect("scripts").len(), before + 8);
+
+    let bound = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:0,2".to_string(),
+      binding: Binding::ScriptRef { script_id: created[0].id.clone(), target: None, meta: None },
+    };
+    backend.set_binding(session_id.clone(), bound, MutationGuard::default()).expect("bind");
+    backend.apply_to_ram(session_id.clone(), MutationGuard::default(), &OperationContext::new()).expect("apply");
+    let tauri::Error::Anyhow(err) = backend
+      .delete_script(session_id.clone(), created[0].id.clone(), DanglingRefs::Clear, MutationGuard::default())
+      .expect_err("script is on the device")
+    else {
+      panic!("expected an anyhow error");
+    };
+    assert_eq!(err.downcast::<ScriptInUse>().expect("typed in-use error").used_by.len(), 1);
+    backend
+      .delete_script(session_id.clone(), created[1].id.clone(), DanglingRefs::Refuse, MutationGuard::default())
+      .expect("unused script deletes");
+    assert_eq!(backend.list_scripts(session_id).expect("scripts").len(), before + 7);
+  }
+
+  #[test]
+  fn delay_classes_are_kept_per_profile() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = MockBackend::new(seed_root, data_root.to_path_buf());
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let session_id = backend.open_session(device_id.clone(), &OperationContext::new()).expect("open").session_id;
+    let seeded = backend.list_delay_classes(session_id.clone()).expect("classes");
+
+    let class = |name: &str| DelayClass { id: 9, name: name.into(), base_ms: 40, jitter: 0, scale: 1.0 };
+    backend.set_delay_classes(session_id.clone(), vec![class("first")]).expect("set first");
+    let switch_to = |profile_id: &str| {
+      backend
+        .cache
+        .update(&device_id, |state| {
+          state.staged.as_mut().unwrap().profile_id = profile_id.to_string();
+          Ok(())
+        })
+        .unwrap()
+    };
+    let first_profile = backend.session_state(session_id.clone()).unwrap().staged.unwrap().profile_id;
+
+    switch_to("p-second");
+    assert_eq!(backend.list_delay_classes(session_id.clone()).expect("classes"), seeded);
+    backend.set_delay_classes(session_id.clone(), vec![class("second")]).expect("set second");
+    switch_to(&first_profile);
+    assert_eq!(backend.list_delay_classes(session_id).expect("classes"), vec![class("first")]);
+  }
+
+  #[test]
+  fn seed_devices_cover_every_control_kind() {
+    use crate::models::layout::ControlKind;
+    use std::collections::HashSet;
+
+    let seed_root = seed_root();
+    let data_root = TempDir::new("mock-backend-test");
+    let backend = MockBackend::new(seed_root, data_root.to_path_buf());
+
+    let mut kinds = HashSet::new();
+    let mut max_layers = 0;
+    for device in backend.list_devices().expect("devices") {
+      let bundle = backend.open_session(device.id.clone(), &OperationContext::new()).expect("open session");
+      let layout = bundle.layout.expect("layout");
+      kinds.extend(layout.controls.iter().map(|c| c.kind));
+      max_layers = max_layers.max(bundle.profile.layers.len());
+    }
+
+    for kind in [
+      ControlKind::Block,
+      ControlKind::EncoderBlock,
+      ControlKind::Knob,
+      ControlKind::Encoder,
+      ControlKind::Oled,
+      ControlKind::Button,
+      ControlKind::Other,
+    ] {
+      assert!(kinds.contains(&kind), "{:?} not covered by seed devices", kind);
+    }
+    assert!(max_layers >= 3);
   }
 }
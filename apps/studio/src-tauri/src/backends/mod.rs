@@ -1,2 +1,9 @@
 pub mod r#trait;
+pub mod capabilities;
+pub mod conflict;
 pub mod mock;
+pub mod nonblocking;
+pub mod operation;
+pub mod registry;
+pub mod simulated;
+pub mod verify;
@@ -0,0 +1,348 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+use crate::models::{
+  binding::BindingEntry,
+  bundle::ProfileBundle,
+  device::{DeviceInfo, DeviceState},
+  script::{DelayClass, Script},
+  state::MutationGuard,
+  verify::{MemoryRegion, VerifyReport},
+};
+
+use super::{operation::OperationContext, r#trait::DeviceBackend, verify};
+use crate::scripts::library::{BindingLocation, DanglingRefs};
+
+/// Async face of `DeviceBackend` for Tauri commands. Calls must not block the
+/// runtime and give up after a timeout.
+#[async_trait]
+pub trait AsyncDeviceBackend {
+  async fn list_devices(&self) -> tauri::Result<Vec<DeviceInfo>>;
+  async fn open_session(&self, device_id: String, ctx: OperationContext) -> tauri::Result<ProfileBundle>;
+  async fn close_session(&self, session_id: String) -> tauri::Result<()>;
+  async fn set_binding(&self, session_id: String, req: BindingEntry, guard: MutationGuard) -> tauri::Result<()>;
+  async fn apply_to_ram(&self, session_id: String, guard: MutationGuard, ctx: OperationContext) -> tauri::Result<()>;
+  async fn revert_ram(&self, session_id: String, guard: MutationGuard) -> tauri::Result<()>;
+  async fn commit(
+    &self,
+    session_id: String,
+    message: Option<String>,
+    guard: MutationGuard,
+    verify: bool,
+    ctx: OperationContext,
+  ) -> tauri::Result<()>;
+  async fn commit_history(&self, session_id: String) -> tauri::Result<Vec<DeviceState>>;
+  async fn verify(&self, session_id: String, region: MemoryRegion, ctx: OperationContext) -> tauri::Result<VerifyReport>;
+  /// Not subject to the call timeout: scripts may loop until stopped.
+  async fn run(&self, session_id: String, script_id: String, ctx: OperationContext) -> tauri::Result<()>;
+  async fn stop_all(&self, session_id: String) -> tauri::Result<()>;
+  async fn promote_inline(
+    &self,
+    session_id: String,
+    layer_id: i32,
+    target_id: String,
+    name: String,
+    guard: MutationGuard,
+  ) -> tauri::Result<Script>;
+  async fn inline_script_ref(&self, session_id: String, layer_id: i32, target_id: String, guard: MutationGuard) -> tauri::Result<usize>;
+  async fn list_scripts(&self, session_id: String) -> tauri::Result<Vec<Script>>;
+  async fn create_script(&self, session_id: String, script: Script) -> tauri::Result<Script>;
+  async fn update_script(&self, session_id: String, script: Script) -> tauri::Result<()>;
+  async fn delete_script(
+    &self,
+    session_id: String,
+    script_id: String,
+    dangling: DanglingRefs,
+    guard: MutationGuard,
+  ) -> tauri::Result<Vec<BindingLocation>>;
+  async fn list_delay_classes(&self, session_id: String) -> tauri::Result<Vec<DelayClass>>;
+  async fn set_delay_classes(&self, session_id: String, classes: Vec<DelayClass>) -> tauri::Result<()>;
+}
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs a synchronous backend on the blocking thread pool. A call that times
+/// out, or whose future is dropped, cancels its `OperationContext` so the
+/// worker stops at its next checkpoint.
+#[derive(Clone)]
+pub struct BlockingAdapter {
+  inner: Arc<dyn DeviceBackend + Send + Sync>,
+  timeout: Duration,
+}
+
+impl BlockingAdapter {
+  pub fn new(inner: Arc<dyn DeviceBackend + Send + Sync>) -> Self {
+    Self {
+      inner,
+      timeout: DEFAULT_TIMEOUT,
+    }
+  }
+
+  pub fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  async fn call<T, F>(&self, op: &str, ctx: OperationContext, f: F) -> tauri::Result<T>
+  where
+    T: Send + 'static,
+    F: FnOnce(&dyn DeviceBackend, &OperationContext) -> tauri::Result<T> + Send + 'static,
+  {
+    self.call_within(op, Some(self.timeout), ctx, f).await
+  }
+
+  /// `call` with an explicit timeout; `None` waits until the worker returns
+  /// or the caller drops the future.
+  async fn call_within<T, F>(&self, op: &str, timeout: Option<Duration>, ctx: OperationContext, f: F) -> tauri::Result<T>
+  where
+    T: Send + 'static,
+    F: FnOnce(&dyn DeviceBackend, &OperationContext) -> tauri::Result<T> + Send + 'static,
+  {
+    let inner = self.inner.clone();
+    let worker_ctx = ctx.clone();
+    let cancel_on_drop = CancelOnDrop(Some(ctx.clone()));
+    let task = tokio::task::spawn_blocking(move || f(inner.as_ref(), &worker_ctx));
+    let joined = match timeout {
+      Some(timeout) => tokio::time::timeout(timeout, task).await.map_err(|_| timeout),
+      None => Ok(task.await),
+    };
+    let result = match joined {
+      Ok(Ok(result)) => result,
+      Ok(Err(e)) => Err(anyhow!("{op} worker failed: {e}").into()),
+      Err(timeout) => {
+        ctx.cancel();
+        Err(anyhow!("{op} timed out after {:?}", timeout).into())
+      }
+    };
+    cancel_on_drop.disarm();
+    result
+  }
+}
+
+struct CancelOnDrop(Option<OperationContext>);
+
+impl CancelOnDrop {
+  fn disarm(mut self) {
+    self.0 = None;
+  }
+}
+
+impl Drop for CancelOnDrop {
+  fn drop(&mut self) {
+    if let Some(ctx) = &self.0 {
+      ctx.cancel();
+    }
+  }
+}
+
+#[async_trait]
+impl AsyncDeviceBackend for BlockingAdapter {
+  async fn list_devices(&self) -> tauri::Result<Vec<DeviceInfo>> {
+    self.call("list_devices", OperationContext::new(), |b, _| b.list_devices()).await
+  }
+
+  async fn open_session(&self, device_id: String, ctx: OperationContext) -> tauri::Result<ProfileBundle> {
+    self.call("open_session", ctx, move |b, ctx| b.open_session(device_id, ctx)).await
+  }
+
+  async fn close_session(&self, session_id: String) -> tauri::Result<()> {
+    self.call("close_session", OperationContext::new(), move |b, _| b.close_session(session_id)).await
+  }
+
+  async fn set_binding(&self, session_id: String, req: BindingEntry, guard: MutationGuard) -> tauri::Result<()> {
+    self.call("set_binding", OperationContext::new(), move |b, _| b.set_binding(session_id, req, guard)).await
+  }
+
+  async fn apply_to_ram(&self, session_id: String, guard: MutationGuard, ctx: OperationContext) -> tauri::Result<()> {
+    self.call("apply_to_ram", ctx, move |b, ctx| b.apply_to_ram(session_id, guard, ctx)).await
+  }
+
+  async fn revert_ram(&self, session_id: String, guard: MutationGuard) -> tauri::Result<()> {
+    self.call("revert_ram", OperationContext::new(), move |b, _| b.revert_ram(session_id, guard)).await
+  }
+
+  async fn commit(
+    &self,
+    session_id: String,
+    message: Option<String>,
+    guard: MutationGuard,
+    verify: bool,
+    ctx: OperationContext,
+  ) -> tauri::Result<()> {
+    self.call("commit", ctx, move |b, ctx| b.commit(session_id, message, guard, verify, ctx)).await
+  }
+
+  async fn commit_history(&self, session_id: String) -> tauri::Result<Vec<DeviceState>> {
+    self.call("commit_history", OperationContext::new(), move |b, _| b.commit_history(session_id)).await
+  }
+
+  async fn verify(&self, session_id: String, region: MemoryRegion, ctx: OperationContext) -> tauri::Result<VerifyReport> {
+    self.call("verify", ctx, move |b, ctx| verify::verify(b, session_id, region, ctx)).await
+  }
+
+  async fn run(&self, session_id: String, script_id: String, ctx: OperationContext) -> tauri::Result<()> {
+    self
+      .call_within("run", None, ctx, move |b, ctx| b.run(session_id, script_id, ctx))
+      .await
+  }
+
+  async fn stop_all(&self, session_id: String) -> tauri::Result<()> {
+    self.call("stop_all", OperationContext::new(), move |b, _| b.stop_all(session_id)).await
+  }
+
+  async fn promote_inline(
+    &self,
+    session_id: String,
+    layer_id: i32,
+    target_id: String,
+    name: String,
+    guard: MutationGuard,
+  ) -> tauri::Result<Script> {
+    self
+      .call("promote_inline", OperationContext::new(), move |b, _| {
+        b.promote_inline(session_id, layer_id, target_id, name, guard)
+      })
+      .await
+  }
+
+  async fn inline_script_ref(&self, session_id: String, layer_id: i32, target_id: String, guard: MutationGuard) -> tauri::Result<usize> {
+    self
+      .call("inline_script_ref", OperationContext::new(), move |b, _| {
+        b.inline_script_ref(session_id, layer_id, target_id, guard)
+      })
+      .await
+  }
+
+  async fn list_scripts(&self, session_id: String) -> tauri::Result<Vec<Script>> {
+    self.call("list_scripts", OperationContext::new(), move |b, _| b.list_scripts(session_id)).await
+  }
+
+  async fn create_script(&self, session_id: String, script: Script) -> tauri::Result<Script> {
+    self.call("create_script", OperationContext::new(), move |b, _| b.create_script(session_id, script)).await
+  }
+
+  async fn update_script(&self, session_id: String, script: Script) -> tauri::Result<()> {
+    self.call("update_script", OperationContext::new(), move |b, _| b.update_script(session_id, script)).await
+  }
+
+  async fn delete_script(
+    &self,
+    session_id: String,
+    script_id: String,
+    dangling: DanglingRefs,
+    guard: MutationGuard,
+  ) -> tauri::Result<Vec<BindingLocation>> {
+    self
+      .call("delete_script", OperationContext::new(), move |b, _| {
+        b.delete_script(session_id, script_id, dangling, guard)
+      })
+      .await
+  }
+
+  async fn list_delay_classes(&self, session_id: String) -> tauri::Result<Vec<DelayClass>> {
+    self.call("list_delay_classes", OperationContext::new(), move |b, _| b.list_delay_classes(session_id)).await
+  }
+
+  async fn set_delay_classes(&self, session_id: String, classes: Vec<DelayClass>) -> tauri::Result<()> {
+    self.call("set_delay_classes", OperationContext::new(), move |b, _| b.set_delay_classes(session_id, classes)).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{seed_root, TempDir};
+  use crate::backends::mock::MockBackend;
+  use std::sync::Mutex;
+
+  #[tokio::test]
+  async fn reports_progress_and_cancels_on_timeout() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("nonblocking-test");
+    let backend = MockBackend::new(seed_root, data_root.to_path_buf()).with_latency(Duration::from_millis(40));
+    let adapter = BlockingAdapter::new(Arc::new(backend));
+
+    let device_id = adapter.list_devices().await.expect("devices")[0].id.clone();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    let ctx = OperationContext::new().with_progress(move |p| sink.lock().unwrap().push(p.done));
+    let bundle = adapter.open_session(device_id.clone(), ctx).await.expect("open session");
+    let layers = bundle.profile.layers.len() as u32;
+    assert_eq!(*seen.lock().unwrap(), (1..=layers).collect::<Vec<_>>());
+
+    let slow = adapter.clone().with_timeout(Duration::from_millis(10));
+    let ctx = OperationContext::new();
+    let err = slow.open_session(device_id, ctx.clone()).await.expect_err("timed out");
+    assert!(err.to_string().contains("timed out"), "{err}");
+    assert!(ctx.is_cancelled());
+  }
+
+  #[tokio::test]
+  async fn run_outlives_the_call_timeout_until_stopped() {
+    use crate::models::script::{ExecutionTarget, Step};
+
+    let seed_root = seed_root();
+    let data_root = TempDir::new("nonblocking-test");
+    let adapter = BlockingAdapter::new(Arc::new(MockBackend::new(seed_root, data_root.to_path_buf())))
+      .with_timeout(Duration::from_millis(20));
+
+    let device_id = adapter.list_devices().await.expect("devices")[0].id.clone();
+    let session_id = adapter.open_session(device_id, OperationContext::new()).await.expect("open").session_id;
+    let wait = Step { id: 2, name: "Wait".into(), op: "WAIT".into(), arg: Some("5".into()), ..Default::default() };
+    let long = Step { id: 1, name: "Loop".into(), op: "REPEAT".into(), arg: Some("10000".into()), steps: vec![wait], ..Default::default() };
+    let script = Script {
+      id: String::new(),
+      profile_id: String::new(),
+      name: "Long".into(),
+      target: ExecutionTarget::Host,
+      steps: vec![long],
+      meta: None,
+    };
+    let script = adapter.create_script(session_id.clone(), script).await.expect("create");
+
+    let ctx = OperationContext::new();
+    let running = {
+      let adapter = adapter.clone();
+      let (session_id, ctx) = (session_id.clone(), ctx.clone());
+      tokio::spawn(async move { adapter.run(session_id, script.id, ctx).await })
+    };
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert!(!running.is_finished(), "run stopped at the call timeout");
+    assert!(!ctx.is_cancelled());
+
+    adapter.stop_all(session_id).await.expect("stop all");
+    let err = running.await.unwrap().expect_err("stopped run");
+    assert!(err.to_string().contains("cancelled"), "{err}");
+    assert!(ctx.is_cancelled());
+  }
+
+  #[tokio::test]
+  async fn commit_that_times_out_records_nothing() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("nonblocking-test");
+    let backend = MockBackend::new(seed_root, data_root.to_path_buf()).with_latency(Duration::from_millis(30));
+    let adapter = BlockingAdapter::new(Arc::new(backend));
+
+    let device_id = adapter.list_devices().await.expect("devices")[0].id.clone();
+    let session_id = adapter.open_session(device_id, OperationContext::new()).await.expect("open").session_id;
+
+    // Times out while the first layer is still being written.
+    let slow = adapter.clone().with_timeout(Duration::from_millis(10));
+    let err = slow
+      .commit(session_id.clone(), None, MutationGuard::default(), false, OperationContext::new())
+      .await
+      .expect_err("timed out");
+    assert!(err.to_string().contains("timed out"), "{err}");
+
+    // Give the worker time to run past where it would have recorded the commit.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(adapter.commit_history(session_id.clone()).await.expect("history").is_empty());
+    adapter
+      .commit(session_id.clone(), None, MutationGuard::default(), false, OperationContext::new())
+      .await
+      .expect("commit from the same base");
+    assert_eq!(adapter.commit_history(session_id).await.expect("history").len(), 1);
+  }
+}
@@ -0,0 +1,194 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+  },
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Event carrying `OperationProgress` for every tracked operation.
+pub const OPERATION_PROGRESS: &str = "operation-progress";
+
+/// How far a long device operation has got.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+  pub phase: String,
+  pub done: u32,
+  pub total: u32,
+}
+
+type ProgressSink = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Handed to long-running backend calls so they can report progress and
+/// stop early once cancelled. Clones share the same cancellation flag.
+#[derive(Clone, Default)]
+pub struct OperationContext {
+  cancelled: Arc<AtomicBool>,
+  /// Flags of the contexts this one was made a `child` of.
+  parents: Vec<Arc<AtomicBool>>,
+  progress: Option<ProgressSink>,
+}
+
+impl OperationContext {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_progress(mut self, sink: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+    self.progress = Some(Arc::new(sink));
+    self
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::SeqCst);
+  }
+
+  /// A context cancelled along with this one that can also be cancelled on
+  /// its own, without reporting progress.
+  pub fn child(&self) -> OperationContext {
+    let mut parents = self.parents.clone();
+    parents.push(self.cancelled.clone());
+    OperationContext { cancelled: Arc::new(AtomicBool::new(false)), parents, progress: None }
+  }
+
+  /// Whether both handles control the same operation.
+  pub fn same_as(&self, other: &OperationContext) -> bool {
+    Arc::ptr_eq(&self.cancelled, &other.cancelled)
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::SeqCst) || self.parents.iter().any(|p| p.load(Ordering::SeqCst))
+  }
+
+  /// Fails with `Cancelled` once `cancel` has been called.
+  pub fn check_cancelled(&self) -> Result<(), Cancelled> {
+    if self.is_cancelled() {
+      Err(Cancelled)
+    } else {
+      Ok(())
+    }
+  }
+
+  pub fn report(&self, phase: &str, done: u32, total: u32) {
+    if let Some(sink) = &self.progress {
+      sink(&Progress {
+        phase: phase.to_string(),
+        done,
+        total,
+      });
+    }
+  }
+}
+
+/// Returned by operations that stopped because their context was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Operation cancelled")
+  }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Payload of `OPERATION_PROGRESS`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationProgress {
+  #[serde(rename = "operationId")]
+  pub operation_id: String,
+  pub kind: String,
+  pub phase: String,
+  pub done: u32,
+  pub total: u32,
+}
+
+/// In-flight operations by id, so they can be cancelled from another command.
+#[derive(Default)]
+pub struct OperationRegistry {
+  running: Mutex<HashMap<String, OperationContext>>,
+}
+
+impl OperationRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Tracks `ctx` under `requested_id`, or a fresh id, and returns the id.
+  pub fn start(&self, requested_id: Option<String>, ctx: &OperationContext) -> String {
+    let id = requested_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut guard = self.running.lock().unwrap();
+    guard.insert(id.clone(), ctx.clone());
+    id
+  }
+
+  pub fn finish(&self, id: &str) {
+    let mut guard = self.running.lock().unwrap();
+    guard.remove(id);
+  }
+
+  /// Cancels a running operation. Returns `false` if it is unknown or already done.
+  pub fn cancel(&self, id: &str) -> bool {
+    let guard = self.running.lock().unwrap();
+    match guard.get(id) {
+      Some(ctx) => {
+        ctx.cancel();
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{seed_root, TempDir};
+  use crate::backends::{mock::MockBackend, r#trait::DeviceBackend};
+  use crate::models::state::MutationGuard;
+
+  #[test]
+  fn cancelled_commit_leaves_state_untouched() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("operation-test");
+    let backend = MockBackend::new(seed_root, data_root.to_path_buf());
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let session_id = backend
+      .open_session(device_id, &OperationContext::new())
+      .expect("open session")
+      .session_id;
+
+    let registry = OperationRegistry::new();
+    let phases = Arc::new(Mutex::new(Vec::new()));
+    let sink = phases.clone();
+    let ctx = OperationContext::new().with_progress(move |p| sink.lock().unwrap().push(p.phase.clone()));
+    let id = registry.start(Some("op-1".to_string()), &ctx);
+    backend
+      .apply_to_ram(session_id.clone(), MutationGuard::default(), &ctx)
+      .expect("apply");
+    assert!(phases.lock().unwrap().iter().all(|p| p == "write-keymap"));
+
+    assert!(registry.cancel(&id));
+    let err = backend
+      .commit(session_id.clone(), None, MutationGuard::default(), false, &ctx)
+      .expect_err("cancelled");
+    assert!(err.to_string().contains("cancelled"), "{err}");
+    assert!(backend.commit_history(session_id).expect("history").is_empty());
+
+    registry.finish(&id);
+    assert!(!registry.cancel(&id));
+
+    let parent = OperationContext::new();
+    let (first, second) = (parent.child(), parent.child());
+    let grandchild = first.child();
+    first.cancel();
+    assert!(first.is_cancelled() && grandchild.is_cancelled());
+    assert!(!parent.is_cancelled() && !second.is_cancelled());
+    parent.cancel();
+    assert!(second.is_cancelled());
+  }
+}
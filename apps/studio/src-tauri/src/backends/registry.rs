@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::models::{
+  binding::BindingEntry,
+  bundle::ProfileBundle,
+  device::{DeviceInfo, DeviceProbe, DeviceState},
+  script::{DelayClass, Script},
+  state::{MutationGuard, SessionState},
+  verify::MemoryRegion,
+};
+
+use super::{
+  operation::OperationContext,
+  r#trait::{DeviceBackend, UnknownSession},
+};
+use crate::scripts::library::{BindingLocation, DanglingRefs};
+use anyhow::anyhow;
+
+struct RegisteredBackend {
+  transport: String,
+  backend: Arc<dyn DeviceBackend + Send + Sync>,
+}
+
+/// Fans `DeviceBackend` calls out to several backends. Devices are routed by
+/// `DeviceInfo.transport`; sessions stick to the backend that opened them.
+#[derive(Default)]
+pub struct BackendRegistry {
+  backends: Vec<RegisteredBackend>,
+  sessions: Mutex<HashMap<String, usize>>,
+}
+
+impl BackendRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers `backend` as the owner of `transport`, replacing any previous owner.
+  pub fn register(&mut self, transport: &str, backend: Arc<dyn DeviceBackend + Send + Sync>) {
+    self.backends.retain(|b| b.transport != transport);
+    self.backends.push(RegisteredBackend {
+      transport: transport.to_string(),
+      backend,
+    });
+  }
+
+  pub fn transports(&self) -> Vec<String> {
+    self.backends.iter().map(|b| b.transport.clone()).collect()
+  }
+
+  fn index_for_transport(&self, transport: &str) -> Option<usize> {
+    self.backends.iter().position(|b| b.transport == transport)
+  }
+
+  /// Merged device list, each device paired with the backend it routes to.
+  fn devices_with_owner(&self) -> Vec<(DeviceInfo, usize)> {
+    let mut devices: Vec<(DeviceInfo, usize)> = Vec::new();
+    for (index, entry) in self.backends.iter().enumerate() {
+      let listed = match entry.backend.list_devices() {
+        Ok(listed) => listed,
+        Err(e) => {
+          log::warn!("list_devices failed for {} backend: {}", entry.transport, e);
+          continue;
+        }
+      };
+      for device in listed {
+        if devices.iter().any(|(d, _)| d.id == device.id) {
+          log::warn!("Device {} listed by more than one backend, keeping the first", device.id);
+          continue;
+        }
+        let owner = self.index_for_transport(&device.transport).unwrap_or(index);
+        devices.push((device, owner));
+      }
+    }
+    devices
+  }
+
+  fn owner_of(&self, device_id: &str) -> anyhow::Result<usize> {
+    self
+      .devices_with_owner()
+      .into_iter()
+      .find(|(d, _)| d.id == device_id)
+      .map(|(_, index)| index)
+      .ok_or_else(|| anyhow!("Unknown device {}", device_id))
+  }
+
+  fn backend_for_session(&self, session_id: &str) -> anyhow::Result<&(dyn DeviceBackend + Send + Sync)> {
+    let guard = self.sessions.lock().unwrap();
+    let index = guard
+      .get(session_id)
+      .copied()
+      .ok_or(UnknownSession)?;
+    Ok(self.backends[index].backend.as_ref())
+  }
+}
+
+impl DeviceBackend for BackendRegistry {
+  fn list_devices(&self) -> tauri::Result<Vec<DeviceInfo>> {
+    Ok(self.devices_with_owner().into_iter().map(|(d, _)| d).collect())
+  }
+
+  fn probe(&self, device_id: String) -> tauri::Result<Option<DeviceProbe>> {
+    let index = self.owner_of(&device_id)?;
+    self.backends[index].backend.probe(device_id)
+  }
+
+  fn open_session(&self, device_id: String, ctx: &OperationContext) -> tauri::Result<ProfileBundle> {
+    let index = self.owner_of(&device_id)?;
+    let bundle = self.backends[index].backend.open_session(device_id, ctx)?;
+    let mut guard = self.sessions.lock().unwrap();
+    guard.insert(bundle.session_id.clone(), index);
+    Ok(bundle)
+  }
+
+  fn close_session(&self, session_id: String) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.close_session(session_id.clone())?;
+    let mut guard = self.sessions.lock().unwrap();
+    guard.remove(&session_id);
+    Ok(())
+  }
+
+  fn set_binding(&self, session_id: String, req: BindingEntry, guard: MutationGuard) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.set_binding(session_id, req, guard)
+  }
+
+  fn apply_to_ram(&self, session_id: String, guard: MutationGuard, ctx: &OperationContext) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.apply_to_ram(session_id, guard, ctx)
+  }
+
+  fn revert_ram(&self, session_id: String, guard: MutationGuard) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.revert_ram(session_id, guard)
+  }
+
+  fn commit(
+    &self,
+    session_id: String,
+    message: Option<String>,
+    guard: MutationGuard,
+    verify: bool,
+    ctx: &OperationContext,
+  ) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.commit(session_id, message, guard, verify, ctx)
+  }
+
+  fn commit_history(&self, session_id: String) -> tauri::Result<Vec<DeviceState>> {
+    self.backend_for_session(&session_id)?.commit_history(session_id)
+  }
+
+  fn session_state(&self, session_id: String) -> tauri::Result<SessionState> {
+    self.backend_for_session(&session_id)?.session_state(session_id)
+  }
+
+  fn read_back(&self, session_id: String, region: MemoryRegion, ctx: &OperationContext) -> tauri::Result<DeviceState> {
+    self.backend_for_session(&session_id)?.read_back(session_id, region, ctx)
+  }
+
+  fn run(&self, session_id: String, script_id: String, ctx: &OperationContext) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.run(session_id, script_id, ctx)
+  }
+
+  fn stop_all(&self, session_id: String) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.stop_all(session_id)
+  }
+
+  fn promote_inline(
+    &self,
+    session_id: String,
+    layer_id: i32,
+    target_id: String,
+    name: String,
+    guard: MutationGuard,
+  ) -> tauri::Result<Script> {
+    self.backend_for_session(&session_id)?.promote_inline(session_id, layer_id, target_id, name, guard)
+  }
+
+  fn inline_script_ref(&self, session_id: String, layer_id: i32, target_id: String, guard: MutationGuard) -> tauri::Result<usize> {
+    self.backend_for_session(&session_id)?.inline_script_ref(session_id, layer_id, target_id, guard)
+  }
+
+  fn list_scripts(&self, session_id: String) -> tauri::Result<Vec<Script>> {
+    self.backend_for_session(&session_id)?.list_scripts(session_id)
+  }
+
+  fn create_script(&self, session_id: String, script: Script) -> tauri::Result<Script> {
+    self.backend_for_session(&session_id)?.create_script(session_id, script)
+  }
+
+  fn update_script(&self, session_id: String, script: Script) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.update_script(session_id, script)
+  }
+
+  fn delete_script(
+    &self,
+    session_id: String,
+    script_id: String,
+    dangling: DanglingRefs,
+    guard: MutationGuard,
+  ) -> tauri::Result<Vec<BindingLocation>> {
+    self.backend_for_session(&session_id)?.delete_script(session_id, script_id, dangling, guard)
+  }
+
+  fn list_delay_classes(&self, session_id: String) -> tauri::Result<Vec<DelayClass>> {
+    self.backend_for_session(&session_id)?.list_delay_classes(session_id)
+  }
+
+  fn set_delay_classes(&self, session_id: String, classes: Vec<DelayClass>) -> tauri::Result<()> {
+    self.backend_for_session(&session_id)?.set_delay_classes(session_id, classes)
+  }
+
+  /// Asks every backend, even after one fails, and always forgets the
+  /// routes of sessions that were dropped before reporting failures.
+  fn invalidate_device(&self, device_id: String) -> tauri::Result<Vec<String>> {
+    // The device is usually gone from every listing by now, so ask all backends.
+    let mut dropped = Vec::new();
+    let mut failures = Vec::new();
+    for entry in &self.backends {
+      match entry.backend.invalidate_device(device_id.clone()) {
+        Ok(sessions) => dropped.extend(sessions),
+        Err(e) => failures.push(format!("{}: {}", entry.transport, e)),
+      }
+    }
+    {
+      let mut guard = self.sessions.lock().unwrap();
+      for session_id in &dropped {
+        guard.remove(session_id);
+      }
+    }
+    if failures.is_empty() {
+      return Ok(dropped);
+    }
+    Err(anyhow!("Invalidate failed for {} backend(s): {}", failures.len(), failures.join("; ")).into())
+  }
+
+  /// Flushes every backend, even after one fails, and reports all failures.
+  fn flush(&self) -> tauri::Result<()> {
+    let failures: Vec<String> = self
+      .backends
+      .iter()
+      .filter_map(|entry| entry.backend.flush().err().map(|e| format!("{}: {}", entry.transport, e)))
+      .collect();
+    if failures.is_empty() {
+      return Ok(());
+    }
+    Err(anyhow!("Flush failed for {} backend(s): {}", failures.len(), failures.join("; ")).into())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{seed_root, TempDir};
+  use crate::backends::simulated::SimulatedBackend;
+  use crate::models::binding::Binding;
+
+  #[test]
+  fn routes_sessions_by_transport() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("registry-test");
+    let mock = Arc::new(SimulatedBackend::new(seed_root.clone(), data_root.join("mock")));
+    let via = Arc::new(SimulatedBackend::new(seed_root, data_root.join("via")));
+
+    let mut device = via.available_devices().expect("devices")[0].clone();
+    device.transport = "via".to_string();
+    via.attach(device.clone());
+
+    let mut registry = BackendRegistry::new();
+    registry.register("mock", mock.clone());
+    registry.register("via", via.clone());
+
+    let devices = registry.list_devices().expect("devices");
+    assert_eq!(devices.len(), 1);
+
+    let session_id = registry.open_session(device.id.clone(), &OperationContext::new()).expect("open").session_id;
+    let entry = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:0,1".to_string(),
+      binding: Binding::None,
+    };
+    registry
+      .set_binding(session_id.clone(), entry.clone(), MutationGuard::default())
+      .expect("routed set_binding");
+    assert!(via.set_binding(session_id.clone(), entry.clone(), MutationGuard::default()).is_ok());
+    assert!(mock.set_binding(session_id.clone(), entry, MutationGuard::default()).is_err());
+    assert!(registry.open_session("missing".to_string(), &OperationContext::new()).is_err());
+  }
+}
@@ -0,0 +1,182 @@
+use std::sync::Mutex;
+
+use crate::models::{
+  binding::BindingEntry,
+  bundle::ProfileBundle,
+  device::{DeviceInfo, DeviceProbe, DeviceState},
+  script::{DelayClass, Script},
+  state::{MutationGuard, SessionState},
+  verify::MemoryRegion,
+};
+
+use super::{mock::MockBackend, operation::OperationContext, r#trait::DeviceBackend};
+use crate::scripts::library::{BindingLocation, DanglingRefs};
+use anyhow::anyhow;
+
+/// Mock backend whose device list is driven by explicit attach/detach calls,
+/// so hot-plug handling can be exercised without hardware.
+pub struct SimulatedBackend {
+  inner: MockBackend,
+  attached: Mutex<Vec<DeviceInfo>>,
+}
+
+impl SimulatedBackend {
+  pub fn new(seed_root: std::path::PathBuf, data_root: std::path::PathBuf) -> Self {
+    Self {
+      inner: MockBackend::new(seed_root, data_root),
+      attached: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// Devices known to the seed data, attached or not.
+  pub fn available_devices(&self) -> tauri::Result<Vec<DeviceInfo>> {
+    self.inner.list_devices()
+  }
+
+  pub fn attach(&self, device: DeviceInfo) {
+    let mut guard = self.attached.lock().unwrap();
+    guard.retain(|d| d.id != device.id);
+    guard.push(device);
+  }
+
+  pub fn detach(&self, device_id: &str) -> Option<DeviceInfo> {
+    let mut guard = self.attached.lock().unwrap();
+    let index = guard.iter().position(|d| d.id == device_id)?;
+    Some(guard.remove(index))
+  }
+
+  /// Makes reads of `target_id` come back empty, as if the write was lost.
+  pub fn lose_writes_to(&self, target_id: &str) {
+    self.inner.lose_writes_to(target_id);
+  }
+
+  pub fn clear_faults(&self) {
+    self.inner.clear_faults();
+  }
+
+  fn is_attached(&self, device_id: &str) -> bool {
+    let guard = self.attached.lock().unwrap();
+    guard.iter().any(|d| d.id == device_id)
+  }
+}
+
+impl DeviceBackend for SimulatedBackend {
+  fn list_devices(&self) -> tauri::Result<Vec<DeviceInfo>> {
+    let guard = self.attached.lock().unwrap();
+    Ok(guard.clone())
+  }
+
+  fn probe(&self, device_id: String) -> tauri::Result<Option<DeviceProbe>> {
+    if !self.is_attached(&device_id) {
+      return Err(anyhow!("Device {} is not attached", device_id).into());
+    }
+    self.inner.probe(device_id)
+  }
+
+  fn open_session(&self, device_id: String, ctx: &OperationContext) -> tauri::Result<ProfileBundle> {
+    if !self.is_attached(&device_id) {
+      return Err(anyhow!("Device {} is not attached", device_id).into());
+    }
+    self.inner.open_session(device_id, ctx)
+  }
+
+  fn close_session(&self, session_id: String) -> tauri::Result<()> {
+    self.inner.close_session(session_id)
+  }
+
+  fn set_binding(&self, session_id: String, req: BindingEntry, guard: MutationGuard) -> tauri::Result<()> {
+    self.inner.set_binding(session_id, req, guard)
+  }
+
+  fn apply_to_ram(&self, session_id: String, guard: MutationGuard, ctx: &OperationContext) -> tauri::Result<()> {
+    self.inner.apply_to_ram(session_id, guard, ctx)
+  }
+
+  fn revert_ram(&self, session_id: String, guard: MutationGuard) -> tauri::Result<()> {
+    self.inner.revert_ram(session_id, guard)
+  }
+
+  fn commit(
+    &self,
+    session_id: String,
+    message: Option<String>,
+    guard: MutationGuard,
+    verify: bool,
+    ctx: &OperationContext,
+  ) -> tauri::Result<()> {
+    self.inner.commit(session_id, message, guard, verify, ctx)
+  }
+
+  fn commit_history(&self, session_id: String) -> tauri::Result<Vec<DeviceState>> {
+    self.inner.commit_history(session_id)
+  }
+
+  fn session_state(&self, session_id: String) -> tauri::Result<SessionState> {
+    self.inner.session_state(session_id)
+  }
+
+  fn read_back(&self, session_id: String, region: MemoryRegion, ctx: &OperationContext) -> tauri::Result<DeviceState> {
+    self.inner.read_back(session_id, region, ctx)
+  }
+
+  fn run(&self, session_id: String, script_id: String, ctx: &OperationContext) -> tauri::Result<()> {
+    self.inner.run(session_id, script_id, ctx)
+  }
+
+  fn stop_all(&self, session_id: String) -> tauri::Result<()> {
+    self.inner.stop_all(session_id)
+  }
+
+  fn promote_inline(
+    &self,
+    session_id: String,
+    layer_id: i32,
+    target_id: String,
+    name: String,
+    guard: MutationGuard,
+  ) -> tauri::Result<Script> {
+    self.inner.promote_inline(session_id, layer_id, target_id, name, guard)
+  }
+
+  fn inline_script_ref(&self, session_id: String, layer_id: i32, target_id: String, guard: MutationGuard) -> tauri::Result<usize> {
+    self.inner.inline_script_ref(session_id, layer_id, target_id, guard)
+  }
+
+  fn list_scripts(&self, session_id: String) -> tauri::Result<Vec<Script>> {
+    self.inner.list_scripts(session_id)
+  }
+
+  fn create_script(&self, session_id: String, script: Script) -> tauri::Result<Script> {
+    self.inner.create_script(session_id, script)
+  }
+
+  fn update_script(&self, session_id: String, script: Script) -> tauri::Result<()> {
+    self.inner.update_script(session_id, script)
+  }
+
+  fn delete_script(
+    &self,
+    session_id: String,
+    script_id: String,
+    dangling: DanglingRefs,
+    guard: MutationGuard,
+  ) -> tauri::Result<Vec<BindingLocation>> {
+    self.inner.delete_script(session_id, script_id, dangling, guard)
+  }
+
+  fn list_delay_classes(&self, session_id: String) -> tauri::Result<Vec<DelayClass>> {
+    self.inner.list_delay_classes(session_id)
+  }
+
+  fn set_delay_classes(&self, session_id: String, classes: Vec<DelayClass>) -> tauri::Result<()> {
+    self.inner.set_delay_classes(session_id, classes)
+  }
+
+  fn invalidate_device(&self, device_id: String) -> tauri::Result<Vec<String>> {
+    self.inner.invalidate_device(device_id)
+  }
+
+  fn flush(&self) -> tauri::Result<()> {
+    self.inner.flush()
+  }
+}
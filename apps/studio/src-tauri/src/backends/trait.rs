@@ -1,17 +1,92 @@
+use std::fmt;
+
+use super::operation::OperationContext;
+use crate::scripts::library::{BindingLocation, DanglingRefs};
 use crate::models::{
   bundle::ProfileBundle,
   binding::BindingEntry,
-  device::DeviceInfo,
+  device::{DeviceInfo, DeviceProbe, DeviceState},
+  script::{DelayClass, Script},
+  state::{MutationGuard, SessionState},
+  verify::MemoryRegion,
 };
 
 pub trait DeviceBackend {
   fn list_devices(&self) -> tauri::Result<Vec<DeviceInfo>>;
-  fn open_session(&self, device_id: String) -> tauri::Result<ProfileBundle>;
+  /// Asks the device for its protocol version and limits. `None` when the
+  /// transport has nothing to probe.
+  fn probe(&self, device_id: String) -> tauri::Result<Option<DeviceProbe>>;
+  /// Reads the device's current state. Long reads report progress through
+  /// `ctx` and stop once it is cancelled.
+  fn open_session(&self, device_id: String, ctx: &OperationContext) -> tauri::Result<ProfileBundle>;
   fn close_session(&self, session_id: String) -> tauri::Result<()>;
-  fn set_binding(&self, session_id: String, req: BindingEntry) -> tauri::Result<()>;
-  fn apply_to_ram(&self, session_id: String) -> tauri::Result<()>;
-  fn revert_ram(&self, session_id: String) -> tauri::Result<()>;
-  fn commit(&self, session_id: String) -> tauri::Result<()>;
-  fn run(&self, session_id: String, script_id: String) -> tauri::Result<()>;
+  fn set_binding(&self, session_id: String, req: BindingEntry, guard: MutationGuard) -> tauri::Result<()>;
+  fn apply_to_ram(&self, session_id: String, guard: MutationGuard, ctx: &OperationContext) -> tauri::Result<()>;
+  fn revert_ram(&self, session_id: String, guard: MutationGuard) -> tauri::Result<()>;
+  /// Writes the applied keymap to EEPROM. With `verify`, it is read back
+  /// first and the commit fails, leaving nothing recorded, if a key differs.
+  fn commit(
+    &self,
+    session_id: String,
+    message: Option<String>,
+    guard: MutationGuard,
+    verify: bool,
+    ctx: &OperationContext,
+  ) -> tauri::Result<()>;
+  fn commit_history(&self, session_id: String) -> tauri::Result<Vec<DeviceState>>;
+  /// The staged/applied/committed states the backend holds for the session.
+  fn session_state(&self, session_id: String) -> tauri::Result<SessionState>;
+  /// Reads the keymap currently held in `region` back from the device.
+  fn read_back(&self, session_id: String, region: MemoryRegion, ctx: &OperationContext) -> tauri::Result<DeviceState>;
+  /// Plays a script. Host scripts run until they end or `ctx` is cancelled,
+  /// by the caller or by `stop_all`.
+  fn run(&self, session_id: String, script_id: String, ctx: &OperationContext) -> tauri::Result<()>;
   fn stop_all(&self, session_id: String) -> tauri::Result<()>;
+  /// Moves the staged inline sequence on a key into a new named script and
+  /// binds the key to it.
+  fn promote_inline(
+    &self,
+    session_id: String,
+    layer_id: i32,
+    target_id: String,
+    name: String,
+    guard: MutationGuard,
+  ) -> tauri::Result<Script>;
+  /// Copies a referenced script's steps into the staged binding. Returns how
+  /// many bindings still reference the script.
+  fn inline_script_ref(&self, session_id: String, layer_id: i32, target_id: String, guard: MutationGuard) -> tauri::Result<usize>;
+  fn list_scripts(&self, session_id: String) -> tauri::Result<Vec<Script>>;
+  /// Stores a new script; an empty id is replaced with a generated one.
+  fn create_script(&self, session_id: String, script: Script) -> tauri::Result<Script>;
+  fn update_script(&self, session_id: String, script: Script) -> tauri::Result<()>;
+  /// Deletes a script, repairing staged bindings that reference it per
+  /// `dangling`. Returns the repaired bindings. Fails with `ScriptInUse`
+  /// while the applied or committed keymap still references it.
+  fn delete_script(
+    &self,
+    session_id: String,
+    script_id: String,
+    dangling: DanglingRefs,
+    guard: MutationGuard,
+  ) -> tauri::Result<Vec<BindingLocation>>;
+  fn list_delay_classes(&self, session_id: String) -> tauri::Result<Vec<DelayClass>>;
+  /// Replaces the profile's delay class table.
+  fn set_delay_classes(&self, session_id: String, classes: Vec<DelayClass>) -> tauri::Result<()>;
+  /// Drops every session bound to `device_id` and returns their ids.
+  fn invalidate_device(&self, device_id: String) -> tauri::Result<Vec<String>>;
+  /// Persists any state still held in memory.
+  fn flush(&self) -> tauri::Result<()>;
 }
+
+/// Raised for a session the backend doesn't know, such as one dropped when
+/// its device was detached.
+#[derive(Debug, Clone, Copy)]
+pub struct UnknownSession;
+
+impl fmt::Display for UnknownSession {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Unknown session")
+  }
+}
+
+impl std::error::Error for UnknownSession {}
@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::models::{
+  binding::Binding,
+  device::DeviceState,
+  verify::{KeyMismatch, MemoryRegion, VerifyReport},
+};
+
+use super::{operation::OperationContext, r#trait::DeviceBackend};
+
+/// Raised when a write was read back and did not match.
+#[derive(Debug, Clone)]
+pub struct VerifyFailed {
+  pub report: VerifyReport,
+}
+
+impl fmt::Display for VerifyFailed {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let region = match self.report.region {
+      MemoryRegion::Ram => "RAM",
+      MemoryRegion::Eeprom => "EEPROM",
+    };
+    write!(f, "{} keys did not verify in {}", self.report.mismatches.len(), region)?;
+    if let Some(first) = self.report.mismatches.first() {
+      write!(f, " (first: layer {} {})", first.layer_id, first.target_id)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for VerifyFailed {}
+
+/// Reads `region` back from the device and compares it with the session's
+/// applied (RAM) or committed (EEPROM) state.
+pub fn verify(
+  backend: &dyn DeviceBackend,
+  session_id: String,
+  region: MemoryRegion,
+  ctx: &OperationContext,
+) -> tauri::Result<VerifyReport> {
+  let state = backend.session_state(session_id.clone())?;
+  let expected = match region {
+    MemoryRegion::Ram => state.applied,
+    MemoryRegion::Eeprom => state.committed,
+  };
+  let actual = backend.read_back(session_id, region, ctx)?;
+  Ok(VerifyReport {
+    region,
+    mismatches: expected.map(|e| compare(&e, &actual)).unwrap_or_default(),
+  })
+}
+
+/// Per-key differences between two keymaps, ordered by layer and target.
+pub fn compare(expected: &DeviceState, actual: &DeviceState) -> Vec<KeyMismatch> {
+  let expected = by_key(expected);
+  let mut actual = by_key(actual);
+  let mut mismatches = Vec::new();
+  for (key, binding) in expected {
+    let found = actual.remove(&key);
+    if found.as_ref().map(json) != Some(json(&binding)) {
+      mismatches.push(KeyMismatch {
+        layer_id: key.0,
+        target_id: key.1,
+        expected: Some(binding),
+        actual: found,
+      });
+    }
+  }
+  for ((layer_id, target_id), binding) in actual {
+    mismatches.push(KeyMismatch {
+      layer_id,
+      target_id,
+      expected: None,
+      actual: Some(binding),
+    });
+  }
+  mismatches.sort_by(|a, b| (a.layer_id, &a.target_id).cmp(&(b.layer_id, &b.target_id)));
+  mismatches
+}
+
+fn by_key(state: &DeviceState) -> BTreeMap<(i32, String), Binding> {
+  state
+    .layers
+    .iter()
+    .flat_map(|l| l.bindings.iter().map(move |b| ((l.id, b.target_id.clone()), b.binding.clone())))
+    .collect()
+}
+
+fn json(binding: &Binding) -> serde_json::Value {
+  serde_json::to_value(binding).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{seed_root, TempDir};
+  use crate::backends::simulated::SimulatedBackend;
+  use crate::models::{binding::BindingEntry, state::MutationGuard};
+
+  #[test]
+  fn lost_eeprom_write_is_reported_per_key() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("verify-test");
+    let backend = SimulatedBackend::new(seed_root, data_root.to_path_buf());
+    let device = backend.available_devices().expect("devices")[0].clone();
+    backend.attach(device.clone());
+    let ctx = OperationContext::new();
+    let session_id = backend.open_session(device.id, &ctx).expect("open").session_id;
+
+    let entry = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:0,1".to_string(),
+      binding: Binding::SimpleAction {
+        action: "TAP".to_string(),
+        arg: Some("KC_TAB".to_string()),
+        meta: None,
+      },
+    };
+    backend.set_binding(session_id.clone(), entry, MutationGuard::default()).expect("set binding");
+    backend.apply_to_ram(session_id.clone(), MutationGuard::default(), &ctx).expect("apply");
+    let report = verify(&backend, session_id.clone(), MemoryRegion::Ram, &ctx).expect("verify ram");
+    assert!(report.is_ok(), "{:?}", report.mismatches);
+
+    backend.lose_writes_to("key:0,1");
+    backend.commit(session_id.clone(), None, MutationGuard::default(), false, &ctx).expect("commit");
+    let report = verify(&backend, session_id, MemoryRegion::Eeprom, &ctx).expect("verify eeprom");
+    assert_eq!(report.mismatches.len(), 1);
+    let mismatch = &report.mismatches[0];
+    assert_eq!((mismatch.layer_id, mismatch.target_id.as_str()), (1, "key:0,1"));
+    assert!(mismatch.expected.is_some() && mismatch.actual.is_none());
+    assert!(VerifyFailed { report }.to_string().contains("EEPROM"));
+  }
+}
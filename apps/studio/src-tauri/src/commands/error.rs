@@ -0,0 +1,68 @@
+use serde::Serialize;
+
+use crate::{
+  backends::{conflict::ConflictError, verify::VerifyFailed},
+  models::{state::SessionState, verify::VerifyReport},
+  scripts::library::{BindingLocation, ScriptInUse},
+};
+
+/// Error returned by mutating commands. Conflicts carry the current state so
+/// the UI can reconcile its view and retry; failed verification carries the
+/// per-key report; deleting a script that is still bound lists the bindings.
+#[derive(Debug, Serialize)]
+pub struct CommandError {
+  pub code: String,
+  pub message: String,
+  pub retryable: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub current: Option<Box<SessionState>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub report: Option<VerifyReport>,
+  #[serde(rename = "usedBy", skip_serializing_if = "Option::is_none")]
+  pub used_by: Option<Vec<BindingLocation>>,
+}
+
+impl CommandError {
+  pub fn from_backend(op: &str, err: tauri::Error) -> Self {
+    if let tauri::Error::Anyhow(e) = &err {
+      if let Some(conflict) = e.downcast_ref::<ConflictError>() {
+        return Self {
+          code: "conflict".to_string(),
+          message: format!("{op} failed: {conflict}"),
+          retryable: true,
+          current: Some(conflict.current.clone()),
+          report: None,
+          used_by: None,
+        };
+      }
+      if let Some(failed) = e.downcast_ref::<VerifyFailed>() {
+        return Self {
+          code: "verify_failed".to_string(),
+          message: format!("{op} failed: {failed}"),
+          retryable: true,
+          current: None,
+          report: Some(failed.report.clone()),
+          used_by: None,
+        };
+      }
+      if let Some(in_use) = e.downcast_ref::<ScriptInUse>() {
+        return Self {
+          code: "script_in_use".to_string(),
+          message: format!("{op} failed: {in_use}"),
+          retryable: false,
+          current: None,
+          report: None,
+          used_by: Some(in_use.used_by.clone()),
+        };
+      }
+    }
+    Self {
+      code: "backend".to_string(),
+      message: format!("{op} failed: {err}"),
+      retryable: false,
+      current: None,
+      report: None,
+      used_by: None,
+    }
+  }
+}
@@ -1 +1,7 @@
+pub mod error;
+pub mod operations;
+pub mod programs;
+pub mod scripts;
 pub mod session;
+pub mod seeds;
+pub mod triggers;
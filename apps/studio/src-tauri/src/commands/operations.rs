@@ -0,0 +1,36 @@
+use tauri::{AppHandle, Emitter, State};
+use uuid::Uuid;
+
+use crate::{
+  AppState,
+  backends::operation::{OperationContext, OperationProgress, OPERATION_PROGRESS},
+};
+
+/// Registers a cancellable operation whose progress is emitted as
+/// `OPERATION_PROGRESS`. Callers must `finish` the returned id when done.
+pub(crate) fn track(app: &AppHandle, state: &AppState, kind: &str, requested_id: Option<String>) -> (String, OperationContext) {
+  let id = requested_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+  let app = app.clone();
+  let operation_id = id.clone();
+  let kind = kind.to_string();
+  let ctx = OperationContext::new().with_progress(move |p| {
+    let payload = OperationProgress {
+      operation_id: operation_id.clone(),
+      kind: kind.clone(),
+      phase: p.phase.clone(),
+      done: p.done,
+      total: p.total,
+    };
+    if let Err(e) = app.emit(OPERATION_PROGRESS, payload) {
+      log::warn!("Failed to emit progress: {e}");
+    }
+  });
+  state.operations.start(Some(id.clone()), &ctx);
+  (id, ctx)
+}
+
+/// Returns `false` when the operation already finished or never existed.
+#[tauri::command]
+pub fn cancel_operation(state: State<AppState>, operation_id: String) -> tauri::Result<bool> {
+  Ok(state.operations.cancel(&operation_id))
+}
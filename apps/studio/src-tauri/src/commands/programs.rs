@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use tauri::{AppHandle, State};
+
+use crate::{launcher::LaunchRequest, AppState};
+
+/// Answers a `program-confirm` prompt; the result arrives as `program-finished`.
+#[tauri::command]
+pub fn confirm_program_launch(
+  app: AppHandle,
+  state: State<AppState>,
+  request_id: String,
+  allow: bool,
+  remember: Option<bool>,
+) -> tauri::Result<()> {
+  state
+    .launcher
+    .confirm(&request_id, allow, remember.unwrap_or(false), Arc::new(app))?;
+  Ok(())
+}
+
+#[tauri::command]
+pub fn pending_program_launches(state: State<AppState>) -> tauri::Result<Vec<LaunchRequest>> {
+  Ok(state.launcher.pending())
+}
@@ -0,0 +1,123 @@
+use tauri::State;
+
+use crate::{
+  AppState,
+  models::{script::{DelayClass, Script, Step}, state::MutationGuard},
+  scripts::{
+    dsl::{self, DslError},
+    library::{BindingLocation, DanglingRefs},
+  },
+};
+
+use super::error::CommandError;
+
+#[tauri::command]
+pub async fn promote_inline(
+  state: State<'_, AppState>,
+  session_id: String,
+  layer_id: i32,
+  target_id: String,
+  name: String,
+  guard: Option<MutationGuard>,
+) -> Result<Script, CommandError> {
+  state
+    .io
+    .promote_inline(session_id, layer_id, target_id, name, guard.unwrap_or_default())
+    .await
+    .map_err(|e| CommandError::from_backend("promote_inline", e))
+}
+
+/// Returns how many bindings still reference the script.
+#[tauri::command]
+pub async fn inline_script_ref(
+  state: State<'_, AppState>,
+  session_id: String,
+  layer_id: i32,
+  target_id: String,
+  guard: Option<MutationGuard>,
+) -> Result<usize, CommandError> {
+  state
+    .io
+    .inline_script_ref(session_id, layer_id, target_id, guard.unwrap_or_default())
+    .await
+    .map_err(|e| CommandError::from_backend("inline_script_ref", e))
+}
+
+#[tauri::command]
+pub async fn list_scripts(state: State<'_, AppState>, session_id: String) -> Result<Vec<Script>, CommandError> {
+  state
+    .io
+    .list_scripts(session_id)
+    .await
+    .map_err(|e| CommandError::from_backend("list_scripts", e))
+}
+
+#[tauri::command]
+pub async fn create_script(state: State<'_, AppState>, session_id: String, script: Script) -> Result<Script, CommandError> {
+  state
+    .io
+    .create_script(session_id, script)
+    .await
+    .map_err(|e| CommandError::from_backend("create_script", e))
+}
+
+#[tauri::command]
+pub async fn update_script(state: State<'_, AppState>, session_id: String, script: Script) -> Result<(), CommandError> {
+  state
+    .io
+    .update_script(session_id, script)
+    .await
+    .map_err(|e| CommandError::from_backend("update_script", e))
+}
+
+#[tauri::command]
+pub async fn list_delay_classes(state: State<'_, AppState>, session_id: String) -> Result<Vec<DelayClass>, CommandError> {
+  state
+    .io
+    .list_delay_classes(session_id)
+    .await
+    .map_err(|e| CommandError::from_backend("list_delay_classes", e))
+}
+
+/// Replaces the profile's delay classes; classed waits pick up the new timings
+/// on the next run or apply.
+#[tauri::command]
+pub async fn set_delay_classes(
+  state: State<'_, AppState>,
+  session_id: String,
+  classes: Vec<DelayClass>,
+) -> Result<(), CommandError> {
+  state
+    .io
+    .set_delay_classes(session_id, classes)
+    .await
+    .map_err(|e| CommandError::from_backend("set_delay_classes", e))
+}
+
+/// Without `dangling`, fails with `script_in_use` while keys are still bound to the script.
+#[tauri::command]
+pub async fn delete_script(
+  state: State<'_, AppState>,
+  session_id: String,
+  script_id: String,
+  dangling: Option<DanglingRefs>,
+  guard: Option<MutationGuard>,
+) -> Result<Vec<BindingLocation>, CommandError> {
+  state
+    .io
+    .delete_script(session_id, script_id, dangling.unwrap_or_default(), guard.unwrap_or_default())
+    .await
+    .map_err(|e| CommandError::from_backend("delete_script", e))
+}
+
+/// Parses script text; errors carry the line and column.
+#[tauri::command]
+pub fn parse_script_text(text: String) -> Result<Vec<Step>, DslError> {
+  dsl::parse(&text)
+}
+
+/// Fails on steps the text form can't represent.
+#[tauri::command]
+pub fn format_script_text(steps: Vec<Step>) -> tauri::Result<String> {
+  Ok(dsl::format(&steps)?)
+}
@@ -0,0 +1,35 @@
+use tauri::State;
+
+use crate::{
+  AppState,
+  store::{seeds::SeedSyncReport, SeedSync},
+};
+use anyhow::anyhow;
+
+fn seed_store<'a>(state: &'a State<AppState>) -> tauri::Result<&'a (dyn SeedSync + Send + Sync)> {
+  state
+    .seed_store
+    .as_deref()
+    .ok_or_else(|| tauri::Error::from(anyhow!("Seed sync needs the mock backend")))
+}
+
+#[tauri::command]
+pub fn sync_seeds(state: State<AppState>) -> tauri::Result<SeedSyncReport> {
+  seed_store(&state)?
+    .sync_seeds()
+    .map_err(|e| tauri::Error::from(anyhow!("sync_seeds failed: {e}")))
+}
+
+#[tauri::command]
+pub fn pending_seed_updates(state: State<AppState>) -> tauri::Result<Vec<String>> {
+  seed_store(&state)?
+    .pending_seed_updates()
+    .map_err(|e| tauri::Error::from(anyhow!("pending_seed_updates failed: {e}")))
+}
+
+#[tauri::command]
+pub fn accept_seed_update(state: State<AppState>, path: String) -> tauri::Result<()> {
+  seed_store(&state)?
+    .accept_seed_update(&path)
+    .map_err(|e| tauri::Error::from(anyhow!("accept_seed_update failed: {e}")))
+}
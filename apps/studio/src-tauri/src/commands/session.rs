@@ -1,83 +1,170 @@
-use tauri::State;
+use tauri::{AppHandle, State};
 
 use crate::{
   AppState,
   models::{
     binding::BindingEntry,
     bundle::ProfileBundle,
-    device::DeviceInfo,
+    device::{DeviceInfo, DeviceState},
+    state::MutationGuard,
+    verify::{MemoryRegion, VerifyReport},
   },
 };
+
+use super::{error::CommandError, operations::track};
 use anyhow::anyhow;
 
 #[tauri::command]
-pub fn list_devices(state: State<AppState>) -> tauri::Result<Vec<DeviceInfo>> {
+pub async fn list_devices(state: State<'_, AppState>) -> tauri::Result<Vec<DeviceInfo>> {
   state
-    .backend
+    .io
     .list_devices()
+    .await
     .map_err(|e| tauri::Error::from(anyhow!("list_devices failed: {e}")))
 }
 
 #[tauri::command]
-pub fn open_session(state: State<AppState>, device_id: String) -> tauri::Result<ProfileBundle> {
-  state
-    .backend
-    .open_session(device_id)
-    .map_err(|e| tauri::Error::from(anyhow!("open_session failed: {e}")))
+pub async fn open_session(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  device_id: String,
+  operation_id: Option<String>,
+) -> tauri::Result<ProfileBundle> {
+  let (operation_id, ctx) = track(&app, &state, "open_session", operation_id);
+  let result = state.io.open_session(device_id, ctx).await;
+  state.operations.finish(&operation_id);
+  result.map_err(|e| tauri::Error::from(anyhow!("open_session failed: {e}")))
 }
 
 #[tauri::command]
-pub fn close_session(state: State<AppState>, session_id: String) -> tauri::Result<()> {
+pub async fn close_session(state: State<'_, AppState>, session_id: String) -> tauri::Result<()> {
+  state.listeners.stop(&session_id);
   state
-    .backend
+    .io
     .close_session(session_id)
+    .await
     .map_err(|e| tauri::Error::from(anyhow!("close_session failed: {e}")))
 }
 
 #[tauri::command]
-pub fn set_binding(state: State<AppState>, session_id: String, req: BindingEntry) -> tauri::Result<()> {
+pub async fn set_binding(
+  state: State<'_, AppState>,
+  session_id: String,
+  req: BindingEntry,
+  guard: Option<MutationGuard>,
+) -> Result<(), CommandError> {
   state
-    .backend
-    .set_binding(session_id, req)
-    .map_err(|e| tauri::Error::from(anyhow!("set_binding failed: {e}")))
+    .io
+    .set_binding(session_id, req, guard.unwrap_or_default())
+    .await
+    .map_err(|e| CommandError::from_backend("set_binding", e))
 }
 
+/// With `verify`, reads RAM back and returns the per-key report.
 #[tauri::command]
-pub fn apply_to_ram(state: State<AppState>, session_id: String) -> tauri::Result<()> {
-  state
-    .backend
-    .apply_to_ram(session_id)
-    .map_err(|e| tauri::Error::from(anyhow!("apply_to_ram failed: {e}")))
+pub async fn apply_to_ram(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  session_id: String,
+  guard: Option<MutationGuard>,
+  verify: Option<bool>,
+  operation_id: Option<String>,
+) -> Result<Option<VerifyReport>, CommandError> {
+  let (operation_id, ctx) = track(&app, &state, "apply_to_ram", operation_id);
+  let result = async {
+    state
+      .io
+      .apply_to_ram(session_id.clone(), guard.unwrap_or_default(), ctx.clone())
+      .await?;
+    if !verify.unwrap_or(false) {
+      return Ok(None);
+    }
+    state.io.verify(session_id, MemoryRegion::Ram, ctx).await.map(Some)
+  }
+  .await;
+  state.operations.finish(&operation_id);
+  result.map_err(|e| CommandError::from_backend("apply_to_ram", e))
 }
 
 #[tauri::command]
-pub fn revert_ram(state: State<AppState>, session_id: String) -> tauri::Result<()> {
+pub async fn revert_ram(
+  state: State<'_, AppState>,
+  session_id: String,
+  guard: Option<MutationGuard>,
+) -> Result<(), CommandError> {
   state
-    .backend
-    .revert_ram(session_id)
-    .map_err(|e| tauri::Error::from(anyhow!("revert_ram failed: {e}")))
+    .io
+    .revert_ram(session_id, guard.unwrap_or_default())
+    .await
+    .map_err(|e| CommandError::from_backend("revert_ram", e))
 }
 
+/// With `verify`, reads EEPROM back and fails, without recording the commit,
+/// if any key did not stick.
 #[tauri::command]
-pub fn commit(state: State<AppState>, session_id: String) -> tauri::Result<()> {
-  state
-    .backend
-    .commit(session_id)
-    .map_err(|e| tauri::Error::from(anyhow!("commit failed: {e}")))
+pub async fn commit(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  session_id: String,
+  message: Option<String>,
+  guard: Option<MutationGuard>,
+  verify: Option<bool>,
+  operation_id: Option<String>,
+) -> Result<(), CommandError> {
+  let (operation_id, ctx) = track(&app, &state, "commit", operation_id);
+  let result = state
+    .io
+    .commit(session_id, message, guard.unwrap_or_default(), verify.unwrap_or(false), ctx)
+    .await;
+  state.operations.finish(&operation_id);
+  result.map_err(|e| CommandError::from_backend("commit", e))
 }
 
 #[tauri::command]
-pub fn run(state: State<AppState>, session_id: String, script_id: String) -> tauri::Result<()> {
+pub async fn commit_history(state: State<'_, AppState>, session_id: String) -> tauri::Result<Vec<DeviceState>> {
   state
-    .backend
-    .run(session_id, script_id)
-    .map_err(|e| tauri::Error::from(anyhow!("run failed: {e}")))
+    .io
+    .commit_history(session_id)
+    .await
+    .map_err(|e| tauri::Error::from(anyhow!("commit_history failed: {e}")))
+}
+
+/// Runs until the script ends; `cancel_operation` or `stop_all` stops it early.
+#[tauri::command]
+pub async fn run(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  session_id: String,
+  script_id: String,
+  operation_id: Option<String>,
+) -> tauri::Result<()> {
+  let (operation_id, ctx) = track(&app, &state, "run", operation_id);
+  let result = state.io.run(session_id, script_id, ctx).await;
+  state.operations.finish(&operation_id);
+  result.map_err(|e| tauri::Error::from(anyhow!("run failed: {e}")))
 }
 
+/// Stops scripts started by `run` and by the session's trigger listener.
 #[tauri::command]
-pub fn stop_all(state: State<AppState>, session_id: String) -> tauri::Result<()> {
+pub async fn stop_all(state: State<'_, AppState>, session_id: String) -> tauri::Result<()> {
+  state.listeners.stop_scripts(&session_id);
   state
-    .backend
+    .io
     .stop_all(session_id)
+    .await
     .map_err(|e| tauri::Error::from(anyhow!("stop_all failed: {e}")))
 }
+
+#[tauri::command]
+pub async fn verify_keymap(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  session_id: String,
+  region: MemoryRegion,
+  operation_id: Option<String>,
+) -> tauri::Result<VerifyReport> {
+  let (operation_id, ctx) = track(&app, &state, "verify", operation_id);
+  let result = state.io.verify(session_id, region, ctx).await;
+  state.operations.finish(&operation_id);
+  result.map_err(|e| tauri::Error::from(anyhow!("verify_keymap failed: {e}")))
+}
@@ -0,0 +1,32 @@
+use tauri::{AppHandle, State};
+
+use crate::{listener, AppState};
+
+/// Starts dispatching host bindings of `session_id` from the device's
+/// keyboard event node. Scripts are read from the session as keys fire.
+#[tauri::command]
+pub fn start_trigger_listener(
+  app: AppHandle,
+  state: State<AppState>,
+  session_id: String,
+  event_path: String,
+) -> tauri::Result<()> {
+  #[cfg(target_os = "linux")]
+  {
+    let source = listener::EvdevTriggers::open(std::path::Path::new(&event_path))?;
+    let handle = listener::spawn(app, session_id.clone(), Box::new(source));
+    state.listeners.insert(session_id, handle);
+    Ok(())
+  }
+  #[cfg(not(target_os = "linux"))]
+  {
+    let _ = (app, state, session_id);
+    Err(anyhow::anyhow!("Key event capture from {} is only supported on Linux", event_path).into())
+  }
+}
+
+/// Returns `false` when no listener was running for the session.
+#[tauri::command]
+pub fn stop_trigger_listener(state: State<AppState>, session_id: String) -> tauri::Result<bool> {
+  Ok(state.listeners.stop(&session_id))
+}
@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  launcher::LaunchPolicy,
+  store::files::{read_json, write_json_atomic},
+};
+
+pub const ENV_SEED_ROOT: &str = "BUUDEV_SEED_ROOT";
+pub const ENV_DATA_ROOT: &str = "BUUDEV_DATA_ROOT";
+pub const ENV_MOCK: &str = "BUUDEV_MOCK";
+pub const ENV_SETTINGS: &str = "BUUDEV_SETTINGS";
+pub const ENV_STORE: &str = "BUUDEV_STORE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StoreKind {
+  #[serde(rename = "json")]
+  Json,
+  #[serde(rename = "sqlite")]
+  Sqlite,
+}
+
+impl StoreKind {
+  fn parse(value: &str) -> Option<Self> {
+    match value.trim().to_ascii_lowercase().as_str() {
+      "json" => Some(Self::Json),
+      "sqlite" => Some(Self::Sqlite),
+      _ => None,
+    }
+  }
+}
+
+/// On-disk settings; every field is optional so the file can stay sparse.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SettingsFile {
+  #[serde(rename = "seedRoot", default)]
+  pub seed_root: Option<PathBuf>,
+  #[serde(rename = "dataRoot", default)]
+  pub data_root: Option<PathBuf>,
+  #[serde(rename = "mockEnabled", default)]
+  pub mock_enabled: Option<bool>,
+  #[serde(default)]
+  pub store: Option<StoreKind>,
+  /// Which program bindings may run; settings file only.
+  #[serde(default)]
+  pub programs: Option<LaunchPolicy>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StudioConfig {
+  pub seed_root: PathBuf,
+  pub data_root: PathBuf,
+  pub mock_enabled: bool,
+  pub store: StoreKind,
+  pub programs: LaunchPolicy,
+  /// Where remembered program approvals are written back.
+  pub settings_path: PathBuf,
+}
+
+/// Values from one source (CLI or env); `None` falls through to the next source.
+#[derive(Debug, Clone, Default)]
+struct Overrides {
+  seed_root: Option<PathBuf>,
+  data_root: Option<PathBuf>,
+  mock_enabled: Option<bool>,
+  store: Option<StoreKind>,
+  settings_path: Option<PathBuf>,
+}
+
+impl StudioConfig {
+  /// Resolves the config with precedence CLI flags > env vars > settings file > defaults.
+  pub fn load() -> Self {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    Self::resolve(&args, |key| std::env::var(key).ok())
+  }
+
+  pub fn resolve(args: &[String], env: impl Fn(&str) -> Option<String>) -> Self {
+    let cli = overrides_from_args(args);
+    let env = overrides_from_env(env);
+
+    let settings_path = cli
+      .settings_path
+      .clone()
+      .or_else(|| env.settings_path.clone())
+      .unwrap_or_else(default_settings_path);
+    let settings = load_settings(&settings_path);
+
+    Self {
+      seed_root: cli
+        .seed_root
+        .or(env.seed_root)
+        .or(settings.seed_root)
+        .unwrap_or_else(default_seed_root),
+      data_root: cli
+        .data_root
+        .or(env.data_root)
+        .or(settings.data_root)
+        .unwrap_or_else(default_data_root),
+      mock_enabled: cli
+        .mock_enabled
+        .or(env.mock_enabled)
+        .or(settings.mock_enabled)
+        .unwrap_or(true),
+      store: cli
+        .store
+        .or(env.store)
+        .or(settings.store)
+        .unwrap_or(StoreKind::Json),
+      programs: settings.programs.unwrap_or_default(),
+      settings_path,
+    }
+  }
+}
+
+/// Writes `policy` into the settings file, keeping its other fields. An
+/// unreadable file is left alone rather than replaced.
+pub fn save_program_policy(path: &Path, policy: &LaunchPolicy) -> anyhow::Result<()> {
+  let mut settings: SettingsFile = if path.exists() { read_json(path)? } else { SettingsFile::default() };
+  settings.programs = Some(policy.clone());
+  write_json_atomic(path, &settings)
+}
+
+fn overrides_from_args(args: &[String]) -> Overrides {
+  let mut out = Overrides::default();
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    let (flag, inline) = match arg.split_once('=') {
+      Some((flag, value)) => (flag, Some(value.to_string())),
+      None => (arg.as_str(), None),
+    };
+    let mut value = || inline.clone().or_else(|| iter.next().cloned());
+    match flag {
+      "--seed-root" => out.seed_root = value().map(PathBuf::from),
+      "--data-root" => out.data_root = value().map(PathBuf::from),
+      "--settings" => out.settings_path = value().map(PathBuf::from),
+      "--store" => out.store = value().and_then(|v| StoreKind::parse(&v)),
+      "--mock" => out.mock_enabled = Some(true),
+      "--no-mock" => out.mock_enabled = Some(false),
+      _ => {}
+    }
+  }
+  out
+}
+
+fn overrides_from_env(env: impl Fn(&str) -> Option<String>) -> Overrides {
+  let path = |key: &str| env(key).filter(|v| !v.trim().is_empty()).map(PathBuf::from);
+  Overrides {
+    seed_root: path(ENV_SEED_ROOT),
+    data_root: path(ENV_DATA_ROOT),
+    settings_path: path(ENV_SETTINGS),
+    mock_enabled: env(ENV_MOCK).and_then(|v| parse_bool(&v)),
+    store: env(ENV_STORE).and_then(|v| StoreKind::parse(&v)),
+  }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+  match value.trim().to_ascii_lowercase().as_str() {
+    "1" | "true" | "yes" | "on" => Some(true),
+    "0" | "false" | "no" | "off" => Some(false),
+    _ => None,
+  }
+}
+
+fn load_settings(path: &Path) -> SettingsFile {
+  if !path.exists() {
+    return SettingsFile::default();
+  }
+  match read_json(path) {
+    Ok(settings) => settings,
+    Err(e) => {
+      log::warn!("Ignoring unreadable settings file {}: {:#}", path.display(), e);
+      SettingsFile::default()
+    }
+  }
+}
+
+fn default_settings_path() -> PathBuf {
+  dirs::config_dir()
+    .unwrap_or_else(std::env::temp_dir)
+    .join("BuuDevStudio")
+    .join("settings.json")
+}
+
+fn default_data_root() -> PathBuf {
+  dirs::data_dir()
+    .unwrap_or_else(std::env::temp_dir)
+    .join("BuuDevStudio")
+    .join("mock-state")
+}
+
+fn default_seed_root() -> PathBuf {
+  let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+
+  let mut candidates = vec![
+    cwd.join("src-tauri").join("mock"),
+    cwd.join("mock"),
+  ];
+
+  if let Some(parent) = cwd.parent() {
+    candidates.push(parent.join("src-tauri").join("mock"));
+    candidates.push(parent.join("mock"));
+  }
+
+  for path in candidates {
+    if path.join("devices.json").exists() {
+      return path;
+    }
+  }
+
+  cwd.join("src-tauri").join("mock")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+  use crate::store::files::write_json_atomic;
+  use std::collections::HashMap;
+
+  #[test]
+  fn cli_beats_env_beats_settings_file() {
+    let dir = TempDir::new("config-test");
+    let settings_path = dir.join("settings.json");
+    write_json_atomic(
+      &settings_path,
+      &SettingsFile {
+        seed_root: Some(PathBuf::from("/settings/seed")),
+        data_root: Some(PathBuf::from("/settings/data")),
+        mock_enabled: Some(false),
+        store: Some(StoreKind::Sqlite),
+        programs: None,
+      },
+    )
+    .expect("write settings");
+
+    let env: HashMap<&str, String> = HashMap::from([
+      (ENV_SETTINGS, settings_path.display().to_string()),
+      (ENV_DATA_ROOT, "/env/data".to_string()),
+      (ENV_MOCK, "yes".to_string()),
+    ]);
+    let args = vec!["--no-mock".to_string(), "--data-root=/cli/data".to_string()];
+    let config = StudioConfig::resolve(&args, |k| env.get(k).cloned());
+
+    assert_eq!(config.seed_root, PathBuf::from("/settings/seed"));
+    assert_eq!(config.data_root, PathBuf::from("/cli/data"));
+    assert!(!config.mock_enabled);
+    assert_eq!(config.store, StoreKind::Sqlite);
+
+    let config = StudioConfig::resolve(&[], |k| env.get(k).cloned());
+    assert_eq!(config.data_root, PathBuf::from("/env/data"));
+    assert!(config.mock_enabled);
+  }
+}
@@ -0,0 +1,649 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  io::Read,
+  path::PathBuf,
+  process::{Command, Stdio},
+  sync::{mpsc, Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime};
+use uuid::Uuid;
+
+use crate::scripts::host::HostInjector;
+
+pub const PROGRAM_CONFIRM: &str = "program-confirm";
+pub const PROGRAM_FINISHED: &str = "program-finished";
+
+/// Captured stdout/stderr is cut to this many bytes each.
+pub const OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// Programs still running after this long are killed.
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Request target for `OPEN_*` script steps, which have no key of their own.
+pub const OPEN_TARGET_ID: &str = "host-script";
+
+/// The system handler that `OPEN_*` steps launch.
+#[cfg(target_os = "macos")]
+const SYSTEM_OPENER: &str = "/usr/bin/open";
+#[cfg(windows)]
+const SYSTEM_OPENER: &str = "C:\\Windows\\explorer.exe";
+#[cfg(not(any(target_os = "macos", windows)))]
+const SYSTEM_OPENER: &str = "/usr/bin/xdg-open";
+
+/// Variables a binding may not set: they change which code the program
+/// loads, so an approval for the path would no longer mean much.
+const DENIED_ENV: &[&str] = &["PATH", "IFS", "BASH_ENV", "ENV", "PYTHONPATH", "NODE_OPTIONS", "PERL5LIB", "RUBYOPT"];
+const DENIED_ENV_PREFIXES: &[&str] = &["LD_", "DYLD_"];
+
+/// What happens to programs that are not on the allowlist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnlistedPrograms {
+  #[default]
+  #[serde(rename = "confirm")]
+  Confirm,
+  #[serde(rename = "deny")]
+  Deny,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaunchPolicy {
+  /// Programs that run without asking, matched on the whole command line:
+  /// path, args, cwd and env. A bare path stands for the program run with
+  /// no args, cwd or env.
+  #[serde(default)]
+  pub allowlist: Vec<LaunchSpec>,
+  #[serde(default)]
+  pub unlisted: UnlistedPrograms,
+}
+
+impl LaunchPolicy {
+  pub fn allows(&self, spec: &LaunchSpec) -> bool {
+    self.allowlist.contains(spec)
+  }
+}
+
+/// A `Binding::Program` resolved into a command line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredSpec")]
+pub struct LaunchSpec {
+  pub path: PathBuf,
+  pub args: Vec<String>,
+  pub cwd: Option<PathBuf>,
+  pub env: BTreeMap<String, String>,
+}
+
+/// Allowlist entries as written in settings: a bare path or a full spec.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSpec {
+  Path(PathBuf),
+  Full {
+    path: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    cwd: Option<PathBuf>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+  },
+}
+
+impl From<StoredSpec> for LaunchSpec {
+  fn from(stored: StoredSpec) -> Self {
+    match stored {
+      StoredSpec::Path(path) => LaunchSpec { path, args: Vec::new(), cwd: None, env: BTreeMap::new() },
+      StoredSpec::Full { path, args, cwd, env } => LaunchSpec { path, args, cwd, env },
+    }
+  }
+}
+
+impl LaunchSpec {
+  /// Reads `args` (string array), `cwd` (string) and `env` (string map) from
+  /// the binding's meta. The path and cwd must be absolute, and `env` may not
+  /// touch the search path or the dynamic loader.
+  pub fn from_binding(path: &str, meta: Option<&serde_json::Map<String, serde_json::Value>>) -> anyhow::Result<Self> {
+    if path.trim().is_empty() {
+      bail!("Program binding has no path");
+    }
+    if !PathBuf::from(path).is_absolute() {
+      bail!("Program path {} must be absolute", path);
+    }
+    let mut spec = LaunchSpec {
+      path: PathBuf::from(path),
+      args: Vec::new(),
+      cwd: None,
+      env: BTreeMap::new(),
+    };
+    let Some(meta) = meta else {
+      return Ok(spec);
+    };
+    if let Some(args) = meta.get("args") {
+      spec.args = serde_json::from_value(args.clone()).map_err(|e| anyhow!("Invalid program args: {e}"))?;
+    }
+    if let Some(cwd) = meta.get("cwd") {
+      let cwd: String = serde_json::from_value(cwd.clone()).map_err(|e| anyhow!("Invalid program cwd: {e}"))?;
+      if !PathBuf::from(&cwd).is_absolute() {
+        bail!("Program cwd {} must be absolute", cwd);
+      }
+      spec.cwd = Some(PathBuf::from(cwd));
+    }
+    if let Some(env) = meta.get("env") {
+      spec.env = serde_json::from_value(env.clone()).map_err(|e| anyhow!("Invalid program env: {e}"))?;
+    }
+    if let Some(key) = spec.env.keys().find(|k| is_denied_env(k)) {
+      bail!("Program env may not set {}", key);
+    }
+    Ok(spec)
+  }
+
+  /// The system handler opening `target`, which must be an http(s) or file
+  /// URL or an absolute path.
+  pub fn for_open(target: &str) -> anyhow::Result<Self> {
+    let lower = target.to_ascii_lowercase();
+    let allowed = ["http://", "https://", "file://"].iter().any(|s| lower.starts_with(s)) || PathBuf::from(target).is_absolute();
+    if !allowed || target.chars().any(char::is_control) {
+      bail!("Only http(s) and file URLs or absolute paths can be opened, got {:?}", target);
+    }
+    Ok(LaunchSpec {
+      path: PathBuf::from(SYSTEM_OPENER),
+      args: vec![target.to_string()],
+      cwd: None,
+      env: BTreeMap::new(),
+    })
+  }
+
+  /// The command line as a person would read it in a prompt.
+  pub fn describe(&self) -> String {
+    let mut out = String::new();
+    for (key, value) in &self.env {
+      out.push_str(&format!("{}={} ", key, quote(value)));
+    }
+    out.push_str(&quote(&self.path.to_string_lossy()));
+    for arg in &self.args {
+      out.push(' ');
+      out.push_str(&quote(arg));
+    }
+    if let Some(cwd) = &self.cwd {
+      out.push_str(&format!(" (in {})", cwd.display()));
+    }
+    out
+  }
+}
+
+fn is_denied_env(key: &str) -> bool {
+  let upper = key.to_ascii_uppercase();
+  DENIED_ENV.contains(&upper.as_str()) || DENIED_ENV_PREFIXES.iter().any(|p| upper.starts_with(p))
+}
+
+fn quote(value: &str) -> String {
+  if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "/._-=:,+@".contains(c)) {
+    value.to_string()
+  } else {
+    format!("'{}'", value.replace('\'', "'\\''"))
+  }
+}
+
+/// A program launch waiting on the policy, keyed by `request_id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LaunchRequest {
+  #[serde(rename = "requestId")]
+  pub request_id: String,
+  #[serde(rename = "targetId")]
+  pub target_id: String,
+  #[serde(rename = "layerId")]
+  pub layer_id: Option<i32>,
+  pub program: LaunchSpec,
+  /// `program` as one readable command line, for the confirm prompt.
+  pub summary: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LaunchOutcome {
+  #[serde(rename = "exited")]
+  Exited,
+  #[serde(rename = "denied")]
+  Denied,
+  #[serde(rename = "failed")]
+  Failed,
+  /// Killed after running past `RUN_TIMEOUT`.
+  #[serde(rename = "timedOut")]
+  TimedOut,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LaunchResult {
+  #[serde(rename = "requestId")]
+  pub request_id: String,
+  #[serde(rename = "targetId")]
+  pub target_id: String,
+  pub path: PathBuf,
+  pub outcome: LaunchOutcome,
+  /// `None` when the program never ran or was killed by a signal.
+  #[serde(rename = "exitCode")]
+  pub exit_code: Option<i32>,
+  pub stdout: String,
+  pub stderr: String,
+  pub truncated: bool,
+  pub error: Option<String>,
+}
+
+impl LaunchResult {
+  fn without_output(request: &LaunchRequest, outcome: LaunchOutcome, error: String) -> Self {
+    Self {
+      request_id: request.request_id.clone(),
+      target_id: request.target_id.clone(),
+      path: request.program.path.clone(),
+      outcome,
+      exit_code: None,
+      stdout: String::new(),
+      stderr: String::new(),
+      truncated: false,
+      error: Some(error),
+    }
+  }
+}
+
+pub trait ProgramEventSink {
+  fn program_confirm(&self, request: &LaunchRequest);
+  fn program_finished(&self, result: &LaunchResult);
+}
+
+impl<R: Runtime> ProgramEventSink for AppHandle<R> {
+  fn program_confirm(&self, request: &LaunchRequest) {
+    if let Err(e) = self.emit(PROGRAM_CONFIRM, request.clone()) {
+      log::warn!("Failed to emit {} for {}: {}", PROGRAM_CONFIRM, request.request_id, e);
+    }
+  }
+
+  fn program_finished(&self, result: &LaunchResult) {
+    if let Err(e) = self.emit(PROGRAM_FINISHED, result.clone()) {
+      log::warn!("Failed to emit {} for {}: {}", PROGRAM_FINISHED, result.request_id, e);
+    }
+  }
+}
+
+/// Runs program bindings under the launch policy. Programs run on their own
+/// thread and report through `PROGRAM_FINISHED`.
+pub struct ProgramLauncher {
+  policy: Mutex<LaunchPolicy>,
+  pending: Mutex<HashMap<String, LaunchRequest>>,
+  /// Settings file that remembered approvals are saved to.
+  settings_path: Option<PathBuf>,
+}
+
+impl ProgramLauncher {
+  pub fn new(policy: LaunchPolicy) -> Self {
+    Self {
+      policy: Mutex::new(policy),
+      pending: Mutex::new(HashMap::new()),
+      settings_path: None,
+    }
+  }
+
+  pub fn with_settings(mut self, settings_path: PathBuf) -> Self {
+    self.settings_path = Some(settings_path);
+    self
+  }
+
+  /// Called when a program binding fires. Returns the request id that the
+  /// confirm and finish events carry.
+  pub fn trigger(
+    &self,
+    target_id: &str,
+    layer_id: Option<i32>,
+    program: LaunchSpec,
+    sink: Arc<dyn ProgramEventSink + Send + Sync>,
+  ) -> String {
+    let request = LaunchRequest {
+      request_id: Uuid::new_v4().to_string(),
+      target_id: target_id.to_string(),
+      layer_id,
+      summary: program.describe(),
+      program,
+    };
+    let request_id = request.request_id.clone();
+    let policy = self.policy.lock().unwrap().clone();
+    if policy.allows(&request.program) {
+      spawn(request, sink);
+    } else {
+      match policy.unlisted {
+        UnlistedPrograms::Confirm => {
+          sink.program_confirm(&request);
+          self.pending.lock().unwrap().insert(request_id.clone(), request);
+        }
+        UnlistedPrograms::Deny => {
+          let error = format!("{} is not on the program allowlist", request.program.path.display());
+          sink.program_finished(&LaunchResult::without_output(&request, LaunchOutcome::Denied, error));
+        }
+      }
+    }
+    request_id
+  }
+
+  /// Answers a `PROGRAM_CONFIRM` prompt. `remember` adds the exact command
+  /// line to the allowlist and saves it to the settings file. The program
+  /// still runs if saving fails; the error is returned after it starts.
+  pub fn confirm(
+    &self,
+    request_id: &str,
+    allow: bool,
+    remember: bool,
+    sink: Arc<dyn ProgramEventSink + Send + Sync>,
+  ) -> anyhow::Result<()> {
+    let request = self
+      .pending
+      .lock()
+      .unwrap()
+      .remove(request_id)
+      .ok_or_else(|| anyhow!("No pending program launch {}", request_id))?;
+    if !allow {
+      let error = "Launch declined".to_string();
+      sink.program_finished(&LaunchResult::without_output(&request, LaunchOutcome::Denied, error));
+      return Ok(());
+    }
+    let saved = if remember { self.remember(&request.program) } else { Ok(()) };
+    spawn(request, sink);
+    saved
+  }
+
+  fn remember(&self, program: &LaunchSpec) -> anyhow::Result<()> {
+    let mut policy = self.policy.lock().unwrap();
+    if !policy.allows(program) {
+      policy.allowlist.push(program.clone());
+    }
+    match &self.settings_path {
+      Some(path) => crate::config::save_program_policy(path, &policy)
+        .map_err(|e| anyhow!("Allowed for this session only; saving to settings failed: {e:#}")),
+      None => Ok(()),
+    }
+  }
+
+  pub fn pending(&self) -> Vec<LaunchRequest> {
+    self.pending.lock().unwrap().values().cloned().collect()
+  }
+}
+
+/// Host injector whose `OPEN_*` steps go through the launcher, so opening a
+/// file or URL is allowed, confirmed or denied like any program binding.
+pub struct PolicyInjector {
+  inner: Arc<dyn HostInjector + Send + Sync>,
+  launcher: Arc<ProgramLauncher>,
+  sink: Arc<dyn ProgramEventSink + Send + Sync>,
+}
+
+impl PolicyInjector {
+  pub fn new(
+    inner: Arc<dyn HostInjector + Send + Sync>,
+    launcher: Arc<ProgramLauncher>,
+    sink: Arc<dyn ProgramEventSink + Send + Sync>,
+  ) -> Self {
+    Self { inner, launcher, sink }
+  }
+}
+
+impl HostInjector for PolicyInjector {
+  fn key_down(&self, keycode: u16) -> anyhow::Result<()> {
+    self.inner.key_down(keycode)
+  }
+
+  fn key_up(&self, keycode: u16) -> anyhow::Result<()> {
+    self.inner.key_up(keycode)
+  }
+
+  fn type_text(&self, text: &str) -> anyhow::Result<()> {
+    self.inner.type_text(text)
+  }
+
+  /// Returns once the launch is requested; the outcome arrives as `PROGRAM_FINISHED`.
+  fn open(&self, target: &str) -> anyhow::Result<()> {
+    let spec = LaunchSpec::for_open(target)?;
+    self.launcher.trigger(OPEN_TARGET_ID, None, spec, self.sink.clone());
+    Ok(())
+  }
+}
+
+fn spawn(request: LaunchRequest, sink: Arc<dyn ProgramEventSink + Send + Sync>) {
+  std::thread::spawn(move || {
+    let result = run(&request);
+    log::info!(
+      "Program {} for {} finished: {:?} {:?}",
+      request.program.path.display(),
+      request.target_id,
+      result.outcome,
+      result.exit_code
+    );
+    sink.program_finished(&result);
+  });
+}
+
+/// Runs the program to completion or `RUN_TIMEOUT`, capturing its exit
+/// status and output.
+pub fn run(request: &LaunchRequest) -> LaunchResult {
+  run_within(request, RUN_TIMEOUT)
+}
+
+/// `run` with an explicit timeout, after which the program is killed.
+pub fn run_within(request: &LaunchRequest, timeout: Duration) -> LaunchResult {
+  let spec = &request.program;
+  let mut command = Command::new(&spec.path);
+  command
+    .args(&spec.args)
+    .envs(&spec.env)
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+  if let Some(cwd) = &spec.cwd {
+    command.current_dir(cwd);
+  }
+  let mut child = match command.spawn() {
+    Ok(child) => child,
+    Err(e) => {
+      let error = format!("Failed to start {}: {}", spec.path.display(), e);
+      return LaunchResult::without_output(request, LaunchOutcome::Failed, error);
+    }
+  };
+  let stdout = capture(child.stdout.take());
+  let stderr = capture(child.stderr.take());
+
+  let deadline = Instant::now() + timeout;
+  let status = loop {
+    match child.try_wait() {
+      Ok(Some(status)) => break Ok(Some(status)),
+      Ok(None) if Instant::now() >= deadline => {
+        let _ = child.kill();
+        let _ = child.wait();
+        break Ok(None);
+      }
+      Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+      Err(e) => break Err(e),
+    }
+  };
+  // Output still held open by a child's own children is given up on.
+  let collect = |rx: mpsc::Receiver<(String, bool)>| {
+    rx.recv_timeout(Duration::from_secs(1)).unwrap_or((String::new(), true))
+  };
+  let ((stdout, out_cut), (stderr, err_cut)) = (collect(stdout), collect(stderr));
+  let (outcome, exit_code, error) = match status {
+    Ok(Some(status)) => (LaunchOutcome::Exited, status.code(), None),
+    Ok(None) => (LaunchOutcome::TimedOut, None, Some(format!("Killed after {:?}", timeout))),
+    Err(e) => (LaunchOutcome::Failed, None, Some(format!("Failed to wait for {}: {}", spec.path.display(), e))),
+  };
+  LaunchResult {
+    request_id: request.request_id.clone(),
+    target_id: request.target_id.clone(),
+    path: spec.path.clone(),
+    outcome,
+    exit_code,
+    stdout,
+    stderr,
+    truncated: out_cut || err_cut,
+    error,
+  }
+}
+
+/// Reads a pipe on its own thread, keeping the first `OUTPUT_LIMIT` bytes and
+/// draining the rest so the program never blocks on a full pipe.
+fn capture(pipe: Option<impl Read + Send + 'static>) -> mpsc::Receiver<(String, bool)> {
+  let (tx, rx) = mpsc::channel();
+  std::thread::spawn(move || {
+    let mut kept = Vec::new();
+    let mut cut = false;
+    if let Some(mut pipe) = pipe {
+      let mut buf = [0u8; 8192];
+      while let Ok(n) = pipe.read(&mut buf) {
+        if n == 0 {
+          break;
+        }
+        let room = OUTPUT_LIMIT - kept.len();
+        kept.extend_from_slice(&buf[..n.min(room)]);
+        cut |= n > room;
+      }
+    }
+    let _ = tx.send((String::from_utf8_lossy(&kept).into_owned(), cut));
+  });
+  rx
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+  use std::{sync::mpsc, time::Duration};
+
+  struct ChannelSink {
+    confirms: Mutex<Vec<String>>,
+    finished: Mutex<mpsc::Sender<LaunchResult>>,
+  }
+
+  impl ProgramEventSink for ChannelSink {
+    fn program_confirm(&self, request: &LaunchRequest) {
+      self.confirms.lock().unwrap().push(request.request_id.clone());
+    }
+
+    fn program_finished(&self, result: &LaunchResult) {
+      let _ = self.finished.lock().unwrap().send(result.clone());
+    }
+  }
+
+  #[test]
+  fn allowlisted_runs_unlisted_waits_for_confirm() {
+    let (tx, rx) = mpsc::channel();
+    let sink = Arc::new(ChannelSink { confirms: Mutex::new(Vec::new()), finished: Mutex::new(tx) });
+    let wait = || rx.recv_timeout(Duration::from_secs(5)).expect("program finished");
+
+    let meta = serde_json::json!({
+      "args": ["-c", "echo \"$GREETING from $(pwd)\"; exit 3"],
+      "cwd": "/",
+      "env": { "GREETING": "hi" }
+    });
+    let spec = LaunchSpec::from_binding("/bin/sh", meta.as_object()).unwrap();
+    let launcher = ProgramLauncher::new(LaunchPolicy {
+      allowlist: vec![spec.clone()],
+      unlisted: UnlistedPrograms::Confirm,
+    });
+
+    let id = launcher.trigger("K1", Some(0), spec, sink.clone());
+    let result = wait();
+    assert_eq!(result.request_id, id);
+    assert_eq!(result.outcome, LaunchOutcome::Exited);
+    assert_eq!(result.exit_code, Some(3));
+    assert_eq!(result.stdout, "hi from /\n");
+
+    let unlisted = LaunchSpec::from_binding("/bin/true", None).unwrap();
+    let id = launcher.trigger("K2", None, unlisted.clone(), sink.clone());
+    assert_eq!(sink.confirms.lock().unwrap().clone(), vec![id.clone()]);
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err(), "runs only after confirm");
+    launcher.confirm(&id, true, true, sink.clone()).unwrap();
+    assert_eq!(wait().exit_code, Some(0));
+    assert!(launcher.confirm(&id, true, false, sink.clone()).is_err());
+
+    // Remembered programs skip the prompt next time.
+    launcher.trigger("K2", None, unlisted, sink.clone());
+    assert_eq!(wait().outcome, LaunchOutcome::Exited);
+    assert_eq!(sink.confirms.lock().unwrap().len(), 1);
+
+    let strict = ProgramLauncher::new(LaunchPolicy { allowlist: Vec::new(), unlisted: UnlistedPrograms::Deny });
+    strict.trigger("K3", None, LaunchSpec::from_binding("/bin/true", None).unwrap(), sink.clone());
+    assert_eq!(wait().outcome, LaunchOutcome::Denied);
+  }
+
+  #[test]
+  fn specs_are_checked_matched_whole_and_remembered_in_settings() {
+    assert!(LaunchSpec::from_binding("sh", None).is_err(), "relative path");
+    let cwd = serde_json::json!({ "cwd": "tmp" });
+    assert!(LaunchSpec::from_binding("/bin/sh", cwd.as_object()).is_err(), "relative cwd");
+    for key in ["LD_PRELOAD", "PATH", "DYLD_INSERT_LIBRARIES"] {
+      let env = serde_json::json!({ "env": { key: "/tmp/x" } });
+      assert!(LaunchSpec::from_binding("/bin/sh", env.as_object()).is_err(), "{key}");
+    }
+
+    // A bare path in settings allows the program only without args.
+    let policy: LaunchPolicy = serde_json::from_value(serde_json::json!({ "allowlist": ["/bin/echo"] })).unwrap();
+    assert!(policy.allows(&LaunchSpec::from_binding("/bin/echo", None).unwrap()));
+    let with_args = serde_json::json!({ "args": ["hi"], "env": { "GREETING": "it's me" } });
+    let with_args = LaunchSpec::from_binding("/bin/echo", with_args.as_object()).unwrap();
+    assert!(!policy.allows(&with_args));
+    assert_eq!(with_args.describe(), "GREETING='it'\\''s me' /bin/echo hi");
+
+    let dir = TempDir::new("launcher-test");
+    let settings_path = dir.join("settings.json");
+    let (tx, rx) = mpsc::channel();
+    let sink = Arc::new(ChannelSink { confirms: Mutex::new(Vec::new()), finished: Mutex::new(tx) });
+    let launcher = ProgramLauncher::new(policy).with_settings(settings_path.clone());
+    let id = launcher.trigger("K1", None, with_args.clone(), sink.clone());
+    launcher.confirm(&id, true, true, sink.clone()).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap().stdout, "hi\n");
+    let saved: crate::config::SettingsFile = crate::store::files::read_json(&settings_path).unwrap();
+    assert!(saved.programs.unwrap().allows(&with_args));
+  }
+
+  #[test]
+  fn long_runs_are_killed_and_output_is_capped() {
+    let request = |args: serde_json::Value| LaunchRequest {
+      request_id: "r".to_string(),
+      target_id: "K".to_string(),
+      layer_id: None,
+      summary: String::new(),
+      program: LaunchSpec::from_binding("/bin/sh", serde_json::json!({ "args": args }).as_object()).unwrap(),
+    };
+    let result = run_within(&request(serde_json::json!(["-c", "exec sleep 5"])), Duration::from_millis(100));
+    assert_eq!((result.outcome, result.exit_code), (LaunchOutcome::TimedOut, None));
+
+    let flood = format!("head -c {} /dev/zero", OUTPUT_LIMIT * 4);
+    let result = run_within(&request(serde_json::json!(["-c", flood])), Duration::from_secs(5));
+    assert_eq!(result.outcome, LaunchOutcome::Exited);
+    assert_eq!(result.stdout.len(), OUTPUT_LIMIT);
+    assert!(result.truncated);
+  }
+
+  #[test]
+  fn script_opens_go_through_the_policy() {
+    use crate::scripts::recording::{HostEvent, RecordingInjector};
+
+    let (tx, rx) = mpsc::channel();
+    let sink = Arc::new(ChannelSink { confirms: Mutex::new(Vec::new()), finished: Mutex::new(tx) });
+    let inner = Arc::new(RecordingInjector::new());
+    let launcher = Arc::new(ProgramLauncher::new(LaunchPolicy::default()));
+    let injector = PolicyInjector::new(inner.clone(), launcher.clone(), sink.clone());
+
+    injector.key_down(0x04).unwrap();
+    assert_eq!(inner.events(), vec![HostEvent::KeyDown(0x04)]);
+    for target in ["javascript:alert(1)", "relative/file.txt", "-n", "ftp://example.com"] {
+      assert!(injector.open(target).is_err(), "{target}");
+    }
+
+    injector.open("https://example.com").unwrap();
+    let pending = launcher.pending();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].target_id, OPEN_TARGET_ID);
+    assert_eq!(pending[0].program.args, vec!["https://example.com".to_string()]);
+    assert_eq!(sink.confirms.lock().unwrap().len(), 1);
+    launcher.confirm(&pending[0].request_id, false, false, sink.clone()).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap().outcome, LaunchOutcome::Denied);
+    assert!(inner.events().iter().all(|e| !matches!(e, HostEvent::Open(_))), "bypassed the launcher");
+  }
+}
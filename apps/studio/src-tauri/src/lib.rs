@@ -2,52 +2,38 @@ pub mod models;
 pub mod backends;
 pub mod store;
 pub mod commands;
+pub mod config;
+pub mod watcher;
+pub mod launcher;
+pub mod listener;
+pub mod via;
+pub mod scripts;
+#[cfg(test)]
+mod testing;
 
-use backends::r#trait::DeviceBackend;
+use backends::{nonblocking::AsyncDeviceBackend, r#trait::DeviceBackend};
+use store::SeedSync;
 use tauri::Manager;
-use std::path::PathBuf;
-
-fn resolve_seed_root() -> PathBuf {
-  let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-
-  let mut candidates = vec![
-    cwd.join("src-tauri").join("mock"),
-    cwd.join("mock"),
-  ];
-
-  if let Some(parent) = cwd.parent() {
-    candidates.push(parent.join("src-tauri").join("mock"));
-    candidates.push(parent.join("mock"));
-  }
-
-  for path in candidates {
-    if path.join("devices.json").exists() {
-      return path;
-    }
-  }
-
-  cwd.join("src-tauri").join("mock")
-}
+use std::sync::Arc;
+use std::time::Duration;
 
 pub struct AppState {
-  pub backend: Box<dyn DeviceBackend + Send + Sync>,
+  pub backend: Arc<dyn DeviceBackend + Send + Sync>,
+  /// Same backend, run off the async runtime for commands.
+  pub io: Arc<dyn AsyncDeviceBackend + Send + Sync>,
+  pub operations: backends::operation::OperationRegistry,
+  /// Store of the mock backend, for seed sync. `None` when mock is disabled.
+  pub seed_store: Option<Arc<dyn store::SeedSync + Send + Sync>>,
+  pub launcher: Arc<launcher::ProgramLauncher>,
+  /// Plays host scripts triggered from a device.
+  pub injector: Arc<dyn scripts::host::HostInjector + Send + Sync>,
+  pub listeners: listener::ListenerRegistry,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
     .setup(|app| {
-      let seed_root = resolve_seed_root();
-      let data_root = dirs::data_dir()
-        .unwrap_or_else(|| std::env::temp_dir())
-        .join("BuuDevStudio")
-        .join("mock-state");
-
-      let backend = backends::mock::MockBackend::new(seed_root, data_root);
-      app.manage(AppState {
-        backend: Box::new(backend),
-      });
-
       if cfg!(debug_assertions) {
         app.handle().plugin(
           tauri_plugin_log::Builder::default()
@@ -55,6 +41,71 @@ pub fn run() {
             .build(),
         )?;
       }
+
+      let config = config::StudioConfig::load();
+      log::info!(
+        "Seed root {}, data root {}, mock backend {}, {:?} store",
+        config.seed_root.display(),
+        config.data_root.display(),
+        if config.mock_enabled { "enabled" } else { "disabled" },
+        config.store
+      );
+
+      let launcher = Arc::new(
+        launcher::ProgramLauncher::new(config.programs.clone()).with_settings(config.settings_path.clone()),
+      );
+      // Script `OPEN_*` steps are launched under the same policy as program bindings.
+      let injector: Arc<dyn scripts::host::HostInjector + Send + Sync> = Arc::new(launcher::PolicyInjector::new(
+        scripts::host::default_injector(),
+        launcher.clone(),
+        Arc::new(app.handle().clone()),
+      ));
+      let mut registry = backends::registry::BackendRegistry::new();
+      let mut seed_store = None;
+      if config.mock_enabled {
+        let backend = match config.store {
+          config::StoreKind::Json => {
+            let mock_store = store::MockStore::new(config.seed_root.clone(), config.data_root.clone());
+            match mock_store.sync_seeds() {
+              Ok(report) if !report.pending.is_empty() => {
+                log::info!("Seed updates waiting for review: {}", report.pending.join(", "));
+              }
+              Ok(_) => {}
+              Err(e) => log::warn!("Seed sync failed: {e:#}"),
+            }
+            let mock_store = Arc::new(mock_store);
+            seed_store = Some(mock_store.clone() as Arc<dyn SeedSync + Send + Sync>);
+            backends::mock::MockBackend::with_store(mock_store)
+          }
+          config::StoreKind::Sqlite => {
+            let db_path = config.data_root.join("studio.db");
+            let sqlite = Arc::new(store::SqliteStore::open(config.seed_root.clone(), &db_path)?);
+            match sqlite.pending_seed_updates() {
+              Ok(pending) if !pending.is_empty() => {
+                log::info!("Seed updates waiting for review: {}", pending.join(", "));
+              }
+              Ok(_) => {}
+              Err(e) => log::warn!("Reading pending seed updates failed: {e:#}"),
+            }
+            seed_store = Some(sqlite.clone() as Arc<dyn SeedSync + Send + Sync>);
+            backends::mock::MockBackend::with_store(sqlite)
+          }
+        };
+        registry.register("mock", Arc::new(backend.with_injector(injector.clone())));
+      }
+      let backend: Arc<dyn DeviceBackend + Send + Sync> = Arc::new(registry);
+      app.manage(AppState {
+        io: Arc::new(backends::nonblocking::BlockingAdapter::new(backend.clone())),
+        backend,
+        operations: backends::operation::OperationRegistry::new(),
+        seed_store,
+        launcher,
+        injector,
+        listeners: listener::ListenerRegistry::new(),
+      });
+      watcher::spawn(app.handle().clone(), Duration::from_millis(1500));
+      spawn_flusher(app.handle().clone(), Duration::from_millis(500));
+
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
@@ -65,9 +116,49 @@ pub fn run() {
       commands::session::apply_to_ram,
       commands::session::revert_ram,
       commands::session::commit,
+      commands::session::commit_history,
       commands::session::run,
       commands::session::stop_all,
+      commands::session::verify_keymap,
+      commands::scripts::promote_inline,
+      commands::scripts::inline_script_ref,
+      commands::scripts::list_scripts,
+      commands::scripts::create_script,
+      commands::scripts::update_script,
+      commands::scripts::delete_script,
+      commands::scripts::list_delay_classes,
+      commands::scripts::set_delay_classes,
+      commands::scripts::parse_script_text,
+      commands::scripts::format_script_text,
+      commands::operations::cancel_operation,
+      commands::programs::confirm_program_launch,
+      commands::programs::pending_program_launches,
+      commands::triggers::start_trigger_listener,
+      commands::triggers::stop_trigger_listener,
+      commands::seeds::sync_seeds,
+      commands::seeds::pending_seed_updates,
+      commands::seeds::accept_seed_update,
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while running tauri application")
+    .run(|app, event| {
+      if let tauri::RunEvent::Exit = event {
+        if let Some(state) = app.try_state::<AppState>() {
+          if let Err(e) = state.backend.flush() {
+            log::warn!("Final state flush failed: {e}");
+          }
+        }
+      }
+    });
+}
+
+/// Periodically writes cached session state to disk.
+fn spawn_flusher<R: tauri::Runtime>(app: tauri::AppHandle<R>, interval: Duration) {
+  std::thread::spawn(move || loop {
+    std::thread::sleep(interval);
+    let state = app.state::<AppState>();
+    if let Err(e) = state.backend.flush() {
+      log::warn!("State flush failed: {e}");
+    }
+  });
 }
@@ -0,0 +1,587 @@
+//! Turns key presses reported by a device into host actions.
+//!
+//! Every host-executed binding (a `ScriptRef` that runs on the host, or a
+//! `Program`) gets a trigger slot. The firmware reports a slot either on the
+//! VIA custom channel or, for stock firmware, by sending F13–F24 for the
+//! first twelve slots.
+
+use std::{
+  collections::HashMap,
+  sync::{mpsc, Arc, Mutex},
+  time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::{
+  backends::{operation::OperationContext, r#trait::UnknownSession},
+  launcher::LaunchSpec,
+  models::{binding::Binding, device::LayerState, script::{ExecutionTarget, Script}},
+  scripts::{
+    host::{run_host_triggered, TriggerKey},
+    inline::inline_id,
+    validate::effective_target,
+  },
+  AppState,
+};
+
+/// VIA `id_custom_set_value`.
+pub const VIA_CUSTOM_SET_VALUE: u8 = 0x07;
+/// Custom channel the firmware reports host triggers on.
+pub const HOST_TRIGGER_CHANNEL: u8 = 0x60;
+/// F13..F24, in slot order.
+pub const TRIGGER_KEYCODES: std::ops::RangeInclusive<u16> = 0x68..=0x73;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A raw report from the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawTrigger {
+  /// A VIA raw HID report: `[0x07, channel, slot, pressed]`.
+  Via(Vec<u8>),
+  /// A basic keycode seen on the device's keyboard interface.
+  Keycode { keycode: u16, pressed: bool },
+}
+
+impl RawTrigger {
+  /// The trigger slot of a press. Releases and unrelated reports are `None`.
+  pub fn pressed_slot(&self) -> Option<u8> {
+    self.slot_event().filter(|(_, pressed)| *pressed).map(|(slot, _)| slot)
+  }
+
+  /// The trigger slot and whether it went down or up.
+  pub fn slot_event(&self) -> Option<(u8, bool)> {
+    match self {
+      RawTrigger::Via(report) => match report.as_slice() {
+        [VIA_CUSTOM_SET_VALUE, HOST_TRIGGER_CHANNEL, slot, pressed, ..] => Some((*slot, *pressed != 0)),
+        _ => None,
+      },
+      RawTrigger::Keycode { keycode, pressed } if TRIGGER_KEYCODES.contains(keycode) => {
+        Some(((keycode - TRIGGER_KEYCODES.start()) as u8, *pressed))
+      }
+      RawTrigger::Keycode { .. } => None,
+    }
+  }
+}
+
+pub trait TriggerSource {
+  /// Waits up to `timeout` for the next report. `Ok(None)` means nothing
+  /// arrived; an error stops the listener.
+  fn next(&mut self, timeout: Duration) -> anyhow::Result<Option<RawTrigger>>;
+}
+
+/// Source fed from code, for tests and the mock backend.
+pub struct SimulatedTriggers {
+  rx: mpsc::Receiver<RawTrigger>,
+}
+
+impl SimulatedTriggers {
+  pub fn new() -> (mpsc::Sender<RawTrigger>, Self) {
+    let (tx, rx) = mpsc::channel();
+    (tx, Self { rx })
+  }
+}
+
+impl TriggerSource for SimulatedTriggers {
+  fn next(&mut self, timeout: Duration) -> anyhow::Result<Option<RawTrigger>> {
+    match self.rx.recv_timeout(timeout) {
+      Ok(trigger) => Ok(Some(trigger)),
+      Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+      Err(mpsc::RecvTimeoutError::Disconnected) => bail!("Trigger source closed"),
+    }
+  }
+}
+
+/// Key events from an evdev node such as `/dev/input/by-id/...-event-kbd`.
+#[cfg(target_os = "linux")]
+pub struct EvdevTriggers {
+  device: std::fs::File,
+  evdev_to_hid: std::collections::HashMap<u16, u16>,
+}
+
+#[cfg(target_os = "linux")]
+impl EvdevTriggers {
+  pub fn open(path: &std::path::Path) -> anyhow::Result<Self> {
+    use anyhow::Context;
+    use crate::scripts::keymap::{hid_to_evdev, mapped_keycodes};
+
+    let device = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let evdev_to_hid = mapped_keycodes()
+      .filter_map(|hid| hid_to_evdev(hid).map(|code| (code, hid)))
+      .collect();
+    Ok(Self { device, evdev_to_hid })
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl TriggerSource for EvdevTriggers {
+  fn next(&mut self, timeout: Duration) -> anyhow::Result<Option<RawTrigger>> {
+    use std::{io::Read, os::fd::AsRawFd};
+
+    const EV_KEY: u16 = 0x01;
+    let mut poll = libc::pollfd { fd: self.device.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    // SAFETY: `poll` points at one valid pollfd for the duration of the call.
+    let ready = unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as libc::c_int) };
+    if ready < 0 {
+      return Err(std::io::Error::last_os_error().into());
+    }
+    if ready == 0 {
+      return Ok(None);
+    }
+    let mut buf = [0u8; std::mem::size_of::<libc::input_event>()];
+    self.device.read_exact(&mut buf)?;
+    // SAFETY: the kernel writes whole input_event structs; read_unaligned copes with the byte buffer.
+    let event: libc::input_event = unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const libc::input_event) };
+    // Value 2 is autorepeat; only presses and releases matter.
+    if event.type_ != EV_KEY || event.value > 1 {
+      return Ok(None);
+    }
+    Ok(self.evdev_to_hid.get(&event.code).map(|keycode| RawTrigger::Keycode {
+      keycode: *keycode,
+      pressed: event.value == 1,
+    }))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostAction {
+  Script(String),
+  /// An inline sequence, already wrapped as a script.
+  Inline(Script),
+  Program(LaunchSpec),
+}
+
+/// A host-executed binding and the slot the device reports it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriggerBinding {
+  pub slot: u8,
+  pub target_id: String,
+  pub layer_id: i32,
+  pub action: HostAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TriggerMap {
+  bindings: Vec<TriggerBinding>,
+}
+
+impl TriggerMap {
+  /// Assigns slots in layer order, then target order, so the same keymap
+  /// always yields the same slots.
+  pub fn build(layers: &[LayerState], scripts: &[Script]) -> Self {
+    let mut entries: Vec<_> = layers
+      .iter()
+      .flat_map(|l| l.bindings.iter().map(move |b| (l.id, b)))
+      .collect();
+    entries.sort_by(|a, b| (a.0, &a.1.target_id).cmp(&(b.0, &b.1.target_id)));
+
+    let mut bindings = Vec::new();
+    for (layer_id, entry) in entries {
+      let action = match &entry.binding {
+        Binding::ScriptRef { script_id, target, .. } => {
+          let Some(script) = scripts.iter().find(|s| s.id == *script_id) else {
+            continue;
+          };
+          if effective_target(*target, script) != ExecutionTarget::Host {
+            continue;
+          }
+          HostAction::Script(script_id.clone())
+        }
+        Binding::InlineSequence { steps, target: Some(ExecutionTarget::Host), .. } => HostAction::Inline(Script {
+          id: inline_id(entry.layer_id.unwrap_or(layer_id), &entry.target_id),
+          profile_id: String::new(),
+          name: format!("{} (inline)", entry.target_id),
+          target: ExecutionTarget::Host,
+          steps: steps.clone(),
+          meta: None,
+        }),
+        Binding::Program { path, meta } => match LaunchSpec::from_binding(path, meta.as_ref()) {
+          Ok(spec) => HostAction::Program(spec),
+          Err(e) => {
+            log::warn!("Skipping program binding on {}: {:#}", entry.target_id, e);
+            continue;
+          }
+        },
+        _ => continue,
+      };
+      let Ok(slot) = u8::try_from(bindings.len()) else {
+        log::warn!("Too many host bindings; {} has no trigger slot", entry.target_id);
+        break;
+      };
+      bindings.push(TriggerBinding {
+        slot,
+        target_id: entry.target_id.clone(),
+        layer_id: entry.layer_id.unwrap_or(layer_id),
+        action,
+      });
+    }
+    Self { bindings }
+  }
+
+  pub fn bindings(&self) -> &[TriggerBinding] {
+    &self.bindings
+  }
+
+  pub fn resolve(&self, trigger: &RawTrigger) -> Option<&TriggerBinding> {
+    let (slot, _) = trigger.slot_event()?;
+    self.bindings.iter().find(|b| b.slot == slot)
+  }
+}
+
+/// Carries out the action bound to a trigger.
+pub trait ActionDispatcher {
+  /// Starts the action for a press of `key`. Must not block on long-running
+  /// scripts, so releases keep flowing to `key`; call `key.set_running(false)`
+  /// once the action is done.
+  fn dispatch(&self, binding: &TriggerBinding, key: &TriggerKey) -> anyhow::Result<()>;
+}
+
+/// Reads `source` until `stop` is cancelled, the source fails or `map`
+/// reports the session gone (`Ok(None)`), dispatching every press that maps
+/// to a binding. The map is re-read on each press so applied keymap changes
+/// take effect without a restart; a press whose map can't be read is logged
+/// and skipped. Releases, and presses while the slot's script still runs, go
+/// to that script's `TriggerKey` instead, which ends `WHILE_HELD` and
+/// `TOGGLE` loops.
+pub fn listen(
+  source: &mut dyn TriggerSource,
+  map: &dyn Fn() -> anyhow::Result<Option<TriggerMap>>,
+  dispatcher: &dyn ActionDispatcher,
+  stop: &OperationContext,
+) -> anyhow::Result<()> {
+  let mut keys: HashMap<u8, TriggerKey> = HashMap::new();
+  let mut current: Option<TriggerMap> = None;
+  while !stop.is_cancelled() {
+    let Some(trigger) = source.next(POLL_INTERVAL)? else {
+      continue;
+    };
+    let Some((slot, pressed)) = trigger.slot_event() else {
+      continue;
+    };
+    if pressed {
+      match map() {
+        Ok(Some(map)) => {
+          if current.as_ref().is_some_and(|c| *c != map) {
+            // Slots were renumbered, so held keys belong to other bindings now.
+            for key in keys.values() {
+              key.release();
+            }
+            keys.clear();
+          }
+          current = Some(map);
+        }
+        Ok(None) => {
+          log::info!("Trigger listener stopped: the session is gone");
+          return Ok(());
+        }
+        Err(e) => {
+          log::warn!("Skipping trigger {:?}: {:#}", trigger, e);
+          continue;
+        }
+      }
+    }
+    match (keys.get(&slot).filter(|key| key.is_running()), pressed) {
+      (Some(key), true) => {
+        key.press();
+        continue;
+      }
+      (Some(key), false) => {
+        key.release();
+        continue;
+      }
+      (None, false) => continue,
+      (None, true) => {}
+    }
+    let Some(binding) = current.as_ref().and_then(|map| map.resolve(&trigger)) else {
+      log::debug!("Trigger {:?} has no host binding", trigger);
+      continue;
+    };
+    let key = TriggerKey::new();
+    key.press();
+    key.set_running(true);
+    if let Err(e) = dispatcher.dispatch(binding, &key) {
+      key.set_running(false);
+      log::warn!("Host action for {} failed: {:#}", binding.target_id, e);
+    }
+    keys.insert(slot, key);
+  }
+  Ok(())
+}
+
+/// Cancels the scripts a listener started without stopping the listener.
+/// Scripts get a child of the listener's stop context, so stopping the
+/// listener ends them too.
+#[derive(Clone)]
+pub struct ScriptScope {
+  listener: OperationContext,
+  current: Arc<Mutex<OperationContext>>,
+}
+
+impl ScriptScope {
+  pub fn new(listener: &OperationContext) -> Self {
+    Self {
+      listener: listener.clone(),
+      current: Arc::new(Mutex::new(listener.child())),
+    }
+  }
+
+  /// Context for a script starting now.
+  pub fn context(&self) -> OperationContext {
+    self.current.lock().unwrap().clone()
+  }
+
+  /// Cancels every script started so far; later presses start fresh.
+  pub fn stop_all(&self) {
+    let mut current = self.current.lock().unwrap();
+    current.cancel();
+    *current = self.listener.child();
+  }
+}
+
+/// Dispatches into the running app: scripts through the host injector,
+/// programs through the launcher.
+pub struct AppDispatcher<R: Runtime> {
+  app: AppHandle<R>,
+  session_id: String,
+  scripts: ScriptScope,
+}
+
+impl<R: Runtime> ActionDispatcher for AppDispatcher<R> {
+  fn dispatch(&self, binding: &TriggerBinding, key: &TriggerKey) -> anyhow::Result<()> {
+    let state = self.app.state::<AppState>();
+    let script = match &binding.action {
+      // Read per press so script edits apply without restarting the listener.
+      HostAction::Script(script_id) => state
+        .backend
+        .list_scripts(self.session_id.clone())?
+        .into_iter()
+        .find(|s| s.id == *script_id)
+        .ok_or_else(|| anyhow!("Unknown script {}", script_id))?,
+      HostAction::Inline(script) => script.clone(),
+      HostAction::Program(spec) => {
+        state
+          .launcher
+          .trigger(&binding.target_id, Some(binding.layer_id), spec.clone(), Arc::new(self.app.clone()));
+        key.set_running(false);
+        return Ok(());
+      }
+    };
+    // Read per press so retuned delay classes apply straight away.
+    let delays = state.backend.list_delay_classes(self.session_id.clone())?;
+    // Scripts play on their own thread so the listener still sees releases.
+    let injector = state.injector.clone();
+    let key = key.clone();
+    let target_id = binding.target_id.clone();
+    let ctx = self.scripts.context();
+    std::thread::spawn(move || {
+      if let Err(e) = run_host_triggered(&script, &delays, injector.as_ref(), &ctx, &key) {
+        log::warn!("Host script on {} failed: {:#}", target_id, e);
+      }
+      key.set_running(false);
+    });
+    Ok(())
+  }
+}
+
+/// A running listener: `stop` ends it and its scripts, `scripts` only the scripts.
+#[derive(Clone)]
+pub struct ListenerHandle {
+  pub stop: OperationContext,
+  pub scripts: ScriptScope,
+}
+
+/// Listens for triggers of one session on a background thread until the
+/// returned handle is stopped or the session goes away. Scripts and the
+/// applied keymap are read from the backend as presses arrive.
+pub fn spawn<R: Runtime>(app: AppHandle<R>, session_id: String, mut source: Box<dyn TriggerSource + Send>) -> ListenerHandle {
+  let stop = OperationContext::new();
+  let handle = ListenerHandle { scripts: ScriptScope::new(&stop), stop };
+  let (ctx, scripts) = (handle.stop.clone(), handle.scripts.clone());
+  std::thread::spawn(move || {
+    let backend = app.state::<AppState>().backend.clone();
+    let map_session = session_id.clone();
+    let map = move || -> anyhow::Result<Option<TriggerMap>> {
+      let state = match backend.session_state(map_session.clone()) {
+        Ok(state) => state,
+        Err(tauri::Error::Anyhow(e)) if e.is::<UnknownSession>() => return Ok(None),
+        Err(e) => return Err(e.into()),
+      };
+      let layers = state.applied.map(|s| s.layers).unwrap_or_default();
+      let scripts = backend.list_scripts(map_session.clone())?;
+      Ok(Some(TriggerMap::build(&layers, &scripts)))
+    };
+    let dispatcher = AppDispatcher { app: app.clone(), session_id, scripts };
+    if let Err(e) = listen(source.as_mut(), &map, &dispatcher, &ctx) {
+      log::info!("Trigger listener stopped: {e:#}");
+    }
+  });
+  handle
+}
+
+/// Running listeners, so closing a session can stop its listener.
+#[derive(Default)]
+pub struct ListenerRegistry {
+  running: Mutex<HashMap<String, ListenerHandle>>,
+}
+
+impl ListenerRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert(&self, session_id: String, handle: ListenerHandle) {
+    if let Some(previous) = self.running.lock().unwrap().insert(session_id, handle) {
+      previous.stop.cancel();
+    }
+  }
+
+  pub fn stop(&self, session_id: &str) -> bool {
+    match self.running.lock().unwrap().remove(session_id) {
+      Some(handle) => {
+        handle.stop.cancel();
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Cancels the scripts the session's listener started; it keeps listening.
+  pub fn stop_scripts(&self, session_id: &str) {
+    if let Some(handle) = self.running.lock().unwrap().get(session_id) {
+      handle.scripts.stop_all();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::{binding::BindingEntry, script::Step};
+
+  #[derive(Default)]
+  struct RecordingDispatcher {
+    dispatched: Mutex<Vec<(String, i32, HostAction)>>,
+    /// Leave dispatched scripts running, as a `TOGGLE` would.
+    keep_running: bool,
+  }
+
+  impl ActionDispatcher for RecordingDispatcher {
+    fn dispatch(&self, binding: &TriggerBinding, key: &TriggerKey) -> anyhow::Result<()> {
+      let mut guard = self.dispatched.lock().unwrap();
+      guard.push((binding.target_id.clone(), binding.layer_id, binding.action.clone()));
+      key.set_running(self.keep_running);
+      Ok(())
+    }
+  }
+
+  fn entry(target_id: &str, binding: Binding) -> BindingEntry {
+    BindingEntry { target_id: target_id.into(), layer_id: None, binding }
+  }
+
+  fn script(id: &str, target: ExecutionTarget) -> Script {
+    Script {
+      id: id.into(),
+      profile_id: "p1".into(),
+      name: id.into(),
+      target,
+      steps: vec![Step { id: 1, name: "Tap".into(), op: "TAP".into(), arg: Some("KC_A".into()), class: None, ..Default::default() }],
+      meta: None,
+    }
+  }
+
+  #[test]
+  fn maps_via_and_function_key_triggers_to_bindings() {
+    let script_ref = |id: &str, target| Binding::ScriptRef { script_id: id.into(), target, meta: None };
+    let layers = vec![
+      LayerState {
+        id: 1,
+        bindings: vec![entry("K2", Binding::Program { path: "/usr/bin/env".into(), meta: None })],
+      },
+      LayerState {
+        id: 0,
+        bindings: vec![
+          entry("K3", script_ref("device-macro", None)),
+          entry("K1", script_ref("host-macro", None)),
+          // Overridden to run on the host, so it needs a slot too.
+          entry("K4", script_ref("device-macro", Some(ExecutionTarget::Host))),
+        ],
+      },
+    ];
+    let scripts = vec![script("host-macro", ExecutionTarget::Host), script("device-macro", ExecutionTarget::Device)];
+    let map = TriggerMap::build(&layers, &scripts);
+    let slots: Vec<_> = map.bindings().iter().map(|b| (b.slot, b.target_id.as_str(), b.layer_id)).collect();
+    assert_eq!(slots, vec![(0, "K1", 0), (1, "K4", 0), (2, "K2", 1)]);
+
+    let (tx, mut source) = SimulatedTriggers::new();
+    for trigger in [
+      RawTrigger::Via(vec![VIA_CUSTOM_SET_VALUE, HOST_TRIGGER_CHANNEL, 2, 1]),
+      RawTrigger::Via(vec![VIA_CUSTOM_SET_VALUE, HOST_TRIGGER_CHANNEL, 2, 0]),
+      RawTrigger::Keycode { keycode: 0x68, pressed: true },
+      RawTrigger::Keycode { keycode: 0x68, pressed: false },
+      RawTrigger::Keycode { keycode: 0x04, pressed: true },
+      RawTrigger::Keycode { keycode: 0x69, pressed: true },
+      RawTrigger::Keycode { keycode: 0x73, pressed: true },
+    ] {
+      tx.send(trigger).unwrap();
+    }
+    drop(tx);
+
+    let dispatcher = RecordingDispatcher::default();
+    let err = listen(&mut source, &|| Ok(Some(map.clone())), &dispatcher, &OperationContext::new()).unwrap_err();
+    assert_eq!(err.to_string(), "Trigger source closed");
+    let program = HostAction::Program(LaunchSpec::from_binding("/usr/bin/env", None).unwrap());
+    assert_eq!(
+      dispatcher.dispatched.lock().unwrap().clone(),
+      vec![
+        ("K2".to_string(), 1, program),
+        ("K1".to_string(), 0, HostAction::Script("host-macro".into())),
+        ("K4".to_string(), 0, HostAction::Script("device-macro".into())),
+      ]
+    );
+  }
+
+  #[test]
+  fn survives_map_errors_drops_keys_on_remap_and_ends_with_the_session() {
+    let map_for = |target_id: &str, script_id: &str| {
+      let layers = vec![LayerState {
+        id: 0,
+        bindings: vec![entry(target_id, Binding::ScriptRef { script_id: script_id.into(), target: None, meta: None })],
+      }];
+      TriggerMap::build(&layers, &[script(script_id, ExecutionTarget::Host)])
+    };
+    let (first, remapped) = (map_for("K1", "host-macro"), map_for("K9", "other-macro"));
+    let reads = Mutex::new(std::collections::VecDeque::from([
+      Err(anyhow!("store busy")),
+      Ok(Some(first.clone())),
+      Ok(Some(first)),
+      Ok(Some(remapped)),
+      Ok(None),
+    ]));
+
+    let (tx, mut source) = SimulatedTriggers::new();
+    for _ in 0..5 {
+      tx.send(RawTrigger::Via(vec![VIA_CUSTOM_SET_VALUE, HOST_TRIGGER_CHANNEL, 0, 1])).unwrap();
+    }
+    let dispatcher = RecordingDispatcher { keep_running: true, ..Default::default() };
+    let map = || reads.lock().unwrap().pop_front().expect("read per press");
+    listen(&mut source, &map, &dispatcher, &OperationContext::new()).expect("ends with the session");
+
+    // The third press went to K1's running script; the fourth found slot 0 remapped.
+    let targets: Vec<String> = dispatcher.dispatched.lock().unwrap().iter().map(|d| d.0.clone()).collect();
+    assert_eq!(targets, ["K1", "K9"]);
+  }
+
+  #[test]
+  fn script_scope_stops_scripts_and_follows_the_listener() {
+    let stop = OperationContext::new();
+    let scope = ScriptScope::new(&stop);
+    let first = scope.context();
+    scope.stop_all();
+    assert!(first.is_cancelled());
+    let second = scope.context();
+    assert!(!second.is_cancelled(), "later presses start fresh");
+    stop.cancel();
+    assert!(second.is_cancelled());
+  }
+}
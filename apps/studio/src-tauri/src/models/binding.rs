@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::script::Step;
+use super::script::{ExecutionTarget, Step};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -11,6 +11,9 @@ pub enum Binding {
   ScriptRef {
     #[serde(rename = "scriptId")]
     script_id: String,
+    /// Overrides the script's own target for this binding.
+    #[serde(default)]
+    target: Option<ExecutionTarget>,
     #[serde(default)]
     meta: Option<serde_json::Map<String, serde_json::Value>>,
   },
@@ -25,6 +28,9 @@ pub enum Binding {
   #[serde(rename = "inlineSequence")]
   InlineSequence {
     steps: Vec<Step>,
+    /// Where the sequence runs; the device unless set.
+    #[serde(default)]
+    target: Option<ExecutionTarget>,
     #[serde(default)]
     meta: Option<serde_json::Map<String, serde_json::Value>>,
   },
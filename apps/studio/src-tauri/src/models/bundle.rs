@@ -2,9 +2,10 @@ use serde::{Deserialize, Serialize};
 
 use super::{
   binding::BindingEntry,
-  device::{Capabilities, DeviceInfo, DeviceState, LayerState},
+  device::{Capabilities, DeviceInfo, DeviceProbe, DeviceState, LayerState},
   layout::NormalizedLayout,
-  script::Script,
+  script::{DelayClass, Script, ScriptWarning},
+  state::StateRecovery,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +13,8 @@ pub struct Profile {
   pub id: String,
   pub name: String,
   pub layers: Vec<LayerState>,
+  #[serde(rename = "delayClasses", default)]
+  pub delay_classes: Vec<DelayClass>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,10 +23,16 @@ pub struct ProfileBundle {
   pub session_id: String,
   pub device: DeviceInfo,
   pub capabilities: Capabilities,
+  /// Raw probe answers the capabilities were negotiated from, if any.
+  #[serde(default)]
+  pub probe: Option<DeviceProbe>,
   pub profile: Profile,
   pub layout: Option<NormalizedLayout>,
   pub targets: Vec<String>,
   pub scripts: Vec<Script>,
+  /// Steps that can't run on the target each script is used with.
+  #[serde(rename = "scriptWarnings", default)]
+  pub script_warnings: Vec<ScriptWarning>,
   #[serde(rename = "committedState")]
   pub committed_state: Option<DeviceState>,
   #[serde(rename = "appliedState")]
@@ -34,4 +43,6 @@ pub struct ProfileBundle {
   #[serde(rename = "bindings")]
   #[serde(default)]
   pub bindings: Vec<BindingEntry>,
+  #[serde(default)]
+  pub recovery: Option<StateRecovery>,
 }
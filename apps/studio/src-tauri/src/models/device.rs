@@ -24,14 +24,60 @@ pub struct Capabilities {
   pub layouts: bool,
   pub keymap: bool,
   pub scripts: bool,
+  #[serde(default)]
+  pub encoders: bool,
+  #[serde(rename = "maxLayers", default)]
+  pub max_layers: Option<u32>,
+  #[serde(rename = "macroBufferBytes", default)]
+  pub macro_buffer_bytes: Option<u32>,
+  #[serde(rename = "macroCount", default)]
+  pub macro_count: Option<u32>,
+}
+
+/// What a device reports about itself over the configuration protocol.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceProbe {
+  #[serde(rename = "protocolVersion")]
+  pub protocol_version: u16,
+  /// Firmware feature bits, see `backends::capabilities`.
+  #[serde(rename = "featureFlags", default)]
+  pub feature_flags: u32,
+  #[serde(rename = "layerCount")]
+  pub layer_count: u32,
+  #[serde(rename = "macroCount", default)]
+  pub macro_count: u32,
+  #[serde(rename = "macroBufferBytes", default)]
+  pub macro_buffer_bytes: u32,
+  #[serde(rename = "encoderCount", default)]
+  pub encoder_count: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredLayerState")]
 pub struct LayerState {
   pub id: i32,
   pub bindings: Vec<BindingEntry>,
 }
 
+/// `LayerState` as written to disk; bindings may omit their layer's id.
+#[derive(Deserialize)]
+struct StoredLayerState {
+  id: i32,
+  bindings: Vec<BindingEntry>,
+}
+
+impl From<StoredLayerState> for LayerState {
+  fn from(stored: StoredLayerState) -> Self {
+    let id = stored.id;
+    let bindings = stored
+      .bindings
+      .into_iter()
+      .map(|entry| BindingEntry { layer_id: entry.layer_id.or(Some(id)), ..entry })
+      .collect();
+    Self { id, bindings }
+  }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceState {
   #[serde(rename = "profileId")]
@@ -41,4 +87,56 @@ pub struct DeviceState {
   pub revision: Option<i32>,
   #[serde(default)]
   pub checksum: Option<u32>,
+  #[serde(default)]
+  pub meta: Option<CommitMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitMeta {
+  #[serde(default)]
+  pub message: Option<String>,
+  #[serde(default)]
+  pub author: Option<String>,
+  #[serde(default)]
+  pub host: Option<String>,
+  #[serde(rename = "appVersion", default)]
+  pub app_version: Option<String>,
+  #[serde(rename = "committedAt", default)]
+  pub committed_at: Option<u64>,
+}
+
+impl CommitMeta {
+  /// Captures who/where/when for a commit. `committed_at` is unix millis.
+  pub fn capture(message: Option<String>) -> Self {
+    let message = message
+      .map(|m| m.trim().to_string())
+      .filter(|m| !m.is_empty());
+    let committed_at = std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .map(|d| d.as_millis() as u64)
+      .ok();
+
+    Self {
+      message,
+      author: env_first(&["USER", "USERNAME"]),
+      host: env_first(&["HOSTNAME", "COMPUTERNAME"]).or_else(read_hostname_file),
+      app_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+      committed_at,
+    }
+  }
+}
+
+fn env_first(keys: &[&str]) -> Option<String> {
+  keys
+    .iter()
+    .filter_map(|k| std::env::var(k).ok())
+    .map(|v| v.trim().to_string())
+    .find(|v| !v.is_empty())
+}
+
+fn read_hostname_file() -> Option<String> {
+  std::fs::read_to_string("/etc/hostname")
+    .ok()
+    .map(|h| h.trim().to_string())
+    .filter(|h| !h.is_empty())
 }
@@ -32,8 +32,7 @@ pub struct KeyElement {
   pub raw_label: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "kind")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ControlKind {
   #[serde(rename = "block")]
   Block,
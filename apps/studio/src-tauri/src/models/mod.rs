@@ -4,3 +4,4 @@ pub mod binding;
 pub mod script;
 pub mod layout;
 pub mod state;
+pub mod verify;
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+  pub id: u32,
+  pub name: String,
+  pub op: String,
+  #[serde(default)]
+  pub arg: Option<String>,
+  #[serde(default)]
+  pub class: Option<u32>,
+  /// Random extra delay of up to this many ms either way, for `WAIT`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub jitter: Option<u32>,
+  /// Body of `REPEAT`, `WHILE_HELD` and `TOGGLE`.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub steps: Vec<Step>,
+}
+
+/// A named delay that `WAIT` steps opt into with `class`, so every wait of
+/// one kind can be retuned in one place.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DelayClass {
+  pub id: u32,
+  pub name: String,
+  #[serde(rename = "baseMs")]
+  pub base_ms: u32,
+  /// Random extra delay of up to this many ms either way, on the host.
+  #[serde(default)]
+  pub jitter: u32,
+  /// Multiplier on `base_ms`, e.g. 1.5 to slow the class down.
+  #[serde(default = "DelayClass::default_scale")]
+  pub scale: f64,
+}
+
+impl DelayClass {
+  fn default_scale() -> f64 {
+    1.0
+  }
+}
+
+/// Where a script runs: compiled into the keyboard's macro buffer, or played
+/// by the studio through the host input injector.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ExecutionTarget {
+  #[default]
+  #[serde(rename = "device")]
+  Device,
+  #[serde(rename = "host")]
+  Host,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Script {
+  pub id: String,
+  #[serde(rename = "profileId")]
+  pub profile_id: String,
+  pub name: String,
+  #[serde(default)]
+  pub target: ExecutionTarget,
+  pub steps: Vec<Step>,
+  #[serde(default)]
+  pub meta: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// A step that the script's execution target cannot perform.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptWarning {
+  #[serde(rename = "scriptId")]
+  pub script_id: String,
+  #[serde(rename = "stepId", default)]
+  pub step_id: Option<u32>,
+  pub target: ExecutionTarget,
+  pub message: String,
+}
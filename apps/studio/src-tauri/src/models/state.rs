@@ -4,7 +4,7 @@ use super::device::DeviceState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionState {
-  #[serde(default = "SessionState::current_version")]
+  #[serde(default = "SessionState::unversioned")]
   pub version: u32,
   #[serde(rename = "sessionId")]
   pub session_id: String,
@@ -20,4 +20,35 @@ impl SessionState {
   pub const fn current_version() -> u32 {
     1
   }
+
+  /// Schema of files written before `version` existed.
+  pub const fn unversioned() -> u32 {
+    1
+  }
+
+  /// Revision of the committed state, `0` before the first commit.
+  pub fn committed_revision(&self) -> i32 {
+    self.committed.as_ref().and_then(|c| c.revision).unwrap_or(0)
+  }
+}
+
+/// Optional preconditions sent with a mutating command. A mismatch is
+/// rejected with a conflict instead of overwriting newer state.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MutationGuard {
+  #[serde(rename = "expectedChecksum", default)]
+  pub expected_checksum: Option<u32>,
+  #[serde(rename = "expectedRevision", default)]
+  pub expected_revision: Option<i32>,
+}
+
+/// A state file that failed to parse and was restored from an older generation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateRecovery {
+  pub file: String,
+  #[serde(rename = "quarantinedAs")]
+  pub quarantined_as: String,
+  #[serde(rename = "restoredGeneration")]
+  pub restored_generation: u32,
+  pub error: String,
 }
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use super::binding::Binding;
+
+/// Where a keymap lives on the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryRegion {
+  /// Volatile keymap written by `apply_to_ram`.
+  #[serde(rename = "ram")]
+  Ram,
+  /// Persistent keymap written by `commit`.
+  #[serde(rename = "eeprom")]
+  Eeprom,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyMismatch {
+  #[serde(rename = "layerId")]
+  pub layer_id: i32,
+  #[serde(rename = "targetId")]
+  pub target_id: String,
+  pub expected: Option<Binding>,
+  pub actual: Option<Binding>,
+}
+
+/// Result of reading a keymap back and comparing it with what was sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyReport {
+  pub region: MemoryRegion,
+  pub mismatches: Vec<KeyMismatch>,
+}
+
+impl VerifyReport {
+  pub fn is_ok(&self) -> bool {
+    self.mismatches.is_empty()
+  }
+}
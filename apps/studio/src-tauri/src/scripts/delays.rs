@@ -0,0 +1,100 @@
+//! Delay classes: a `WAIT` with a `class` found in the profile's table waits
+//! the class's `baseMs × scale`, with the class's jitter unless the step sets
+//! its own. The step's own delay is only used when its class is missing.
+
+use std::collections::BTreeSet;
+
+use anyhow::bail;
+
+use crate::models::script::{DelayClass, ExecutionTarget, Script, Step};
+
+/// `script` with every classed `WAIT` replaced by the delay it resolves to.
+/// The firmware only has fixed delays, so device scripts get no class jitter.
+pub fn resolve_script(script: &Script, classes: &[DelayClass], target: ExecutionTarget) -> Script {
+  Script {
+    steps: resolve_steps(&script.steps, classes, target),
+    ..script.clone()
+  }
+}
+
+fn resolve_steps(steps: &[Step], classes: &[DelayClass], target: ExecutionTarget) -> Vec<Step> {
+  steps
+    .iter()
+    .map(|step| {
+      let mut step = step.clone();
+      step.steps = resolve_steps(&step.steps, classes, target);
+      let class = step.class.and_then(|id| classes.iter().find(|c| c.id == id));
+      if let (true, Some(class)) = (step.op == "WAIT", class) {
+        step.arg = Some(class_ms(class).to_string());
+        if target == ExecutionTarget::Host && step.jitter.is_none() && class.jitter > 0 {
+          step.jitter = Some(class.jitter);
+        }
+      }
+      step
+    })
+    .collect()
+}
+
+/// The class's scaled delay, rounded to whole milliseconds.
+pub fn class_ms(class: &DelayClass) -> u32 {
+  (class.base_ms as f64 * class.scale).round().clamp(0.0, u32::MAX as f64) as u32
+}
+
+/// Rejects tables the resolver can't use unambiguously.
+pub fn check_classes(classes: &[DelayClass]) -> anyhow::Result<()> {
+  let mut ids = BTreeSet::new();
+  for class in classes {
+    if !ids.insert(class.id) {
+      bail!("Delay class {} is defined twice", class.id);
+    }
+    if class.name.trim().is_empty() {
+      bail!("Delay class {} needs a name", class.id);
+    }
+    if !class.scale.is_finite() || class.scale < 0.0 {
+      bail!("Delay class {} has an invalid scale {}", class.name, class.scale);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn wait(id: u32, ms: &str, class: Option<u32>) -> Step {
+    Step { id, name: format!("Wait {}ms", ms), op: "WAIT".into(), arg: Some(ms.into()), class, ..Default::default() }
+  }
+
+  #[test]
+  fn classed_waits_follow_the_table() {
+    let short = DelayClass { id: 1, name: "Short wait".into(), base_ms: 40, jitter: 5, scale: 1.5 };
+    let script = Script {
+      id: "s1".into(),
+      profile_id: "p1".into(),
+      name: "Waits".into(),
+      target: ExecutionTarget::Host,
+      steps: vec![
+        wait(1, "30", Some(1)),
+        wait(2, "30", None),
+        wait(3, "30", Some(9)),
+        Step { id: 4, name: "Repeat".into(), op: "REPEAT".into(), arg: Some("2".into()), steps: vec![wait(5, "10", Some(1))], ..Default::default() },
+      ],
+      meta: None,
+    };
+    let classes = std::slice::from_ref(&short);
+
+    let host = resolve_script(&script, classes, ExecutionTarget::Host);
+    let resolved = |s: &Step| (s.arg.clone().unwrap(), s.jitter);
+    assert_eq!(resolved(&host.steps[0]), ("60".to_string(), Some(5)));
+    assert_eq!(resolved(&host.steps[1]), ("30".to_string(), None));
+    assert_eq!(resolved(&host.steps[2]), ("30".to_string(), None));
+    assert_eq!(resolved(&host.steps[3].steps[0]), ("60".to_string(), Some(5)));
+
+    let device = resolve_script(&script, classes, ExecutionTarget::Device);
+    assert_eq!(resolved(&device.steps[0]), ("60".to_string(), None));
+
+    assert!(check_classes(classes).is_ok());
+    assert!(check_classes(&[short.clone(), short.clone()]).is_err());
+    assert!(check_classes(&[DelayClass { scale: f64::NAN, ..short }]).is_err());
+  }
+}
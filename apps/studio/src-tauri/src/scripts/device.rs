@@ -0,0 +1,59 @@
+use anyhow::bail;
+
+use crate::{
+  models::{
+    device::{Capabilities, LayerState},
+    script::{DelayClass, ExecutionTarget, Script},
+  },
+  via::macros::encode_buffer,
+};
+
+use super::{
+  delays::resolve_script,
+  inline::inline_scripts,
+  validate::{targets_in_use, validate_script},
+};
+
+/// Macro buffer size assumed when the device did not report one.
+pub const DEFAULT_MACRO_BUFFER_BYTES: u32 = 1024;
+/// Macro slot count assumed when the device did not report one.
+pub const DEFAULT_MACRO_COUNT: u32 = 16;
+
+/// Scripts that end up in the firmware macro buffer, in slot order.
+pub fn device_scripts<'a>(scripts: &'a [Script], layers: &[LayerState]) -> Vec<&'a Script> {
+  scripts
+    .iter()
+    .filter(|s| targets_in_use(s, layers).contains(&ExecutionTarget::Device))
+    .collect()
+}
+
+/// Compiles the device-side scripts, then device-side inline sequences, into
+/// a full macro buffer for the device, with classed waits resolved against
+/// `delays`.
+pub fn compile_macro_buffer(
+  scripts: &[Script],
+  layers: &[LayerState],
+  caps: &Capabilities,
+  delays: &[DelayClass],
+) -> anyhow::Result<Vec<u8>> {
+  let mut selected: Vec<Script> = device_scripts(scripts, layers).into_iter().cloned().collect();
+  selected.extend(inline_scripts("", layers).into_iter().filter(|s| s.target == ExecutionTarget::Device));
+  let selected: Vec<Script> = selected
+    .iter()
+    .map(|s| resolve_script(s, delays, ExecutionTarget::Device))
+    .collect();
+  if selected.is_empty() {
+    return Ok(Vec::new());
+  }
+  if !caps.scripts {
+    bail!("Device does not support macros, {} device scripts cannot be stored", selected.len());
+  }
+  for script in &selected {
+    if let Some(warning) = validate_script(script, ExecutionTarget::Device).into_iter().next() {
+      bail!("Script {} step {:?}: {}", script.id, warning.step_id, warning.message);
+    }
+  }
+  let capacity = caps.macro_buffer_bytes.unwrap_or(DEFAULT_MACRO_BUFFER_BYTES) as usize;
+  let count = caps.macro_count.unwrap_or(DEFAULT_MACRO_COUNT) as usize;
+  encode_buffer(&selected, capacity, count)
+}
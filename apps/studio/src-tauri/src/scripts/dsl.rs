@@ -0,0 +1,497 @@
+//! Text form of script steps, one statement per line or separated by `;`:
+//!
+//! ```text
+//! tap KC_SPACE; wait 30ms class 1
+//! down LSFT; tap A; up LSFT   # comments run to the end of the line
+//! text "hello\n"
+//! while_held {
+//!   repeat 3 { tap A }
+//!   wait 50ms jitter 10ms
+//! }
+//! ```
+//!
+//! Keycodes are resolved against the catalog and stored by their catalog id,
+//! so `tap A` and `tap KC_A` parse to the same step.
+
+use std::fmt;
+
+use anyhow::bail;
+use serde::Serialize;
+
+use crate::{models::script::Step, via::keycodes::KeycodeTable};
+
+/// A parse failure at a 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DslError {
+  pub line: u32,
+  pub column: u32,
+  pub message: String,
+}
+
+impl fmt::Display for DslError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "line {}:{}: {}", self.line, self.column, self.message)
+  }
+}
+
+impl std::error::Error for DslError {}
+
+/// Deepest nesting of `{ }` blocks the parser accepts.
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pos {
+  line: u32,
+  column: u32,
+}
+
+impl Pos {
+  fn error(self, message: impl Into<String>) -> DslError {
+    DslError { line: self.line, column: self.column, message: message.into() }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+  Word(String),
+  Str(String),
+  /// `;` or a line break.
+  End,
+  Open,
+  Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, Pos)>, DslError> {
+  let mut tokens = Vec::new();
+  let mut pos = Pos { line: 1, column: 1 };
+  let mut chars = text.chars().peekable();
+  let advance = |c: char, pos: &mut Pos| {
+    if c == '\n' {
+      pos.line += 1;
+      pos.column = 1;
+    } else {
+      pos.column += 1;
+    }
+  };
+
+  while let Some(&c) = chars.peek() {
+    let start = pos;
+    match c {
+      '\n' | ';' => {
+        tokens.push((Token::End, start));
+        chars.next();
+        advance(c, &mut pos);
+      }
+      '{' | '}' => {
+        tokens.push((if c == '{' { Token::Open } else { Token::Close }, start));
+        chars.next();
+        advance(c, &mut pos);
+      }
+      '#' => {
+        while let Some(&c) = chars.peek() {
+          if c == '\n' {
+            break;
+          }
+          chars.next();
+          advance(c, &mut pos);
+        }
+      }
+      '"' => {
+        chars.next();
+        advance(c, &mut pos);
+        let mut value = String::new();
+        loop {
+          let Some(c) = chars.next() else {
+            return Err(start.error("Unterminated string"));
+          };
+          let at = pos;
+          advance(c, &mut pos);
+          match c {
+            '"' => break,
+            '\n' => return Err(start.error("Unterminated string")),
+            '\\' => {
+              let Some(escaped) = chars.next() else {
+                return Err(start.error("Unterminated string"));
+              };
+              advance(escaped, &mut pos);
+              value.push(match escaped {
+                'n' => '\n',
+                't' => '\t',
+                '"' => '"',
+                '\\' => '\\',
+                other => return Err(at.error(format!("Unknown escape \\{}", other))),
+              });
+            }
+            c => value.push(c),
+          }
+        }
+        tokens.push((Token::Str(value), start));
+      }
+      c if c.is_whitespace() => {
+        chars.next();
+        advance(c, &mut pos);
+      }
+      _ => {
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+          if c.is_whitespace() || matches!(c, ';' | '#' | '"' | '{' | '}') {
+            break;
+          }
+          word.push(c);
+          chars.next();
+          advance(c, &mut pos);
+        }
+        tokens.push((Token::Word(word), start));
+      }
+    }
+  }
+  tokens.push((Token::End, pos));
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<(Token, Pos)>,
+  at: usize,
+  next_id: u32,
+  /// Blocks open around the current statement.
+  depth: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> &(Token, Pos) {
+    &self.tokens[self.at.min(self.tokens.len() - 1)]
+  }
+
+  fn next(&mut self) -> (Token, Pos) {
+    let token = self.peek().clone();
+    self.at += 1;
+    token
+  }
+
+  fn word(&mut self, what: &str) -> Result<(String, Pos), DslError> {
+    match self.next() {
+      (Token::Word(word), pos) => Ok((word, pos)),
+      (_, pos) => Err(pos.error(format!("Expected {}", what))),
+    }
+  }
+
+  fn string(&mut self, what: &str) -> Result<String, DslError> {
+    match self.next() {
+      (Token::Str(value), _) => Ok(value),
+      (_, pos) => Err(pos.error(format!("Expected {} in quotes", what))),
+    }
+  }
+
+  /// Consumes `keyword` and its argument word if it comes next.
+  fn option(&mut self, keyword: &str, what: &str) -> Result<Option<(String, Pos)>, DslError> {
+    match self.peek() {
+      (Token::Word(w), _) if w.eq_ignore_ascii_case(keyword) => {
+        self.next();
+        self.word(what).map(Some)
+      }
+      _ => Ok(None),
+    }
+  }
+
+  /// A statement ends at `;`, a line break, or the `}` closing its block.
+  fn end_of_statement(&mut self) -> Result<(), DslError> {
+    match self.peek() {
+      (Token::Close, _) => Ok(()),
+      (Token::End, _) => {
+        self.next();
+        Ok(())
+      }
+      (_, pos) => Err(pos.error("Expected `;` or end of line")),
+    }
+  }
+
+  fn step(&mut self, op: &str, arg: String) -> Step {
+    self.next_id += 1;
+    Step { id: self.next_id, name: step_name(op, &arg), op: op.to_string(), arg: Some(arg), ..Default::default() }
+  }
+
+  /// Statements up to the `}` matching the `{` at `open`, or to the end of
+  /// the text at the top level.
+  fn block(&mut self, open: Option<Pos>) -> Result<Vec<Step>, DslError> {
+    let mut steps = Vec::new();
+    loop {
+      match self.peek().clone() {
+        _ if self.at >= self.tokens.len() => {
+          return match open {
+            Some(pos) => Err(pos.error("Unclosed `{`")),
+            None => Ok(steps),
+          };
+        }
+        (Token::End, _) => {
+          self.next();
+        }
+        (Token::Close, pos) => {
+          if open.is_none() {
+            return Err(pos.error("Unexpected `}`"));
+          }
+          self.next();
+          return Ok(steps);
+        }
+        _ => steps.push(self.statement()?),
+      }
+    }
+  }
+
+  fn body(&mut self, mut step: Step) -> Result<Step, DslError> {
+    match self.next() {
+      (Token::Open, pos) => {
+        if self.depth >= MAX_DEPTH {
+          return Err(pos.error(format!("Blocks nest deeper than {} levels", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        step.steps = self.block(Some(pos))?;
+        self.depth -= 1;
+        Ok(step)
+      }
+      (_, pos) => Err(pos.error("Expected `{`")),
+    }
+  }
+
+  fn statement(&mut self) -> Result<Step, DslError> {
+    let (keyword, pos) = self.word("a step")?;
+    let step = match keyword.to_ascii_lowercase().as_str() {
+      kind @ ("tap" | "down" | "up") => {
+        let (name, at) = self.word("a keycode")?;
+        let code = KeycodeTable::get().code(&name).ok_or_else(|| at.error(format!("Unknown keycode {}", name)))?;
+        let arg = if name.starts_with("0x") || name.starts_with("0X") { name } else { KeycodeTable::get().name(code) };
+        self.step(&kind.to_ascii_uppercase(), arg)
+      }
+      "wait" => {
+        let (duration, at) = self.word("a duration")?;
+        let ms = duration_ms(&duration, at)?;
+        let ms = u32::try_from(ms).map_err(|_| at.error(format!("Wait {} is too long", duration)))?;
+        let mut step = self.step("WAIT", ms.to_string());
+        // `class` and `jitter` may come in either order.
+        for _ in 0..2 {
+          if let Some((class, at)) = self.option("class", "a delay class")? {
+            step.class = Some(class.parse().map_err(|_| at.error(format!("Invalid delay class {}", class)))?);
+          }
+          if let Some((jitter, at)) = self.option("jitter", "a duration")? {
+            let ms = duration_ms(&jitter, at)?;
+            step.jitter = Some(u32::try_from(ms).map_err(|_| at.error(format!("Jitter {} is too long", jitter)))?);
+          }
+        }
+        step
+      }
+      kind @ ("text" | "paste_text" | "sarcasify_text" | "open_file" | "open_folder" | "open_website") => {
+        let value = self.string("text")?;
+        self.step(&kind.to_ascii_uppercase(), value)
+      }
+      "repeat" => {
+        let (count, at) = self.word("a count or forever")?;
+        if !count.eq_ignore_ascii_case("forever") && count.parse::<u32>().is_err() {
+          return Err(at.error(format!("Invalid repeat count {}", count)));
+        }
+        let step = self.step("REPEAT", count.to_ascii_lowercase());
+        self.body(step)?
+      }
+      kind @ ("while_held" | "toggle") => {
+        self.next_id += 1;
+        let op = kind.to_ascii_uppercase();
+        let step = Step { id: self.next_id, name: step_name(&op, ""), op, ..Default::default() };
+        self.body(step)?
+      }
+      _ => return Err(pos.error(format!("Unknown step {}", keyword))),
+    };
+    self.end_of_statement()?;
+    Ok(step)
+  }
+}
+
+fn duration_ms(text: &str, at: Pos) -> Result<u64, DslError> {
+  parse_duration(text).ok_or_else(|| at.error(format!("Invalid duration {}; use e.g. 30ms or 2s", text)))
+}
+
+fn parse_duration(text: &str) -> Option<u64> {
+  if let Some(ms) = text.strip_suffix("ms") {
+    return ms.parse().ok();
+  }
+  if let Some(s) = text.strip_suffix('s') {
+    return s.parse::<u64>().ok()?.checked_mul(1000);
+  }
+  text.parse().ok()
+}
+
+/// Step name in the style the editor uses, e.g. `Tap SPACE`, `Wait 30ms`.
+fn step_name(op: &str, arg: &str) -> String {
+  let mut verb: String = op.replace('_', " ").to_ascii_lowercase();
+  if let Some(first) = verb.get_mut(0..1) {
+    first.make_ascii_uppercase();
+  }
+  match op {
+    "TAP" | "DOWN" | "UP" => format!("{} {}", verb, arg.strip_prefix("KC_").unwrap_or(arg)),
+    "WAIT" => format!("{} {}ms", verb, arg),
+    "REPEAT" if arg == "forever" => format!("{} forever", verb),
+    "REPEAT" => format!("{} {} times", verb, arg),
+    "WHILE_HELD" | "TOGGLE" => verb,
+    _ => format!("{} {:?}", verb, arg),
+  }
+}
+
+/// Parses script text into steps numbered from 1, nested steps included.
+pub fn parse(text: &str) -> Result<Vec<Step>, DslError> {
+  Parser { tokens: tokenize(text)?, at: 0, next_id: 0, depth: 0 }.block(None)
+}
+
+/// Prints steps one per line in the form `parse` reads back. Fails on steps
+/// the text form can't carry, rather than printing text that parses to
+/// something else. Step names and ids are regenerated by `parse`.
+pub fn format(steps: &[Step]) -> anyhow::Result<String> {
+  let mut out = String::new();
+  format_into(&mut out, steps, 0)?;
+  Ok(out)
+}
+
+fn format_into(out: &mut String, steps: &[Step], depth: usize) -> anyhow::Result<()> {
+  for step in steps {
+    check_representable(step, depth)?;
+    let arg = step.arg.as_deref().unwrap_or("");
+    let op = step.op.to_ascii_lowercase();
+    out.push_str(&"  ".repeat(depth));
+    match step.op.as_str() {
+      "TAP" | "DOWN" | "UP" => out.push_str(&format!("{} {}", op, arg)),
+      "WAIT" => {
+        out.push_str(&format!("wait {}ms", arg));
+        if let Some(class) = step.class {
+          out.push_str(&format!(" class {}", class));
+        }
+        if let Some(jitter) = step.jitter {
+          out.push_str(&format!(" jitter {}ms", jitter));
+        }
+      }
+      "REPEAT" | "WHILE_HELD" | "TOGGLE" => {
+        out.push_str(&op);
+        if !arg.is_empty() {
+          out.push_str(&format!(" {}", arg));
+        }
+        out.push_str(" {\n");
+        format_into(out, &step.steps, depth + 1)?;
+        out.push_str(&"  ".repeat(depth));
+        out.push('}');
+      }
+      _ => out.push_str(&format!("{} {}", op, quote(arg))),
+    }
+    out.push('\n');
+  }
+  Ok(())
+}
+
+const TEXT_OPS: &[&str] = &["TEXT", "PASTE_TEXT", "SARCASIFY_TEXT", "OPEN_FILE", "OPEN_FOLDER", "OPEN_WEBSITE"];
+
+/// Fails when `step` would print as text that `parse` rejects or reads back differently.
+fn check_representable(step: &Step, depth: usize) -> anyhow::Result<()> {
+  let op = step.op.as_str();
+  let arg = step.arg.as_deref();
+  let block = matches!(op, "REPEAT" | "WHILE_HELD" | "TOGGLE");
+  let problem = match op {
+    "TAP" | "DOWN" | "UP" => match arg {
+      Some(name) if KeycodeTable::get().code(name).is_some() => None,
+      _ => Some(format!("needs a keycode, got {:?}", arg)),
+    },
+    "WAIT" => match arg.map(str::parse::<u32>) {
+      Some(Ok(_)) => None,
+      _ => Some(format!("needs a delay in ms, got {:?}", arg)),
+    },
+    "REPEAT" => match arg {
+      Some(count) if count == "forever" || count.parse::<u32>().is_ok() => None,
+      _ => Some(format!("needs a count or forever, got {:?}", arg)),
+    },
+    "WHILE_HELD" | "TOGGLE" if arg.is_some_and(|a| !a.is_empty()) => Some("takes no argument".to_string()),
+    "WHILE_HELD" | "TOGGLE" => None,
+    _ if TEXT_OPS.contains(&op) => arg.is_none().then(|| "needs text".to_string()),
+    _ => Some("has no text form".to_string()),
+  }
+  .or_else(|| (!block && !step.steps.is_empty()).then(|| "can't hold nested steps".to_string()))
+  .or_else(|| (op != "WAIT" && (step.class.is_some() || step.jitter.is_some())).then(|| "only WAIT takes class or jitter".to_string()))
+  .or_else(|| (block && depth >= MAX_DEPTH).then(|| format!("nests deeper than {} levels", MAX_DEPTH)));
+  if let Some(problem) = problem {
+    bail!("Step {} ({}) {}", step.id, step.op, problem);
+  }
+  Ok(())
+}
+
+fn quote(text: &str) -> String {
+  let mut out = String::from('"');
+  for c in text.chars() {
+    match c {
+      '\n' => out.push_str("\\n"),
+      '\t' => out.push_str("\\t"),
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_formats_and_reports_positions() {
+    let steps = parse("tap KC_SPACE; wait 30ms class 1\ndown LSFT; tap A; up LSFT # shout\n\ntext \"hi \\\"you\\\"\\n\"").unwrap();
+    let ops: Vec<_> = steps.iter().map(|s| (s.op.as_str(), s.arg.as_deref().unwrap(), s.class)).collect();
+    assert_eq!(
+      ops,
+      vec![
+        ("TAP", "KC_SPACE", None),
+        ("WAIT", "30", Some(1)),
+        ("DOWN", "KC_LSFT", None),
+        ("TAP", "KC_A", None),
+        ("UP", "KC_LSFT", None),
+        ("TEXT", "hi \"you\"\n", None),
+      ]
+    );
+    assert_eq!(steps[0].name, "Tap SPACE");
+    assert_eq!(steps[1].name, "Wait 30ms");
+    assert_eq!(steps.iter().map(|s| s.id).collect::<Vec<_>>(), (1..=6).collect::<Vec<_>>());
+
+    let text = format(&steps).unwrap();
+    assert_eq!(parse(&text).unwrap(), steps);
+    assert!(text.starts_with("tap KC_SPACE\nwait 30ms class 1\n"));
+
+    let err = |text: &str| parse(text).unwrap_err();
+    assert_eq!(err("tap KC_SPACE\n  tap NOPE").to_string(), "line 2:7: Unknown keycode NOPE");
+    assert_eq!(err("wait 3 minutes").to_string(), "line 1:8: Expected `;` or end of line");
+    assert_eq!(err("wait soon").to_string(), "line 1:6: Invalid duration soon; use e.g. 30ms or 2s");
+    assert_eq!(err("wait 5000000s").to_string(), "line 1:6: Wait 5000000s is too long");
+    assert_eq!(err("text \"open").to_string(), "line 1:6: Unterminated string");
+    assert_eq!(err("jump").to_string(), "line 1:1: Unknown step jump");
+
+    let steps = parse("toggle {\n  repeat 2 { tap A; wait 10ms }\n  wait 1s jitter 50ms\n}").unwrap();
+    assert_eq!(steps.len(), 1);
+    assert_eq!((steps[0].op.as_str(), steps[0].name.as_str()), ("TOGGLE", "Toggle"));
+    let repeat = &steps[0].steps[0];
+    assert_eq!((repeat.id, repeat.name.as_str(), repeat.steps.len()), (2, "Repeat 2 times", 2));
+    assert_eq!(steps[0].steps[1].jitter, Some(50));
+    let text = format(&steps).unwrap();
+    assert_eq!(parse(&text).unwrap(), steps);
+    assert!(text.contains("\n  repeat 2 {\n    tap KC_A\n"));
+    assert_eq!(err("repeat 2 {\n  tap A").to_string(), "line 1:10: Unclosed `{`");
+    assert_eq!(err("tap A }").to_string(), "line 1:7: Unexpected `}`");
+  }
+
+  #[test]
+  fn refuses_unrepresentable_steps_and_deep_nesting() {
+    let step = |op: &str, arg: Option<&str>| Step { id: 7, name: op.into(), op: op.into(), arg: arg.map(Into::into), ..Default::default() };
+    assert_eq!(format(&[step("JUMP", Some("x"))]).unwrap_err().to_string(), "Step 7 (JUMP) has no text form");
+    assert!(format(&[step("WAIT", None)]).is_err());
+    assert!(format(&[step("WAIT", Some("5000000000"))]).is_err());
+    assert!(format(&[step("TAP", Some("NOPE"))]).is_err());
+    let nested = Step { steps: vec![step("TAP", Some("KC_A"))], ..step("TAP", Some("KC_B")) };
+    assert_eq!(format(&[nested]).unwrap_err().to_string(), "Step 7 (TAP) can't hold nested steps");
+
+    let deep = |levels: usize| format!("{}tap A{}", "repeat 2 { ".repeat(levels), " }".repeat(levels));
+    assert!(parse(&deep(MAX_DEPTH)).is_ok());
+    let err = parse(&deep(MAX_DEPTH + 1)).unwrap_err();
+    assert_eq!((err.line, err.column), (1, 11 * MAX_DEPTH as u32 + 10));
+    assert_eq!(err.message, format!("Blocks nest deeper than {} levels", MAX_DEPTH));
+  }
+}
@@ -0,0 +1,359 @@
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+use uuid::Uuid;
+
+use crate::{
+  backends::operation::OperationContext,
+  models::script::{DelayClass, ExecutionTarget, Script, Step},
+  via::keycodes::KeycodeTable,
+};
+
+use super::{
+  delays::resolve_script,
+  validate::{repeat_count, validate_script},
+};
+
+/// Emits input on the computer the studio runs on. Keycodes are QMK basic
+/// keycodes (HID usages).
+pub trait HostInjector {
+  fn key_down(&self, keycode: u16) -> anyhow::Result<()>;
+  fn key_up(&self, keycode: u16) -> anyhow::Result<()>;
+  fn type_text(&self, text: &str) -> anyhow::Result<()>;
+  /// Opens a file, folder or URL with the system handler.
+  fn open(&self, target: &str) -> anyhow::Result<()>;
+}
+
+/// Injector that only logs, used until a platform injector is configured.
+pub struct LogInjector;
+
+impl HostInjector for LogInjector {
+  fn key_down(&self, keycode: u16) -> anyhow::Result<()> {
+    log::info!("host key down 0x{:04X}", keycode);
+    Ok(())
+  }
+
+  fn key_up(&self, keycode: u16) -> anyhow::Result<()> {
+    log::info!("host key up 0x{:04X}", keycode);
+    Ok(())
+  }
+
+  fn type_text(&self, text: &str) -> anyhow::Result<()> {
+    log::info!("host type {:?}", text);
+    Ok(())
+  }
+
+  fn open(&self, target: &str) -> anyhow::Result<()> {
+    log::info!("host open {}", target);
+    Ok(())
+  }
+}
+
+/// The platform injector when one can be created, otherwise `LogInjector`.
+pub fn default_injector() -> Arc<dyn HostInjector + Send + Sync> {
+  #[cfg(target_os = "linux")]
+  match super::uinput::UinputInjector::new() {
+    Ok(injector) => return Arc::new(injector),
+    Err(e) => log::warn!("Host scripts will only be logged: {e:#}"),
+  }
+  Arc::new(LogInjector)
+}
+
+/// State of the key that started a host script, read by `WHILE_HELD` and
+/// `TOGGLE`. Clones share state.
+#[derive(Debug, Clone, Default)]
+pub struct TriggerKey {
+  held: Arc<AtomicBool>,
+  toggled: Arc<AtomicBool>,
+  running: Arc<AtomicBool>,
+}
+
+impl TriggerKey {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The key went down: it is held and its toggle flips.
+  pub fn press(&self) {
+    self.held.store(true, Ordering::SeqCst);
+    self.toggled.fetch_xor(true, Ordering::SeqCst);
+  }
+
+  pub fn release(&self) {
+    self.held.store(false, Ordering::SeqCst);
+  }
+
+  pub fn is_held(&self) -> bool {
+    self.held.load(Ordering::SeqCst)
+  }
+
+  pub fn is_toggled_on(&self) -> bool {
+    self.toggled.load(Ordering::SeqCst)
+  }
+
+  /// Whether a script started by this key is still playing.
+  pub fn is_running(&self) -> bool {
+    self.running.load(Ordering::SeqCst)
+  }
+
+  pub fn set_running(&self, running: bool) {
+    self.running.store(running, Ordering::SeqCst);
+  }
+}
+
+/// Plays `script` through `injector`. Keys still held when the script ends,
+/// fails or is cancelled are released. Classed waits resolve against
+/// `delays`. With no trigger key, `WHILE_HELD` and `TOGGLE` bodies play once.
+pub fn run_host(
+  script: &Script,
+  delays: &[DelayClass],
+  injector: &dyn HostInjector,
+  ctx: &OperationContext,
+) -> anyhow::Result<()> {
+  run_host_triggered(script, delays, injector, ctx, &TriggerKey::new())
+}
+
+/// `run_host` for a script started by `key`.
+pub fn run_host_triggered(
+  script: &Script,
+  delays: &[DelayClass],
+  injector: &dyn HostInjector,
+  ctx: &OperationContext,
+  key: &TriggerKey,
+) -> anyhow::Result<()> {
+  let script = &resolve_script(script, delays, ExecutionTarget::Host);
+  if let Some(warning) = validate_script(script, ExecutionTarget::Host).into_iter().next() {
+    bail!("Script {} cannot run on the host: {}", script.id, warning.message);
+  }
+  let mut player = Player { injector, ctx, key, held: Vec::new() };
+  let total = script.steps.len() as u32;
+  let mut result = Ok(());
+  for (index, step) in script.steps.iter().enumerate() {
+    result = player.step(step, &|| false);
+    if result.is_err() {
+      break;
+    }
+    ctx.report("run-script", index as u32 + 1, total);
+  }
+  for keycode in player.held.into_iter().rev() {
+    if let Err(e) = injector.key_up(keycode) {
+      log::warn!("Failed to release 0x{:04X}: {:#}", keycode, e);
+    }
+  }
+  result
+}
+
+struct Player<'a> {
+  injector: &'a dyn HostInjector,
+  ctx: &'a OperationContext,
+  key: &'a TriggerKey,
+  held: Vec<u16>,
+}
+
+impl Player<'_> {
+  /// Plays `steps` once, returning early when `stop` says the enclosing loop is over.
+  fn steps(&mut self, steps: &[Step], stop: &dyn Fn() -> bool) -> anyhow::Result<()> {
+    for step in steps {
+      if stop() {
+        break;
+      }
+      self.step(step, stop)?;
+    }
+    Ok(())
+  }
+
+  fn step(&mut self, step: &Step, stop: &dyn Fn() -> bool) -> anyhow::Result<()> {
+    self.ctx.check_cancelled()?;
+    let table = KeycodeTable::get();
+    let arg = step.arg.as_deref().unwrap_or("");
+    let keycode = || table.code(arg).ok_or_else(|| anyhow!("Unknown keycode {:?}", arg));
+    match step.op.as_str() {
+      "TAP" => {
+        let code = keycode()?;
+        self.injector.key_down(code)?;
+        self.injector.key_up(code)?;
+      }
+      "DOWN" => {
+        let code = keycode()?;
+        self.injector.key_down(code)?;
+        self.held.push(code);
+      }
+      "UP" => {
+        let code = keycode()?;
+        self.injector.key_up(code)?;
+        self.held.retain(|c| *c != code);
+      }
+      "WAIT" => wait(jittered(arg.parse()?, step.jitter), self.ctx)?,
+      "TEXT" | "PASTE_TEXT" => self.injector.type_text(arg)?,
+      "SARCASIFY_TEXT" => self.injector.type_text(&sarcasify(arg))?,
+      "OPEN_FILE" | "OPEN_FOLDER" | "OPEN_WEBSITE" => self.injector.open(arg)?,
+      "REPEAT" => match repeat_count(step)? {
+        Some(count) => {
+          for _ in 0..count {
+            if stop() {
+              break;
+            }
+            self.steps(&step.steps, stop)?;
+          }
+        }
+        None => {
+          while !stop() {
+            self.steps(&step.steps, stop)?;
+          }
+        }
+      },
+      // Both play their body through once, so a quick tap still does
+      // something, then repeat until the key lets go or toggles off.
+      "WHILE_HELD" | "TOGGLE" => {
+        let key = self.key;
+        let released = || match step.op.as_str() {
+          "WHILE_HELD" => !key.is_held(),
+          _ => !key.is_toggled_on(),
+        };
+        let stop_loop = || stop() || released();
+        self.steps(&step.steps, stop)?;
+        while !stop_loop() {
+          self.steps(&step.steps, &stop_loop)?;
+        }
+      }
+      other => bail!("Unsupported op {}", other),
+    }
+    Ok(())
+  }
+}
+
+/// `ms` moved by a random amount of up to `jitter` ms either way, never below zero.
+fn jittered(ms: u64, jitter: Option<u32>) -> Duration {
+  let Some(jitter) = jitter.filter(|j| *j > 0) else {
+    return Duration::from_millis(ms);
+  };
+  let span = 2 * jitter as u64 + 1;
+  let offset = (Uuid::new_v4().as_u128() as u64 % span) as i64 - jitter as i64;
+  Duration::from_millis(ms.saturating_add_signed(offset))
+}
+
+/// Sleeps in short slices so a cancel takes effect promptly.
+fn wait(duration: Duration, ctx: &OperationContext) -> anyhow::Result<()> {
+  const SLICE: Duration = Duration::from_millis(10);
+  let mut left = duration;
+  while !left.is_zero() {
+    ctx.check_cancelled()?;
+    let step = left.min(SLICE);
+    std::thread::sleep(step);
+    left -= step;
+  }
+  Ok(())
+}
+
+fn sarcasify(text: &str) -> String {
+  text
+    .chars()
+    .enumerate()
+    .map(|(i, c)| if i % 2 == 0 { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    models::script::Step,
+    scripts::recording::{HostEvent, RecordingInjector},
+  };
+
+  fn step(id: u32, op: &str, arg: &str) -> Step {
+    Step { id, name: op.to_string(), op: op.to_string(), arg: Some(arg.to_string()), class: None, ..Default::default() }
+  }
+
+  #[test]
+  fn plays_steps_and_releases_held_keys() {
+    let script = Script {
+      id: "s1".into(),
+      profile_id: "p1".into(),
+      name: "Copy and open".into(),
+      target: ExecutionTarget::Host,
+      steps: vec![
+        step(1, "DOWN", "KC_LCTL"),
+        step(2, "TAP", "KC_C"),
+        step(3, "SARCASIFY_TEXT", "hello"),
+        step(4, "OPEN_WEBSITE", "https://example.com"),
+      ],
+      meta: None,
+    };
+    let injector = RecordingInjector::new();
+    run_host(&script, &[], &injector, &OperationContext::new()).unwrap();
+    assert_eq!(
+      injector.events(),
+      vec![
+        HostEvent::KeyDown(0xE0),
+        HostEvent::KeyDown(0x06),
+        HostEvent::KeyUp(0x06),
+        HostEvent::Text("hElLo".into()),
+        HostEvent::Open("https://example.com".into()),
+        HostEvent::KeyUp(0xE0),
+      ]
+    );
+  }
+
+  #[test]
+  fn loops_until_the_trigger_key_lets_go() {
+    let looped = |op: &str, arg: &str, steps: Vec<Step>| Step { steps, ..step(1, op, arg) };
+    let script = |steps: Vec<Step>| Script {
+      id: "s-loop".into(),
+      profile_id: "p1".into(),
+      name: "Loop".into(),
+      target: ExecutionTarget::Host,
+      steps,
+      meta: None,
+    };
+    let taps = |injector: &RecordingInjector| injector.events().iter().filter(|e| **e == HostEvent::KeyDown(0x04)).count();
+
+    let injector = RecordingInjector::new();
+    run_host(&script(vec![looped("REPEAT", "3", vec![step(2, "TAP", "KC_A")])]), &[], &injector, &OperationContext::new()).unwrap();
+    assert_eq!(taps(&injector), 3);
+
+    // Without a held key the body still plays once.
+    let held = script(vec![looped("WHILE_HELD", "", vec![step(2, "TAP", "KC_A"), Step { jitter: Some(2), ..step(3, "WAIT", "5") }])]);
+    let injector = RecordingInjector::new();
+    run_host(&held, &[], &injector, &OperationContext::new()).unwrap();
+    assert_eq!(taps(&injector), 1);
+
+    let key = TriggerKey::new();
+    key.press();
+    let releaser = {
+      let key = key.clone();
+      std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(60));
+        key.release();
+      })
+    };
+    let injector = RecordingInjector::new();
+    run_host_triggered(&held, &[], &injector, &OperationContext::new(), &key).unwrap();
+    releaser.join().unwrap();
+    assert!(taps(&injector) > 1);
+
+    // A second press toggles the loop off.
+    let toggle = script(vec![looped("TOGGLE", "", vec![step(2, "TAP", "KC_A"), step(3, "WAIT", "5")])]);
+    let key = TriggerKey::new();
+    key.press();
+    let toggler = {
+      let key = key.clone();
+      std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(60));
+        key.press();
+      })
+    };
+    let injector = RecordingInjector::new();
+    run_host_triggered(&toggle, &[], &injector, &OperationContext::new(), &key).unwrap();
+    toggler.join().unwrap();
+    assert!(taps(&injector) > 1);
+    assert!(!key.is_toggled_on());
+  }
+}
+
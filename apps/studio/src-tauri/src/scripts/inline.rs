@@ -0,0 +1,178 @@
+//! Inline sequences run through the same paths as named scripts by wrapping
+//! them in a transient `Script`, and can be converted to and from one.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail};
+
+use crate::models::{
+  binding::{Binding, BindingEntry},
+  device::LayerState,
+  script::{ExecutionTarget, Script},
+};
+
+/// Prefix of the ids given to inline sequences, `inline:<layer>:<target>`.
+pub const INLINE_PREFIX: &str = "inline:";
+
+pub fn inline_id(layer_id: i32, target_id: &str) -> String {
+  format!("{}{}:{}", INLINE_PREFIX, layer_id, target_id)
+}
+
+pub fn is_inline_id(script_id: &str) -> bool {
+  script_id.starts_with(INLINE_PREFIX)
+}
+
+/// Every inline sequence in `layers` as a script the runner and compiler accept.
+pub fn inline_scripts(profile_id: &str, layers: &[LayerState]) -> Vec<Script> {
+  layers
+    .iter()
+    .flat_map(|layer| layer.bindings.iter().map(move |entry| (layer.id, entry)))
+    .filter_map(|(layer_id, entry)| inline_script(profile_id, entry.layer_id.unwrap_or(layer_id), entry))
+    .collect()
+}
+
+fn inline_script(profile_id: &str, layer_id: i32, entry: &BindingEntry) -> Option<Script> {
+  let Binding::InlineSequence { steps, target, .. } = &entry.binding else {
+    return None;
+  };
+  Some(Script {
+    id: inline_id(layer_id, &entry.target_id),
+    profile_id: profile_id.to_string(),
+    name: format!("{} (inline)", entry.target_id),
+    target: target.unwrap_or_default(),
+    steps: steps.clone(),
+    meta: None,
+  })
+}
+
+/// How many bindings reference each script. Scripts nothing references are absent.
+pub fn script_ref_counts(layers: &[LayerState]) -> BTreeMap<String, usize> {
+  let mut counts = BTreeMap::new();
+  for entry in layers.iter().flat_map(|l| &l.bindings) {
+    if let Binding::ScriptRef { script_id, .. } = &entry.binding {
+      *counts.entry(script_id.clone()).or_insert(0) += 1;
+    }
+  }
+  counts
+}
+
+fn binding_mut<'a>(layers: &'a mut [LayerState], layer_id: i32, target_id: &str) -> anyhow::Result<&'a mut BindingEntry> {
+  layers
+    .iter_mut()
+    .find(|l| l.id == layer_id)
+    .ok_or_else(|| anyhow!("Layer {} not found", layer_id))?
+    .bindings
+    .iter_mut()
+    .find(|b| b.target_id == target_id)
+    .ok_or_else(|| anyhow!("No binding on {} in layer {}", target_id, layer_id))
+}
+
+/// Turns the inline sequence on `target_id` into a script with `script_id`
+/// and points the binding at it. The caller must persist the returned
+/// script before the staged layers, so the steps always live somewhere.
+pub fn promote_inline(
+  layers: &mut [LayerState],
+  scripts: &[Script],
+  layer_id: i32,
+  target_id: &str,
+  script_id: String,
+  name: String,
+  profile_id: &str,
+) -> anyhow::Result<Script> {
+  if scripts.iter().any(|s| s.id == script_id) {
+    bail!("Script {} already exists", script_id);
+  }
+  let entry = binding_mut(layers, layer_id, target_id)?;
+  let Binding::InlineSequence { steps, target, meta } = &entry.binding else {
+    bail!("Binding on {} is not an inline sequence", target_id);
+  };
+  let script = Script {
+    id: script_id.clone(),
+    profile_id: profile_id.to_string(),
+    name,
+    target: target.unwrap_or_default(),
+    steps: steps.clone(),
+    meta: None,
+  };
+  entry.binding = Binding::ScriptRef {
+    script_id,
+    target: None,
+    meta: meta.clone(),
+  };
+  Ok(script)
+}
+
+/// Replaces the script reference on `target_id` with a copy of the script's
+/// steps. The script itself is kept; returns how many references remain so
+/// the caller can offer to delete it once nothing uses it.
+pub fn inline_script_ref(
+  layers: &mut [LayerState],
+  scripts: &[Script],
+  layer_id: i32,
+  target_id: &str,
+) -> anyhow::Result<usize> {
+  let entry = binding_mut(layers, layer_id, target_id)?;
+  let Binding::ScriptRef { script_id, target, meta } = &entry.binding else {
+    bail!("Binding on {} is not a script reference", target_id);
+  };
+  let script = scripts
+    .iter()
+    .find(|s| s.id == *script_id)
+    .ok_or_else(|| anyhow!("Unknown script {}", script_id))?;
+  let script_id = script_id.clone();
+  // Keep the binding running where it ran before.
+  let target = target.or(Some(script.target)).filter(|t| *t != ExecutionTarget::Device);
+  entry.binding = Binding::InlineSequence {
+    steps: script.steps.clone(),
+    target,
+    meta: meta.clone(),
+  };
+  Ok(script_ref_counts(layers).get(&script_id).copied().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::script::Step;
+
+  fn steps() -> Vec<Step> {
+    vec![Step { id: 1, name: "Tap".into(), op: "TAP".into(), arg: Some("KC_A".into()), class: None, ..Default::default() }]
+  }
+
+  #[test]
+  fn promote_then_inline_round_trips_and_counts_refs() {
+    let layers = &mut [LayerState {
+      id: 0,
+      bindings: vec![
+        BindingEntry {
+          target_id: "K1".into(),
+          layer_id: Some(0),
+          binding: Binding::InlineSequence { steps: steps(), target: Some(ExecutionTarget::Host), meta: None },
+        },
+        BindingEntry {
+          target_id: "K2".into(),
+          layer_id: Some(0),
+          binding: Binding::ScriptRef { script_id: "s1".into(), target: None, meta: None },
+        },
+      ],
+    }];
+    let inline = inline_scripts("p1", layers);
+    assert_eq!(inline.len(), 1);
+    assert_eq!(inline[0].id, "inline:0:K1");
+    assert_eq!(inline[0].target, ExecutionTarget::Host);
+
+    let mut scripts = Vec::new();
+    let script = promote_inline(layers, &scripts, 0, "K1", "s1".into(), "Tap A".into(), "p1").unwrap();
+    assert_eq!(script.target, ExecutionTarget::Host);
+    assert!(inline_scripts("p1", layers).is_empty());
+    assert_eq!(script_ref_counts(layers).get("s1"), Some(&2));
+    assert!(promote_inline(layers, std::slice::from_ref(&script), 0, "K2", "s1".into(), "Dup".into(), "p1").is_err());
+    scripts.push(script);
+
+    assert_eq!(inline_script_ref(layers, &scripts, 0, "K1").unwrap(), 1);
+    assert_eq!(inline_script_ref(layers, &scripts, 0, "K2").unwrap(), 0);
+    let inline = inline_scripts("p1", layers);
+    assert_eq!(inline.len(), 2);
+    assert!(inline.iter().all(|s| s.target == ExecutionTarget::Host && s.steps.len() == 1));
+  }
+}
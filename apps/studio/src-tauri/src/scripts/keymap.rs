@@ -0,0 +1,93 @@
+//! QMK basic keycodes are HID keyboard usages; this maps them to Linux
+//! evdev key codes and text to the keys that type it on a US layout.
+
+/// evdev codes for HID usages 0x00..=0x73, as in the kernel's `hid_keyboard`
+/// table. Zero means unmapped.
+#[rustfmt::skip]
+const HID_TO_EVDEV: [u16; 0x74] = [
+    0,   0,   0,   0,  30,  48,  46,  32,  18,  33,  34,  35,  23,  36,  37,  38,
+   50,  49,  24,  25,  16,  19,  31,  20,  22,  47,  17,  45,  21,  44,   2,   3,
+    4,   5,   6,   7,   8,   9,  10,  11,  28,   1,  14,  15,  57,  12,  13,  26,
+   27,  43,  43,  39,  40,  41,  51,  52,  53,  58,  59,  60,  61,  62,  63,  64,
+   65,  66,  67,  68,  87,  88,  99,  70, 119, 110, 102, 104, 111, 107, 109, 106,
+  105, 108, 103,  69,  98,  55,  74,  78,  96,  79,  80,  81,  75,  76,  77,  71,
+   72,  73,  82,  83,  86, 127, 116, 117, 183, 184, 185, 186, 187, 188, 189, 190,
+  191, 192, 193, 194,
+];
+
+/// evdev codes for the modifier usages 0xE0..=0xE7 (LCTL..RGUI).
+const MODIFIERS_TO_EVDEV: [u16; 8] = [29, 42, 56, 125, 97, 54, 100, 126];
+
+pub const KC_LSFT: u16 = 0xE1;
+
+/// The evdev key code for a QMK basic keycode.
+pub fn hid_to_evdev(keycode: u16) -> Option<u16> {
+  let code = match keycode {
+    0xE0..=0xE7 => MODIFIERS_TO_EVDEV[(keycode - 0xE0) as usize],
+    _ => *HID_TO_EVDEV.get(keycode as usize)?,
+  };
+  (code != 0).then_some(code)
+}
+
+/// Every basic keycode that has an evdev equivalent.
+pub fn mapped_keycodes() -> impl Iterator<Item = u16> {
+  (0..0xE8).filter(|code| hid_to_evdev(*code).is_some())
+}
+
+/// The keycode that types `c` on a US layout, and whether shift is needed.
+pub fn char_to_keycode(c: char) -> Option<(u16, bool)> {
+  const DIGITS: &str = "1234567890";
+  const SHIFTED_DIGITS: &str = "!@#$%^&*()";
+  const PUNCTUATION: &[(char, char, u16)] = &[
+    ('-', '_', 0x2D),
+    ('=', '+', 0x2E),
+    ('[', '{', 0x2F),
+    (']', '}', 0x30),
+    ('\\', '|', 0x31),
+    (';', ':', 0x33),
+    ('\'', '"', 0x34),
+    ('`', '~', 0x35),
+    (',', '<', 0x36),
+    ('.', '>', 0x37),
+    ('/', '?', 0x38),
+  ];
+  match c {
+    'a'..='z' => Some((0x04 + (c as u16 - 'a' as u16), false)),
+    'A'..='Z' => Some((0x04 + (c as u16 - 'A' as u16), true)),
+    '\n' => Some((0x28, false)),
+    '\t' => Some((0x2B, false)),
+    ' ' => Some((0x2C, false)),
+    _ => {
+      if let Some(i) = DIGITS.find(c) {
+        return Some((0x1E + i as u16, false));
+      }
+      if let Some(i) = SHIFTED_DIGITS.find(c) {
+        return Some((0x1E + i as u16, true));
+      }
+      PUNCTUATION.iter().find_map(|(plain, shifted, code)| {
+        if c == *plain {
+          Some((*code, false))
+        } else if c == *shifted {
+          Some((*code, true))
+        } else {
+          None
+        }
+      })
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn maps_hid_usages_and_characters() {
+    // KC_A, KC_ENTER and KC_LCTL land on KEY_A, KEY_ENTER and KEY_LEFTCTRL.
+    assert_eq!(hid_to_evdev(0x04), Some(30));
+    assert_eq!(hid_to_evdev(0x28), Some(28));
+    assert_eq!(hid_to_evdev(0xE0), Some(29));
+    assert_eq!(hid_to_evdev(0x5220), None);
+    assert_eq!(char_to_keycode('?'), Some((0x38, true)));
+  }
+}
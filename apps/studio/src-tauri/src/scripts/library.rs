@@ -0,0 +1,181 @@
+//! Create/update/delete on a device's script list, keeping bindings that
+//! reference scripts consistent.
+
+use std::fmt;
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{
+  binding::Binding,
+  device::LayerState,
+  script::Script,
+};
+
+use super::inline::{inline_script_ref, is_inline_id};
+
+/// A key binding, by layer and target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BindingLocation {
+  #[serde(rename = "layerId")]
+  pub layer_id: i32,
+  #[serde(rename = "targetId")]
+  pub target_id: String,
+}
+
+/// What deleting a script does to bindings that still reference it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DanglingRefs {
+  /// Fail with `ScriptInUse`.
+  #[default]
+  #[serde(rename = "refuse")]
+  Refuse,
+  /// Unbind the keys.
+  #[serde(rename = "clear")]
+  Clear,
+  /// Give each key an inline copy of the steps.
+  #[serde(rename = "inline")]
+  Inline,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptInUse {
+  pub script_id: String,
+  pub used_by: Vec<BindingLocation>,
+}
+
+impl fmt::Display for ScriptInUse {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Script {} is still bound to {} key(s)", self.script_id, self.used_by.len())
+  }
+}
+
+impl std::error::Error for ScriptInUse {}
+
+/// Bindings in `layers` that reference `script_id`.
+pub fn references(layers: &[LayerState], script_id: &str) -> Vec<BindingLocation> {
+  layers
+    .iter()
+    .flat_map(|layer| layer.bindings.iter().map(move |entry| (layer.id, entry)))
+    .filter(|(_, entry)| matches!(&entry.binding, Binding::ScriptRef { script_id: id, .. } if id == script_id))
+    .map(|(layer_id, entry)| BindingLocation {
+      layer_id: entry.layer_id.unwrap_or(layer_id),
+      target_id: entry.target_id.clone(),
+    })
+    .collect()
+}
+
+/// Adds `script`, giving it a fresh id when it has none.
+pub fn create(scripts: &mut Vec<Script>, mut script: Script) -> anyhow::Result<Script> {
+  if script.id.trim().is_empty() {
+    script.id = Uuid::new_v4().to_string();
+  }
+  if is_inline_id(&script.id) {
+    bail!("Script id {} is reserved for inline sequences", script.id);
+  }
+  if scripts.iter().any(|s| s.id == script.id) {
+    bail!("Script {} already exists", script.id);
+  }
+  scripts.push(script.clone());
+  Ok(script)
+}
+
+pub fn update(scripts: &mut [Script], script: Script) -> anyhow::Result<()> {
+  let existing = scripts
+    .iter_mut()
+    .find(|s| s.id == script.id)
+    .ok_or_else(|| anyhow!("Unknown script {}", script.id))?;
+  *existing = script;
+  Ok(())
+}
+
+/// Removes `script_id`, handling bindings that reference it per `dangling`.
+/// Returns the bindings that were repaired.
+pub fn delete(
+  scripts: &mut Vec<Script>,
+  layers: &mut [LayerState],
+  script_id: &str,
+  dangling: DanglingRefs,
+) -> anyhow::Result<Vec<BindingLocation>> {
+  if !scripts.iter().any(|s| s.id == script_id) {
+    bail!("Unknown script {}", script_id);
+  }
+  let used_by = references(layers, script_id);
+  match dangling {
+    DanglingRefs::Refuse if !used_by.is_empty() => {
+      return Err(ScriptInUse { script_id: script_id.to_string(), used_by }.into());
+    }
+    DanglingRefs::Refuse => {}
+    DanglingRefs::Clear => {
+      for entry in layers.iter_mut().flat_map(|l| l.bindings.iter_mut()) {
+        if matches!(&entry.binding, Binding::ScriptRef { script_id: id, .. } if id == script_id) {
+          entry.binding = Binding::None;
+        }
+      }
+    }
+    DanglingRefs::Inline => {
+      for location in &used_by {
+        inline_script_ref(layers, scripts, location.layer_id, &location.target_id)?;
+      }
+    }
+  }
+  scripts.retain(|s| s.id != script_id);
+  Ok(used_by)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::{binding::BindingEntry, script::{ExecutionTarget, Step}};
+  use crate::scripts::validate::validate_scripts;
+
+  fn layers() -> Vec<LayerState> {
+    let script_ref = |target_id: &str| BindingEntry {
+      target_id: target_id.into(),
+      layer_id: Some(0),
+      binding: Binding::ScriptRef { script_id: "s1".into(), target: None, meta: None },
+    };
+    vec![LayerState { id: 0, bindings: vec![script_ref("K1"), script_ref("K2")] }]
+  }
+
+  #[test]
+  fn delete_refuses_clears_or_inlines_references() {
+    let mut scripts = Vec::new();
+    let step = Step { id: 1, name: "Tap".into(), op: "TAP".into(), arg: Some("KC_A".into()), class: None, ..Default::default() };
+    let draft = Script {
+      id: "s1".into(),
+      profile_id: "p1".into(),
+      name: "Tap A".into(),
+      target: ExecutionTarget::Device,
+      steps: vec![step],
+      meta: None,
+    };
+    create(&mut scripts, draft.clone()).unwrap();
+    assert!(create(&mut scripts, draft.clone()).is_err());
+    let fresh = create(&mut scripts, Script { id: String::new(), ..draft.clone() }).unwrap();
+    assert!(!fresh.id.is_empty());
+    update(&mut scripts, Script { name: "Renamed".into(), ..draft.clone() }).unwrap();
+    assert_eq!(scripts[0].name, "Renamed");
+
+    let mut refused = layers();
+    let err = delete(&mut scripts.clone(), &mut refused, "s1", DanglingRefs::Refuse).unwrap_err();
+    assert_eq!(err.downcast::<ScriptInUse>().unwrap().used_by.len(), 2);
+
+    let mut cleared = layers();
+    let mut remaining = scripts.clone();
+    assert_eq!(delete(&mut remaining, &mut cleared, "s1", DanglingRefs::Clear).unwrap().len(), 2);
+    assert!(cleared[0].bindings.iter().all(|b| matches!(b.binding, Binding::None)));
+    assert_eq!(remaining.len(), 1);
+
+    let mut inlined = layers();
+    let mut remaining = scripts.clone();
+    delete(&mut remaining, &mut inlined, "s1", DanglingRefs::Inline).unwrap();
+    assert!(inlined[0].bindings.iter().all(|b| matches!(&b.binding, Binding::InlineSequence { steps, .. } if steps.len() == 1)));
+
+    // Bindings left pointing at a deleted script are reported.
+    let warnings = validate_scripts(&remaining, &layers());
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings.iter().all(|w| w.script_id == "s1" && w.step_id.is_none()));
+  }
+}
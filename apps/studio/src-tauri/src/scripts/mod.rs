@@ -0,0 +1,11 @@
+pub mod delays;
+pub mod device;
+pub mod dsl;
+pub mod host;
+pub mod inline;
+pub mod library;
+pub mod keymap;
+pub mod recording;
+#[cfg(target_os = "linux")]
+pub mod uinput;
+pub mod validate;
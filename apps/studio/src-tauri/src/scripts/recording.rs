@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+
+use super::host::HostInjector;
+
+/// One call made on a `RecordingInjector`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostEvent {
+  KeyDown(u16),
+  KeyUp(u16),
+  Text(String),
+  Open(String),
+}
+
+/// Injector that records what it was asked to do, for tests and dry runs.
+#[derive(Default)]
+pub struct RecordingInjector {
+  events: Mutex<Vec<HostEvent>>,
+}
+
+impl RecordingInjector {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn events(&self) -> Vec<HostEvent> {
+    self.events.lock().unwrap().clone()
+  }
+
+  fn push(&self, event: HostEvent) -> anyhow::Result<()> {
+    self.events.lock().unwrap().push(event);
+    Ok(())
+  }
+}
+
+impl HostInjector for RecordingInjector {
+  fn key_down(&self, keycode: u16) -> anyhow::Result<()> {
+    self.push(HostEvent::KeyDown(keycode))
+  }
+
+  fn key_up(&self, keycode: u16) -> anyhow::Result<()> {
+    self.push(HostEvent::KeyUp(keycode))
+  }
+
+  fn type_text(&self, text: &str) -> anyhow::Result<()> {
+    self.push(HostEvent::Text(text.to_string()))
+  }
+
+  fn open(&self, target: &str) -> anyhow::Result<()> {
+    self.push(HostEvent::Open(target.to_string()))
+  }
+}
@@ -0,0 +1,151 @@
+//! Host injector backed by a virtual keyboard created through `/dev/uinput`.
+
+use std::{
+  fs::{File, OpenOptions},
+  io::Write,
+  os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+  time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use super::{
+  host::HostInjector,
+  keymap::{char_to_keycode, hid_to_evdev, mapped_keycodes, KC_LSFT},
+};
+
+const UINPUT_PATH: &str = "/dev/uinput";
+const DEVICE_NAME: &[u8] = b"Studio host input";
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0;
+const BUS_VIRTUAL: u16 = 0x06;
+
+// ioctl numbers from linux/uinput.h.
+const UI_DEV_CREATE: u64 = 0x5501;
+const UI_DEV_DESTROY: u64 = 0x5502;
+const UI_DEV_SETUP: u64 = 0x405c_5503;
+const UI_SET_EVBIT: u64 = 0x4004_5564;
+const UI_SET_KEYBIT: u64 = 0x4004_5565;
+
+#[repr(C)]
+struct InputId {
+  bustype: u16,
+  vendor: u16,
+  product: u16,
+  version: u16,
+}
+
+#[repr(C)]
+struct UinputSetup {
+  id: InputId,
+  name: [u8; 80],
+  ff_effects_max: u32,
+}
+
+/// Virtual keyboard that types into whatever window has focus. Needs write
+/// access to `/dev/uinput`.
+pub struct UinputInjector {
+  device: File,
+}
+
+impl UinputInjector {
+  pub fn new() -> anyhow::Result<Self> {
+    let device = OpenOptions::new()
+      .write(true)
+      .custom_flags(libc::O_NONBLOCK)
+      .open(UINPUT_PATH)
+      .with_context(|| format!("Failed to open {}", UINPUT_PATH))?;
+
+    let mut setup = UinputSetup {
+      id: InputId { bustype: BUS_VIRTUAL, vendor: 0, product: 0, version: 1 },
+      name: [0; 80],
+      ff_effects_max: 0,
+    };
+    setup.name[..DEVICE_NAME.len()].copy_from_slice(DEVICE_NAME);
+
+    ioctl(&device, UI_SET_EVBIT, EV_KEY as libc::c_ulong)?;
+    for keycode in mapped_keycodes() {
+      let code = hid_to_evdev(keycode).expect("mapped keycodes have evdev codes");
+      ioctl(&device, UI_SET_KEYBIT, code as libc::c_ulong)?;
+    }
+    ioctl(&device, UI_DEV_SETUP, &setup as *const UinputSetup as libc::c_ulong)?;
+    ioctl(&device, UI_DEV_CREATE, 0)?;
+    // Give the compositor a moment to pick up the new device before the first event.
+    std::thread::sleep(Duration::from_millis(100));
+    Ok(Self { device })
+  }
+
+  fn key(&self, keycode: u16, pressed: bool) -> anyhow::Result<()> {
+    let code = hid_to_evdev(keycode).ok_or_else(|| anyhow!("Keycode 0x{:04X} has no host key", keycode))?;
+    self.emit(EV_KEY, code, pressed as i32)?;
+    self.emit(EV_SYN, SYN_REPORT, 0)
+  }
+
+  fn emit(&self, kind: u16, code: u16, value: i32) -> anyhow::Result<()> {
+    let event = libc::input_event {
+      time: libc::timeval { tv_sec: 0, tv_usec: 0 },
+      type_: kind,
+      code,
+      value,
+    };
+    // SAFETY: input_event is plain old data; the kernel reads exactly its size.
+    let bytes = unsafe {
+      std::slice::from_raw_parts(&event as *const libc::input_event as *const u8, std::mem::size_of::<libc::input_event>())
+    };
+    (&self.device).write_all(bytes).context("Failed to write uinput event")
+  }
+}
+
+impl HostInjector for UinputInjector {
+  fn key_down(&self, keycode: u16) -> anyhow::Result<()> {
+    self.key(keycode, true)
+  }
+
+  fn key_up(&self, keycode: u16) -> anyhow::Result<()> {
+    self.key(keycode, false)
+  }
+
+  fn type_text(&self, text: &str) -> anyhow::Result<()> {
+    for c in text.chars() {
+      let Some((keycode, shift)) = char_to_keycode(c) else {
+        log::warn!("Skipping {:?}: no key types it", c);
+        continue;
+      };
+      if shift {
+        self.key_down(KC_LSFT)?;
+      }
+      self.key_down(keycode)?;
+      self.key_up(keycode)?;
+      if shift {
+        self.key_up(KC_LSFT)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Never launches anything itself: `PolicyInjector` sends opens through the
+  /// launcher's allowlist.
+  fn open(&self, target: &str) -> anyhow::Result<()> {
+    bail!("Can't open {}: open goes through the launcher", target)
+  }
+}
+
+impl Drop for UinputInjector {
+  fn drop(&mut self) {
+    if let Err(e) = ioctl(&self.device, UI_DEV_DESTROY, 0) {
+      log::warn!("Failed to destroy uinput device: {e:#}");
+    }
+  }
+}
+
+fn ioctl(device: &File, request: u64, arg: libc::c_ulong) -> anyhow::Result<()> {
+  // SAFETY: the fd is open for the lifetime of `device` and every request
+  // passes either an integer or a pointer to a live, correctly sized struct.
+  let rc = unsafe { libc::ioctl(device.as_raw_fd(), request as _, arg) };
+  if rc < 0 {
+    return Err(std::io::Error::last_os_error()).with_context(|| format!("uinput ioctl 0x{:X} failed", request));
+  }
+  Ok(())
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeSet;
+
+use super::inline::inline_scripts;
+use crate::{
+  models::{
+    binding::Binding,
+    device::LayerState,
+    script::{ExecutionTarget, Script, ScriptWarning, Step},
+  },
+  via::keycodes::KeycodeTable,
+};
+
+/// Ops the firmware macro buffer can express. `REPEAT` is unrolled.
+pub const DEVICE_OPS: &[&str] = &["TAP", "DOWN", "UP", "WAIT", "TEXT", "REPEAT"];
+/// Ops only the studio can perform on the host.
+pub const HOST_ONLY_OPS: &[&str] = &[
+  "OPEN_FILE",
+  "OPEN_FOLDER",
+  "OPEN_WEBSITE",
+  "PASTE_TEXT",
+  "SARCASIFY_TEXT",
+  "WHILE_HELD",
+  "TOGGLE",
+];
+/// Ops that run their nested `steps`.
+pub const LOOP_OPS: &[&str] = &["REPEAT", "WHILE_HELD", "TOGGLE"];
+/// Largest count a `REPEAT` may have; anything longer should loop forever
+/// under a stop condition instead.
+pub const MAX_REPEAT: u32 = 10_000;
+
+/// Iterations of a `REPEAT` step: `Some(n)` for a count, `None` for `forever`.
+pub fn repeat_count(step: &Step) -> anyhow::Result<Option<u32>> {
+  let arg = step.arg.as_deref().unwrap_or("");
+  if arg.eq_ignore_ascii_case("forever") {
+    return Ok(None);
+  }
+  match arg.parse::<u32>() {
+    Ok(n) if (1..=MAX_REPEAT).contains(&n) => Ok(Some(n)),
+    _ => anyhow::bail!("Repeat count must be 1 to {} or forever, got {:?}", MAX_REPEAT, arg),
+  }
+}
+
+/// Target a binding runs its script on: the binding's override, else the script's own.
+pub fn effective_target(binding_target: Option<ExecutionTarget>, script: &Script) -> ExecutionTarget {
+  binding_target.unwrap_or(script.target)
+}
+
+/// Every target `script` is used with: its own plus any binding overrides.
+pub fn targets_in_use(script: &Script, layers: &[LayerState]) -> BTreeSet<ExecutionTarget> {
+  let mut targets = BTreeSet::from([script.target]);
+  for entry in layers.iter().flat_map(|l| &l.bindings) {
+    if let Binding::ScriptRef { script_id, target, .. } = &entry.binding {
+      if *script_id == script.id {
+        targets.insert(effective_target(*target, script));
+      }
+    }
+  }
+  targets
+}
+
+/// Warnings for every script under each target it is used with, and for
+/// every inline sequence under its own target.
+pub fn validate_scripts(scripts: &[Script], layers: &[LayerState]) -> Vec<ScriptWarning> {
+  let mut warnings: Vec<ScriptWarning> = scripts
+    .iter()
+    .flat_map(|script| {
+      targets_in_use(script, layers)
+        .into_iter()
+        .flat_map(move |target| validate_script(script, target))
+    })
+    .collect();
+  for script in inline_scripts("", layers) {
+    warnings.extend(validate_script(&script, script.target));
+  }
+  warnings.extend(dangling_refs(scripts, layers));
+  warnings
+}
+
+/// One warning per binding that references a script that does not exist.
+fn dangling_refs(scripts: &[Script], layers: &[LayerState]) -> Vec<ScriptWarning> {
+  layers
+    .iter()
+    .flat_map(|layer| layer.bindings.iter().map(move |entry| (layer.id, entry)))
+    .filter_map(|(layer_id, entry)| {
+      let Binding::ScriptRef { script_id, target, .. } = &entry.binding else {
+        return None;
+      };
+      if scripts.iter().any(|s| s.id == *script_id) {
+        return None;
+      }
+      Some(ScriptWarning {
+        script_id: script_id.clone(),
+        step_id: None,
+        target: target.unwrap_or_default(),
+        message: format!(
+          "{} in layer {} is bound to a script that does not exist",
+          entry.target_id,
+          entry.layer_id.unwrap_or(layer_id)
+        ),
+      })
+    })
+    .collect()
+}
+
+/// Steps of `script` that `target` cannot perform, including nested ones.
+pub fn validate_script(script: &Script, target: ExecutionTarget) -> Vec<ScriptWarning> {
+  let mut warnings = Vec::new();
+  check_steps(&script.steps, target, false, &mut |step, message| {
+    warnings.push(ScriptWarning {
+      script_id: script.id.clone(),
+      step_id: Some(step.id),
+      target,
+      message,
+    })
+  });
+  warnings
+}
+
+/// `stoppable` is set inside `WHILE_HELD` and `TOGGLE`, whose key ends the loop.
+fn check_steps(steps: &[Step], target: ExecutionTarget, stoppable: bool, warn: &mut dyn FnMut(&Step, String)) {
+  for step in steps {
+    if let Some(message) = check_step(step, target, stoppable) {
+      warn(step, message);
+    }
+    if LOOP_OPS.contains(&step.op.as_str()) {
+      check_steps(&step.steps, target, stoppable || step.op != "REPEAT", warn);
+    }
+  }
+}
+
+fn check_step(step: &Step, target: ExecutionTarget, stoppable: bool) -> Option<String> {
+  let op = step.op.as_str();
+  let arg = step.arg.as_deref().unwrap_or("");
+  let known = DEVICE_OPS.contains(&op) || HOST_ONLY_OPS.contains(&op);
+  if !known {
+    return Some(format!("Unknown op {}", op));
+  }
+  if target == ExecutionTarget::Device && HOST_ONLY_OPS.contains(&op) {
+    return Some(format!("{} can only run on the host", op));
+  }
+  if step.jitter.is_some() && op != "WAIT" {
+    return Some(format!("{} cannot have jitter", op));
+  }
+  if !step.steps.is_empty() && !LOOP_OPS.contains(&op) {
+    return Some(format!("{} cannot contain steps", op));
+  }
+  if LOOP_OPS.contains(&op) && step.steps.is_empty() {
+    return Some(format!("{} needs at least one step", op));
+  }
+  match op {
+    "TAP" | "DOWN" | "UP" => match KeycodeTable::get().code(arg) {
+      None => Some(format!("Unknown keycode {:?}", arg)),
+      // Anything past the HID usage range is handled inside the firmware.
+      Some(code) if target == ExecutionTarget::Host && code > 0xFF => {
+        Some(format!("{} is a firmware keycode and cannot be sent from the host", arg))
+      }
+      Some(_) => None,
+    },
+    "WAIT" if arg.parse::<u32>().is_err() => Some(format!("Invalid delay {:?}", arg)),
+    "WAIT" if step.jitter.is_some() && target == ExecutionTarget::Device => {
+      Some("Wait jitter needs the host; the firmware only has fixed delays".to_string())
+    }
+    "TEXT" if target == ExecutionTarget::Device && !arg.is_ascii() => {
+      Some("Device text can only contain ASCII characters".to_string())
+    }
+    "REPEAT" => match repeat_count(step) {
+      Err(e) => Some(e.to_string()),
+      Ok(None) if target == ExecutionTarget::Device => Some("The firmware can only repeat a fixed number of times".to_string()),
+      Ok(None) if !stoppable => Some("REPEAT forever never stops; put it inside WHILE_HELD or TOGGLE".to_string()),
+      Ok(None) if !has_wait(&step.steps) => Some("REPEAT forever needs a WAIT so it does not flood the host".to_string()),
+      Ok(_) => None,
+    },
+    "WHILE_HELD" | "TOGGLE" if !has_wait(&step.steps) => Some(format!("{} needs a WAIT so it does not flood the host", op)),
+    _ => None,
+  }
+}
+
+/// Whether `steps` wait a non-zero time somewhere on every pass.
+fn has_wait(steps: &[Step]) -> bool {
+  steps.iter().any(|step| match step.op.as_str() {
+    "WAIT" => step.arg.as_deref().and_then(|a| a.parse::<u32>().ok()).is_some_and(|ms| ms > 0),
+    "REPEAT" => has_wait(&step.steps),
+    _ => false,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::binding::BindingEntry;
+
+  fn step(id: u32, op: &str, arg: &str) -> Step {
+    Step {
+      id,
+      name: format!("{} {}", op, arg),
+      op: op.to_string(),
+      arg: Some(arg.to_string()),
+      class: None,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn warns_per_target_including_binding_overrides() {
+    let script = Script {
+      id: "s-mixed".to_string(),
+      profile_id: "p-default".to_string(),
+      name: "Mixed".to_string(),
+      target: ExecutionTarget::Device,
+      steps: vec![step(1, "TAP", "KC_A"), step(2, "OPEN_WEBSITE", "https://qmk.fm"), step(3, "TAP", "0x5221")],
+      meta: None,
+    };
+
+    let device = validate_script(&script, ExecutionTarget::Device);
+    assert_eq!(device.iter().map(|w| w.step_id).collect::<Vec<_>>(), [Some(2)]);
+    let host = validate_script(&script, ExecutionTarget::Host);
+    assert_eq!(host.iter().map(|w| w.step_id).collect::<Vec<_>>(), [Some(3)]);
+
+    let layers = vec![LayerState {
+      id: 1,
+      bindings: vec![BindingEntry {
+        target_id: "key:0,0".to_string(),
+        layer_id: Some(1),
+        binding: Binding::ScriptRef {
+          script_id: "s-mixed".to_string(),
+          target: Some(ExecutionTarget::Host),
+          meta: None,
+        },
+      }],
+    }];
+    let all = validate_scripts(std::slice::from_ref(&script), &layers);
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[0].target, ExecutionTarget::Device);
+    assert_eq!(all[1].target, ExecutionTarget::Host);
+  }
+
+  #[test]
+  fn loops_need_a_bound_or_a_stop_condition() {
+    let looped = |op: &str, arg: &str, steps: Vec<Step>| Step { steps, ..step(1, op, arg) };
+    let script = |steps: Vec<Step>| Script {
+      id: "s-loop".to_string(),
+      profile_id: "p-default".to_string(),
+      name: "Loop".to_string(),
+      target: ExecutionTarget::Host,
+      steps,
+      meta: None,
+    };
+    let messages = |steps: Vec<Step>, target| {
+      validate_script(&script(steps), target).into_iter().map(|w| w.message).collect::<Vec<_>>()
+    };
+    let tap = || step(2, "TAP", "KC_A");
+    let wait = || Step { jitter: Some(5), ..step(3, "WAIT", "20") };
+
+    assert!(messages(vec![looped("REPEAT", "3", vec![tap()])], ExecutionTarget::Device).is_empty());
+    assert_eq!(
+      messages(vec![looped("REPEAT", "forever", vec![tap(), wait()])], ExecutionTarget::Host),
+      ["REPEAT forever never stops; put it inside WHILE_HELD or TOGGLE"]
+    );
+    let held = looped("WHILE_HELD", "", vec![looped("REPEAT", "forever", vec![tap(), wait()])]);
+    assert!(messages(vec![held.clone()], ExecutionTarget::Host).is_empty());
+    assert_eq!(messages(vec![held], ExecutionTarget::Device).len(), 3);
+    assert_eq!(
+      messages(vec![looped("TOGGLE", "", vec![tap()])], ExecutionTarget::Host),
+      ["TOGGLE needs a WAIT so it does not flood the host"]
+    );
+    assert_eq!(
+      messages(vec![looped("REPEAT", "0", vec![tap()]), looped("REPEAT", "2", vec![])], ExecutionTarget::Host),
+      ["Repeat count must be 1 to 10000 or forever, got \"0\"", "REPEAT needs at least one step"]
+    );
+  }
+}
@@ -0,0 +1,195 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+
+use crate::models::{device::DeviceState, state::SessionState};
+
+use super::r#trait::Store;
+
+struct CachedSession {
+  /// `None` while the entry is a placeholder whose state is still loading.
+  state: Option<SessionState>,
+  dirty: bool,
+  /// Set once the entry is dropped from the map; holders must look it up again.
+  evicted: bool,
+}
+
+type Entry = Arc<Mutex<CachedSession>>;
+
+/// Keeps each device's `SessionState` in memory. Edits are written behind by
+/// `flush`; commits and evictions persist immediately through the store.
+///
+/// Every device has its own lock, so edits to one device never wait on
+/// another device's disk I/O, and each read-modify-write runs atomically.
+/// The map lock is only held to look entries up, never across store calls.
+pub struct SessionCache {
+  store: Arc<dyn Store + Send + Sync>,
+  entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl SessionCache {
+  pub fn new(store: Arc<dyn Store + Send + Sync>) -> Self {
+    Self {
+      store,
+      entries: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Cached state for `device_id`, loading it from the store on first use.
+  pub fn get(&self, device_id: &str) -> anyhow::Result<Option<SessionState>> {
+    self.with_entry(device_id, false, None, |entry| Ok(entry.and_then(|e| e.state.clone())))
+  }
+
+  /// Like `update`, but starts from `seed()` when the store has no state for
+  /// the device, and writes the result through.
+  pub fn open<R>(
+    &self,
+    device_id: &str,
+    seed: &dyn Fn() -> SessionState,
+    f: impl FnOnce(&mut SessionState) -> anyhow::Result<R>,
+  ) -> anyhow::Result<R> {
+    self.with_entry(device_id, true, Some(seed), |entry| {
+      let entry = entry.ok_or_else(|| anyhow!("No session state found"))?;
+      let mut next = entry.state.clone().ok_or_else(|| anyhow!("No session state found"))?;
+      let result = f(&mut next)?;
+      self.store.save_session_state(device_id, &next)?;
+      entry.state = Some(next);
+      entry.dirty = false;
+      Ok(result)
+    })
+  }
+
+  /// Applies `f` to a copy of the state; the cache only changes if `f` succeeds.
+  /// The change is persisted on the next `flush`.
+  pub fn update<R>(&self, device_id: &str, f: impl FnOnce(&mut SessionState) -> anyhow::Result<R>) -> anyhow::Result<R> {
+    self.with_entry(device_id, true, None, |entry| {
+      let entry = entry.ok_or_else(|| anyhow!("No session state found"))?;
+      let mut next = entry.state.clone().ok_or_else(|| anyhow!("No session state found"))?;
+      let result = f(&mut next)?;
+      entry.state = Some(next);
+      entry.dirty = true;
+      Ok(result)
+    })
+  }
+
+  /// Runs `f` with the device's entry locked, without changing the state.
+  /// For writes kept elsewhere that must not interleave with this device's edits.
+  pub fn with_state<R>(&self, device_id: &str, f: impl FnOnce(&SessionState) -> anyhow::Result<R>) -> anyhow::Result<R> {
+    self.with_entry(device_id, true, None, |entry| {
+      let state = entry.and_then(|e| e.state.as_ref()).ok_or_else(|| anyhow!("No session state found"))?;
+      f(state)
+    })
+  }
+
+  /// Like `update`, but `f` returns the newly committed state, which is saved
+  /// together with the session before the cache is updated.
+  pub fn commit(&self, device_id: &str, f: impl FnOnce(&mut SessionState) -> anyhow::Result<DeviceState>) -> anyhow::Result<()> {
+    self.with_entry(device_id, true, None, |entry| {
+      let entry = entry.ok_or_else(|| anyhow!("No session state found"))?;
+      let mut next = entry.state.clone().ok_or_else(|| anyhow!("No session state found"))?;
+      let committed = f(&mut next)?;
+      self.store.save_commit(device_id, &next, &committed)?;
+      entry.state = Some(next);
+      entry.dirty = false;
+      Ok(())
+    })
+  }
+
+  /// Persists every dirty entry. Entries that fail to save stay dirty.
+  pub fn flush(&self) -> anyhow::Result<()> {
+    let entries: Vec<(String, Entry)> = {
+      let guard = self.entries.lock().unwrap();
+      guard.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    };
+    let mut first_err = None;
+    for (device_id, entry) in entries {
+      let mut cached = entry.lock().unwrap();
+      if !cached.dirty || cached.evicted {
+        continue;
+      }
+      let Some(state) = &cached.state else {
+        continue;
+      };
+      match self.store.save_session_state(&device_id, state) {
+        Ok(()) => cached.dirty = false,
+        Err(e) => {
+          log::warn!("Failed to flush state for {}: {:#}", device_id, e);
+          first_err.get_or_insert(e);
+        }
+      }
+    }
+    first_err.map_or(Ok(()), Err)
+  }
+
+  /// Flushes and drops the cached state for `device_id`.
+  pub fn evict(&self, device_id: &str) -> anyhow::Result<()> {
+    let entry = {
+      let guard = self.entries.lock().unwrap();
+      guard.get(device_id).cloned()
+    };
+    let Some(entry) = entry else {
+      return Ok(());
+    };
+    let mut cached = entry.lock().unwrap();
+    if cached.evicted {
+      return Ok(());
+    }
+    if let (true, Some(state)) = (cached.dirty, &cached.state) {
+      self.store.save_session_state(device_id, state)?;
+      cached.dirty = false;
+    }
+    cached.evicted = true;
+    let mut guard = self.entries.lock().unwrap();
+    guard.remove(device_id);
+    Ok(())
+  }
+
+  /// Runs `f` with the device's entry locked, loading it on first use.
+  /// When the store has no state for the device, the entry starts from
+  /// `seed()` if given; otherwise `f` gets `None`.
+  ///
+  /// A first lookup inserts a placeholder and loads into it under the
+  /// device's own lock, so other devices never wait on this load.
+  fn with_entry<R>(
+    &self,
+    device_id: &str,
+    required: bool,
+    seed: Option<&dyn Fn() -> SessionState>,
+    f: impl FnOnce(Option<&mut CachedSession>) -> anyhow::Result<R>,
+  ) -> anyhow::Result<R> {
+    let entry = {
+      let mut guard = self.entries.lock().unwrap();
+      guard
+        .entry(device_id.to_string())
+        .or_insert_with(|| Arc::new(Mutex::new(CachedSession { state: None, dirty: false, evicted: false })))
+        .clone()
+    };
+    let mut cached = entry.lock().unwrap();
+    if cached.evicted {
+      // Lost a race with `evict` or a failed load; pick up a fresh entry instead.
+      drop(cached);
+      return self.with_entry(device_id, required, seed, f);
+    }
+    if cached.state.is_none() {
+      match self.store.load_session_state(device_id) {
+        Ok(Some(state)) => cached.state = Some(state),
+        // Not dirty: a seed nobody edited needs no saving.
+        Ok(None) if seed.is_some() => cached.state = seed.map(|seed| seed()),
+        loaded => {
+          // Drop the placeholder so waiters and later calls load again.
+          cached.evicted = true;
+          self.entries.lock().unwrap().remove(device_id);
+          drop(cached);
+          if loaded?.is_none() && !required {
+            return f(None);
+          }
+          return Err(anyhow!("No session state found"));
+        }
+      }
+    }
+    f(Some(&mut cached))
+  }
+}
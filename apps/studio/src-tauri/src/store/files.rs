@@ -7,6 +7,8 @@ use std::{
 use anyhow::Context;
 use serde::{de::DeserializeOwned, Serialize};
 
+use crate::models::state::StateRecovery;
+
 pub fn ensure_dir(path: &Path) -> anyhow::Result<()> {
   fs::create_dir_all(path).with_context(|| format!("Failed to create directory {}", path.display()))
 }
@@ -27,36 +29,154 @@ pub fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> anyhow::Result
     let mut f = fs::File::create(&tmp_path).with_context(|| format!("Failed to create temp file {}", tmp_path.display()))?;
     f.write_all(json.as_bytes())
       .with_context(|| format!("Failed to write temp file {}", tmp_path.display()))?;
-    f.sync_all().ok();
+    f.sync_all()
+      .with_context(|| format!("Failed to sync temp file {}", tmp_path.display()))?;
   }
   fs::rename(&tmp_path, path).with_context(|| format!("Failed to rename temp file {}", path.display()))?;
+  sync_parent_dir(path)
+}
+
+/// Atomic write that first keeps the current file as generation `.1`,
+/// shifting older generations up to `.{keep}`.
+pub fn write_json_generational<T: Serialize>(path: &Path, value: &T, keep: usize) -> anyhow::Result<()> {
+  rotate_generations(path, keep)?;
+  write_json_atomic(path, value)
+}
+
+pub struct Recovered<T> {
+  pub value: T,
+  pub recovery: Option<StateRecovery>,
+}
+
+/// Reads a file written by `write_json_generational`.
+///
+/// An intact temp file left by an interrupted write is treated as a journal
+/// entry and promoted first. If the file then fails `parse`, it is moved
+/// aside as `.corrupt-<millis>` and the newest generation that parses is restored.
+pub fn read_json_recovering<T>(
+  path: &Path,
+  keep: usize,
+  parse: impl Fn(&str) -> anyhow::Result<T>,
+) -> anyhow::Result<Option<Recovered<T>>> {
+  replay_journal(path, keep, &parse)?;
+  if !path.exists() {
+    return Ok(None);
+  }
+
+  let primary_err = match read_and_parse(path, &parse) {
+    Ok(value) => return Ok(Some(Recovered { value, recovery: None })),
+    Err(e) => e,
+  };
+
+  for generation in 1..=keep {
+    let candidate = generation_path(path, generation);
+    if !candidate.exists() {
+      continue;
+    }
+    let Ok(value) = read_and_parse(&candidate, &parse) else {
+      continue;
+    };
+    let quarantined = quarantine(path)?;
+    fs::copy(&candidate, path)
+      .with_context(|| format!("Failed to restore {} from {}", path.display(), candidate.display()))?;
+    sync_parent_dir(path)?;
+    log::warn!(
+      "Recovered {} from generation {} after: {:#}",
+      path.display(),
+      generation,
+      primary_err
+    );
+    return Ok(Some(Recovered {
+      value,
+      recovery: Some(StateRecovery {
+        file: path.display().to_string(),
+        quarantined_as: quarantined.display().to_string(),
+        restored_generation: generation as u32,
+        error: format!("{:#}", primary_err),
+      }),
+    }));
+  }
+
+  Err(primary_err.context(format!("No valid generation of {} to recover from", path.display())))
+}
+
+fn read_and_parse<T>(path: &Path, parse: &impl Fn(&str) -> anyhow::Result<T>) -> anyhow::Result<T> {
+  let data = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+  parse(&data).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn replay_journal<T>(path: &Path, keep: usize, parse: &impl Fn(&str) -> anyhow::Result<T>) -> anyhow::Result<()> {
+  let tmp_path = tmp_path_for(path);
+  if !tmp_path.exists() {
+    return Ok(());
+  }
+  if read_and_parse(&tmp_path, parse).is_ok() {
+    rotate_generations(path, keep)?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replay journal {}", tmp_path.display()))?;
+    sync_parent_dir(path)?;
+    log::info!("Replayed interrupted write of {}", path.display());
+  } else {
+    fs::remove_file(&tmp_path).with_context(|| format!("Failed to discard journal {}", tmp_path.display()))?;
+  }
   Ok(())
 }
 
-pub fn copy_seed_data_if_missing(seed_root: &Path, data_root: &Path) -> anyhow::Result<()> {
-  if data_root.exists() {
+fn rotate_generations(path: &Path, keep: usize) -> anyhow::Result<()> {
+  if keep == 0 || !path.exists() {
     return Ok(());
   }
-  copy_dir(seed_root, data_root)
-}
-
-fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
-  ensure_dir(to)?;
-  for entry in fs::read_dir(from)? {
-    let entry = entry?;
-    let file_type = entry.file_type()?;
-    let src_path = entry.path();
-    let dst_path = to.join(entry.file_name());
-    if file_type.is_dir() {
-      copy_dir(&src_path, &dst_path)?;
-    } else {
-      fs::copy(&src_path, &dst_path)
-        .with_context(|| format!("Failed to copy {} to {}", src_path.display(), dst_path.display()))?;
+  for generation in (1..keep).rev() {
+    let from = generation_path(path, generation);
+    if from.exists() {
+      let to = generation_path(path, generation + 1);
+      fs::rename(&from, &to).with_context(|| format!("Failed to rotate {} to {}", from.display(), to.display()))?;
     }
   }
+  let first = generation_path(path, 1);
+  fs::copy(path, &first).with_context(|| format!("Failed to keep {} as {}", path.display(), first.display()))?;
+  Ok(())
+}
+
+fn quarantine(path: &Path) -> anyhow::Result<PathBuf> {
+  let millis = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_millis())
+    .unwrap_or(0);
+  let target = sibling_path(path, &format!("corrupt-{}", millis));
+  fs::rename(path, &target).with_context(|| format!("Failed to quarantine {}", path.display()))?;
+  Ok(target)
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> anyhow::Result<()> {
+  if let Some(parent) = path.parent() {
+    fs::File::open(parent)
+      .and_then(|dir| dir.sync_all())
+      .with_context(|| format!("Failed to sync directory {}", parent.display()))?;
+  }
+  Ok(())
+}
+
+// Directory handles cannot be fsynced on Windows; the rename is already durable there.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> anyhow::Result<()> {
   Ok(())
 }
 
+fn generation_path(path: &Path, generation: usize) -> PathBuf {
+  sibling_path(path, &generation.to_string())
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+  let mut sibling = path.to_path_buf();
+  let file_name = path
+    .file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .unwrap_or_default();
+  sibling.set_file_name(format!("{}.{}", file_name, suffix));
+  sibling
+}
+
 fn tmp_path_for(path: &Path) -> PathBuf {
   let mut tmp = path.to_path_buf();
   let file_name = path
@@ -66,3 +186,38 @@ fn tmp_path_for(path: &Path) -> PathBuf {
   tmp.set_file_name(format!(".{}.tmp", file_name));
   tmp
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  fn parse_number(data: &str) -> anyhow::Result<u32> {
+    Ok(serde_json::from_str(data)?)
+  }
+
+  #[test]
+  fn replays_journal_and_falls_back_to_last_good_generation() {
+    let dir = TempDir::new("files-test");
+    let path = dir.join("state.json");
+    for n in 1..=4u32 {
+      write_json_generational(&path, &n, 3).expect("write");
+    }
+    assert!(generation_path(&path, 3).exists());
+    assert!(!generation_path(&path, 4).exists());
+
+    // An intact journal from an interrupted write wins over the primary.
+    fs::write(tmp_path_for(&path), "5").unwrap();
+    let read = read_json_recovering(&path, 3, parse_number).unwrap().unwrap();
+    assert_eq!(read.value, 5);
+    assert!(read.recovery.is_none());
+
+    fs::write(&path, "{ torn").unwrap();
+    let read = read_json_recovering(&path, 3, parse_number).unwrap().unwrap();
+    assert_eq!(read.value, 4);
+    let recovery = read.recovery.expect("recovery reported");
+    assert_eq!(recovery.restored_generation, 1);
+    assert!(Path::new(&recovery.quarantined_as).exists());
+    assert_eq!(read_json::<u32>(&path).unwrap(), 4);
+  }
+}
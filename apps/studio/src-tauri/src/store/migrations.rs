@@ -0,0 +1,147 @@
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+use crate::models::state::SessionState;
+
+pub type Migration = fn(Value) -> anyhow::Result<Value>;
+
+/// `MIGRATIONS[n]` upgrades a v(n+1) session state to v(n+2). Append only.
+/// Fields that can be derived on read (such as a binding's `layerId`) get a
+/// serde default instead of a schema bump, so older builds can still read
+/// the file.
+pub const MIGRATIONS: &[Migration] = &[];
+
+const _: () = assert!(MIGRATIONS.len() as u32 + 1 == SessionState::current_version());
+
+/// Files written before versioning carry no `version` field.
+pub fn version_of(value: &Value) -> u32 {
+  value
+    .get("version")
+    .and_then(Value::as_u64)
+    .map(|v| v as u32)
+    .unwrap_or(SessionState::unversioned())
+}
+
+/// Runs every migration from the value's version up to the current one.
+pub fn migrate_session_state(value: Value) -> anyhow::Result<Value> {
+  migrate_with(value, MIGRATIONS)
+}
+
+/// Like `migrate_session_state`, against a table laid out like `MIGRATIONS`.
+pub fn migrate_with(mut value: Value, migrations: &[Migration]) -> anyhow::Result<Value> {
+  let from = version_of(&value);
+  let current = migrations.len() as u32 + 1;
+  if from > current {
+    bail!(
+      "State was written by a newer version of BuuDevStudio (schema v{}, this build supports up to v{})",
+      from,
+      current
+    );
+  }
+  if from == 0 {
+    bail!("Invalid state schema version 0");
+  }
+
+  for (index, migration) in migrations.iter().enumerate().skip(from as usize - 1) {
+    let target = index as u32 + 2;
+    value = migration(value).map_err(|e| anyhow!("Migration to v{} failed: {e}", target))?;
+    set_version(&mut value, target)?;
+  }
+  Ok(value)
+}
+
+fn set_version(value: &mut Value, version: u32) -> anyhow::Result<()> {
+  let obj = value
+    .as_object_mut()
+    .ok_or_else(|| anyhow!("State is not a JSON object"))?;
+  obj.insert("version".to_string(), Value::from(version));
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+  use crate::store::{MockStore, Store};
+  use serde_json::json;
+
+  fn v1_state() -> Value {
+    json!({
+      "sessionId": "s1",
+      "staged": {
+        "profileId": "p",
+        "layers": [{ "id": 2, "bindings": [{ "targetId": "key:0,0", "binding": { "type": "none" } }] }]
+      }
+    })
+  }
+
+  #[test]
+  fn reads_unversioned_state_and_refuses_newer() {
+    let data_root = TempDir::new("migrations-test");
+    let state_dir = data_root.join("state");
+    std::fs::create_dir_all(&state_dir).unwrap();
+    std::fs::write(state_dir.join("dev.json"), v1_state().to_string()).unwrap();
+
+    // A versionless file is the same schema to the migrator and to serde,
+    // and bindings pick up their layer's id on read.
+    assert_eq!(version_of(&v1_state()), SessionState::unversioned());
+    let store = MockStore::new(data_root.join("seed"), data_root.to_path_buf());
+    let state = store.load_session_state("dev").expect("load").expect("state");
+    assert_eq!(state.version, SessionState::current_version());
+    let binding = &state.staged.unwrap().layers[0].bindings[0];
+    assert_eq!(binding.layer_id, Some(2));
+
+    let mut newer = v1_state();
+    newer["version"] = json!(SessionState::current_version() + 1);
+    std::fs::write(state_dir.join("dev.json"), newer.to_string()).unwrap();
+    let err = store.load_session_state("dev").expect_err("newer schema refused");
+    assert!(format!("{err:#}").contains("newer version"));
+  }
+
+  /// Appends `step@v<version it saw>` to the state's `trail`.
+  fn record(mut value: Value, step: &str) -> anyhow::Result<Value> {
+    let entry = format!("{}@v{}", step, version_of(&value));
+    match value.get_mut("trail").and_then(Value::as_array_mut) {
+      Some(trail) => trail.push(entry.into()),
+      None => value["trail"] = json!([entry]),
+    }
+    Ok(value)
+  }
+
+  const TWO_STEPS: &[Migration] = &[|v| record(v, "to-v2"), |v| record(v, "to-v3")];
+  const FAILS_TO_V3: &[Migration] = &[|v| record(v, "to-v2"), |_| Err(anyhow!("no trail"))];
+
+  #[test]
+  fn runs_migrations_in_order_and_names_the_failing_target() {
+    let migrated = migrate_with(v1_state(), TWO_STEPS).expect("migrate");
+    assert_eq!(migrated["trail"], json!(["to-v2@v1", "to-v3@v2"]));
+    assert_eq!(version_of(&migrated), 3);
+
+    let mut v2 = v1_state();
+    v2["version"] = json!(2);
+    assert_eq!(migrate_with(v2, TWO_STEPS).expect("migrate")["trail"], json!(["to-v3@v2"]));
+
+    let err = migrate_with(v1_state(), FAILS_TO_V3).expect_err("second step fails");
+    assert_eq!(format!("{err:#}"), "Migration to v3 failed: no trail");
+  }
+
+  #[test]
+  fn json_store_backs_up_before_overwriting() {
+    let data_root = TempDir::new("migrations-test");
+    let state_dir = data_root.join("state");
+    std::fs::create_dir_all(&state_dir).unwrap();
+    let store = MockStore::new(data_root.join("seed"), data_root.to_path_buf()).with_migrations(TWO_STEPS);
+    let read = |name: &str| serde_json::from_str::<Value>(&std::fs::read_to_string(state_dir.join(name)).unwrap()).unwrap();
+
+    std::fs::write(state_dir.join("dev.json"), v1_state().to_string()).unwrap();
+    assert_eq!(store.load_session_state("dev").expect("load").expect("state").version, 3);
+    assert_eq!(read("dev.v1.bak.json"), v1_state());
+    assert_eq!(version_of(&read("dev.json")), 3);
+
+    // A backup that can't be written leaves the original in place.
+    std::fs::write(state_dir.join("blocked.json"), v1_state().to_string()).unwrap();
+    std::fs::create_dir_all(state_dir.join("blocked.v1.bak.json")).unwrap();
+    assert!(store.load_session_state("blocked").is_err());
+    assert_eq!(read("blocked.json"), v1_state());
+  }
+}
@@ -1,4 +1,11 @@
+pub mod cache;
 pub mod files;
+pub mod migrations;
+pub mod seeds;
+pub mod sqlite;
 pub mod store;
+pub mod r#trait;
 
+pub use r#trait::{SeedSync, Store};
+pub use sqlite::SqliteStore;
 pub use store::MockStore;
@@ -0,0 +1,245 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use super::files::{ensure_dir, read_json, write_json_atomic};
+
+pub const SEED_MANIFEST: &str = "seed-manifest.json";
+
+/// Hash of every seed file as last copied into the data root. A data file
+/// whose hash still matches was never edited and can be upgraded in place.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeedManifest {
+  #[serde(default)]
+  pub files: BTreeMap<String, SeedRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedRecord {
+  pub hash: String,
+  /// Hash of a newer seed that was not applied because the user edited the file.
+  #[serde(rename = "pendingHash", default)]
+  pub pending_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeedSyncReport {
+  pub added: Vec<String>,
+  pub updated: Vec<String>,
+  pub pending: Vec<String>,
+}
+
+/// What a sync does with one seeded item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeedAction {
+  /// Not stored yet: copy the seed.
+  Add,
+  /// An unedited copy of an older seed: replace it.
+  Update,
+  /// Already matches the seed.
+  InSync,
+  /// Edited locally: keep it, recording `hash` as the seeded version and the
+  /// new seed as pending.
+  Defer { hash: String },
+}
+
+/// Decides an item's fate from the seed's hash, the stored copy's hash (if
+/// any) and its manifest record. Shared by every store that syncs seeds.
+pub fn plan_seed(seed_hash: &str, data_hash: Option<&str>, record: Option<&SeedRecord>) -> SeedAction {
+  let Some(data_hash) = data_hash else {
+    return SeedAction::Add;
+  };
+  match record {
+    Some(record) if record.hash == seed_hash => SeedAction::InSync,
+    Some(record) if record.hash == data_hash => SeedAction::Update,
+    _ if data_hash == seed_hash => SeedAction::InSync,
+    // Edited locally (or stored before the manifest existed).
+    record => SeedAction::Defer {
+      hash: record.map(|r| r.hash.clone()).unwrap_or_else(|| data_hash.to_string()),
+    },
+  }
+}
+
+pub fn sync_seed_data(seed_root: &Path, data_root: &Path) -> anyhow::Result<SeedSyncReport> {
+  ensure_dir(data_root)?;
+  let mut manifest = load_manifest(data_root)?;
+  let mut report = SeedSyncReport::default();
+
+  for rel in list_files(seed_root)? {
+    let seed_path = seed_root.join(&rel);
+    let data_path = data_root.join(&rel);
+    let seed_hash = hash_file(&seed_path)?;
+    let data_hash = if data_path.exists() { Some(hash_file(&data_path)?) } else { None };
+
+    let action = plan_seed(&seed_hash, data_hash.as_deref(), manifest.files.get(&rel));
+    let record = match &action {
+      SeedAction::Add | SeedAction::Update => {
+        copy_file(&seed_path, &data_path)?;
+        SeedRecord { hash: seed_hash, pending_hash: None }
+      }
+      SeedAction::InSync => SeedRecord { hash: seed_hash, pending_hash: None },
+      SeedAction::Defer { hash } => SeedRecord { hash: hash.clone(), pending_hash: Some(seed_hash) },
+    };
+    manifest.files.insert(rel.clone(), record);
+    match action {
+      SeedAction::Add => report.added.push(rel),
+      SeedAction::Update => report.updated.push(rel),
+      SeedAction::Defer { .. } => report.pending.push(rel),
+      SeedAction::InSync => {}
+    }
+  }
+
+  save_manifest(data_root, &manifest)?;
+  if !report.added.is_empty() || !report.updated.is_empty() {
+    log::info!(
+      "Seed sync: {} added, {} updated, {} pending",
+      report.added.len(),
+      report.updated.len(),
+      report.pending.len()
+    );
+  }
+  Ok(report)
+}
+
+pub fn pending_seed_updates(data_root: &Path) -> anyhow::Result<Vec<String>> {
+  let manifest = load_manifest(data_root)?;
+  Ok(
+    manifest
+      .files
+      .into_iter()
+      .filter(|(_, r)| r.pending_hash.is_some())
+      .map(|(rel, _)| rel)
+      .collect(),
+  )
+}
+
+/// Replaces the user's copy of `rel` with the shipped seed. The previous copy
+/// is kept next to it as `<name>.bak` so local edits can be recovered.
+pub fn accept_seed_update(seed_root: &Path, data_root: &Path, rel: &str) -> anyhow::Result<()> {
+  let mut manifest = load_manifest(data_root)?;
+  if manifest.files.get(rel).and_then(|r| r.pending_hash.as_ref()).is_none() {
+    return Err(anyhow!("No pending seed update for {}", rel));
+  }
+
+  let seed_path = seed_root.join(rel);
+  let data_path = data_root.join(rel);
+  if data_path.exists() {
+    let backup = backup_path_for(&data_path);
+    fs::copy(&data_path, &backup)
+      .with_context(|| format!("Failed to back up {} to {}", data_path.display(), backup.display()))?;
+  }
+  copy_file(&seed_path, &data_path)?;
+  let hash = hash_file(&seed_path)?;
+  manifest.files.insert(rel.to_string(), SeedRecord { hash, pending_hash: None });
+  save_manifest(data_root, &manifest)
+}
+
+fn load_manifest(data_root: &Path) -> anyhow::Result<SeedManifest> {
+  let path = data_root.join(SEED_MANIFEST);
+  if !path.exists() {
+    return Ok(SeedManifest::default());
+  }
+  read_json(&path)
+}
+
+fn save_manifest(data_root: &Path, manifest: &SeedManifest) -> anyhow::Result<()> {
+  write_json_atomic(&data_root.join(SEED_MANIFEST), manifest)
+}
+
+fn list_files(root: &Path) -> anyhow::Result<Vec<String>> {
+  let mut out = Vec::new();
+  collect_files(root, root, &mut out)?;
+  out.sort();
+  Ok(out)
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<String>) -> anyhow::Result<()> {
+  for entry in fs::read_dir(dir).with_context(|| format!("Failed to read seed directory {}", dir.display()))? {
+    let entry = entry?;
+    let path = entry.path();
+    if entry.file_type()?.is_dir() {
+      collect_files(root, &path, out)?;
+    } else if let Ok(rel) = path.strip_prefix(root) {
+      let parts: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+      out.push(parts.join("/"));
+    }
+  }
+  Ok(())
+}
+
+fn copy_file(from: &Path, to: &Path) -> anyhow::Result<()> {
+  if let Some(parent) = to.parent() {
+    ensure_dir(parent)?;
+  }
+  fs::copy(from, to).with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+  Ok(())
+}
+
+fn hash_file(path: &Path) -> anyhow::Result<String> {
+  let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+  Ok(hash_bytes(&bytes))
+}
+
+/// FNV-1a: stable across runs and platforms, which `DefaultHasher` is not.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+  let hash = bytes
+    .iter()
+    .fold(0xcbf29ce484222325u64, |acc, b| (acc ^ *b as u64).wrapping_mul(0x100000001b3));
+  format!("{:016x}", hash)
+}
+
+fn backup_path_for(path: &Path) -> PathBuf {
+  let mut backup = path.to_path_buf();
+  let file_name = path
+    .file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .unwrap_or_default();
+  backup.set_file_name(format!("{}.bak", file_name));
+  backup
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  #[test]
+  fn sync_adds_upgrades_and_defers_edited_files() {
+    let root = TempDir::new("seed-sync-test");
+    let seed_root = root.join("seed");
+    let data_root = root.join("data");
+    fs::create_dir_all(seed_root.join("profiles/a")).unwrap();
+    fs::write(seed_root.join("devices.json"), "[1]").unwrap();
+    fs::write(seed_root.join("profiles/a/bundle.json"), "{\"v\":1}").unwrap();
+
+    let report = sync_seed_data(&seed_root, &data_root).expect("first sync");
+    assert_eq!(report.added, vec!["devices.json", "profiles/a/bundle.json"]);
+
+    // Ship an update for both files, but the user edited the bundle meanwhile.
+    fs::write(seed_root.join("devices.json"), "[1,2]").unwrap();
+    fs::write(seed_root.join("profiles/a/bundle.json"), "{\"v\":2}").unwrap();
+    fs::write(data_root.join("profiles/a/bundle.json"), "{\"v\":1,\"mine\":true}").unwrap();
+    fs::create_dir_all(seed_root.join("profiles/b")).unwrap();
+    fs::write(seed_root.join("profiles/b/bundle.json"), "{}").unwrap();
+
+    let report = sync_seed_data(&seed_root, &data_root).expect("second sync");
+    assert_eq!(report.added, vec!["profiles/b/bundle.json"]);
+    assert_eq!(report.updated, vec!["devices.json"]);
+    assert_eq!(report.pending, vec!["profiles/a/bundle.json"]);
+    assert_eq!(fs::read_to_string(data_root.join("devices.json")).unwrap(), "[1,2]");
+    assert_eq!(
+      fs::read_to_string(data_root.join("profiles/a/bundle.json")).unwrap(),
+      "{\"v\":1,\"mine\":true}"
+    );
+
+    accept_seed_update(&seed_root, &data_root, "profiles/a/bundle.json").expect("accept");
+    assert_eq!(fs::read_to_string(data_root.join("profiles/a/bundle.json")).unwrap(), "{\"v\":2}");
+    assert!(data_root.join("profiles/a/bundle.json.bak").exists());
+    assert!(pending_seed_updates(&data_root).unwrap().is_empty());
+  }
+}
@@ -0,0 +1,547 @@
+use std::{
+  path::{Path, PathBuf},
+  sync::Mutex,
+};
+
+use anyhow::{anyhow, Context};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::models::{
+  device::{DeviceInfo, DeviceState},
+  script::{DelayClass, Script},
+  state::SessionState,
+};
+
+use super::{
+  files::{ensure_dir, read_json},
+  migrations::{self, Migration},
+  r#trait::{SeedSync, Store},
+  seeds::{hash_bytes, plan_seed, SeedAction, SeedRecord, SeedSyncReport},
+  store::SeedBundle,
+};
+
+const SCHEMA: &str = "
+  CREATE TABLE IF NOT EXISTS devices (
+    id TEXT PRIMARY KEY,
+    json TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS bundles (
+    device_id TEXT PRIMARY KEY,
+    json TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS session_state (
+    device_id TEXT PRIMARY KEY,
+    version INTEGER NOT NULL,
+    json TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id TEXT NOT NULL,
+    revision INTEGER,
+    committed_at INTEGER,
+    json TEXT NOT NULL
+  );
+  CREATE INDEX IF NOT EXISTS history_by_device ON history (device_id, id);
+  CREATE TABLE IF NOT EXISTS scripts (
+    device_id TEXT PRIMARY KEY,
+    json TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS delay_classes (
+    profile_id TEXT PRIMARY KEY,
+    json TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS seed_manifest (
+    rel TEXT PRIMARY KEY,
+    hash TEXT NOT NULL,
+    pending_hash TEXT
+  );
+  CREATE TABLE IF NOT EXISTS seed_backup (
+    rel TEXT PRIMARY KEY,
+    json TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS session_state_backup (
+    device_id TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    json TEXT NOT NULL
+  );
+";
+
+/// SQLite-backed `Store`. Seed devices and bundles are synced from
+/// `seed_root` on open: new and unedited rows follow the seeds, edited rows
+/// wait as pending updates, as with the JSON store's seed manifest.
+pub struct SqliteStore {
+  seed_root: PathBuf,
+  conn: Mutex<Connection>,
+  migrations: &'static [Migration],
+}
+
+impl SqliteStore {
+  pub fn open(seed_root: PathBuf, db_path: &Path) -> anyhow::Result<Self> {
+    if let Some(parent) = db_path.parent() {
+      ensure_dir(parent)?;
+    }
+    let conn = Connection::open(db_path).with_context(|| format!("Failed to open database {}", db_path.display()))?;
+    conn
+      .execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = FULL;")
+      .context("Failed to configure database")?;
+    conn.execute_batch(SCHEMA).context("Failed to create database schema")?;
+    let store = Self {
+      seed_root,
+      conn: Mutex::new(conn),
+      migrations: migrations::MIGRATIONS,
+    };
+    store.sync_seeds()?;
+    Ok(store)
+  }
+
+  /// Loads state through `migrations` instead of the shipped table.
+  #[cfg(test)]
+  pub fn with_migrations(mut self, migrations: &'static [Migration]) -> Self {
+    self.migrations = migrations;
+    self
+  }
+
+  /// Every row the seeds provide. Manifest keys are `devices/<id>` and the
+  /// bundle's seed path, `profiles/<id>/bundle.json`.
+  fn seed_rows(&self) -> anyhow::Result<Vec<SeedRow>> {
+    let devices: Vec<DeviceInfo> = read_json(&self.seed_root.join("devices.json"))?;
+    let mut rows = Vec::new();
+    for device in &devices {
+      rows.push(SeedRow {
+        rel: format!("devices/{}", device.id),
+        table: SeedTable::Devices,
+        id: device.id.clone(),
+        json: serde_json::to_string(device)?,
+      });
+      let rel = format!("profiles/{}/bundle.json", device.id);
+      let bundle_path = self.seed_root.join(&rel);
+      if bundle_path.exists() {
+        let bundle: SeedBundle = read_json(&bundle_path)?;
+        rows.push(SeedRow {
+          rel,
+          table: SeedTable::Bundles,
+          id: device.id.clone(),
+          json: serde_json::to_string(&bundle)?,
+        });
+      }
+    }
+    Ok(rows)
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SeedTable {
+  Devices,
+  Bundles,
+}
+
+impl SeedTable {
+  fn select(self) -> &'static str {
+    match self {
+      SeedTable::Devices => "SELECT json FROM devices WHERE id = ?1",
+      SeedTable::Bundles => "SELECT json FROM bundles WHERE device_id = ?1",
+    }
+  }
+
+  fn upsert(self) -> &'static str {
+    match self {
+      SeedTable::Devices => "INSERT INTO devices (id, json) VALUES (?1, ?2) ON CONFLICT (id) DO UPDATE SET json = excluded.json",
+      SeedTable::Bundles => {
+        "INSERT INTO bundles (device_id, json) VALUES (?1, ?2) ON CONFLICT (device_id) DO UPDATE SET json = excluded.json"
+      }
+    }
+  }
+}
+
+/// A seeded row, normalized to the JSON the store writes so an unedited row
+/// hashes the same as its seed.
+struct SeedRow {
+  rel: String,
+  table: SeedTable,
+  id: String,
+  json: String,
+}
+
+impl SeedRow {
+  fn stored(&self, conn: &Connection) -> anyhow::Result<Option<String>> {
+    Ok(conn.query_row(self.table.select(), params![self.id], |row| row.get(0)).optional()?)
+  }
+
+  fn write(&self, conn: &Connection) -> anyhow::Result<()> {
+    conn.execute(self.table.upsert(), params![self.id, self.json])?;
+    Ok(())
+  }
+}
+
+fn load_seed_record(conn: &Connection, rel: &str) -> anyhow::Result<Option<SeedRecord>> {
+  Ok(
+    conn
+      .query_row("SELECT hash, pending_hash FROM seed_manifest WHERE rel = ?1", params![rel], |row| {
+        Ok(SeedRecord { hash: row.get(0)?, pending_hash: row.get(1)? })
+      })
+      .optional()?,
+  )
+}
+
+fn save_seed_record(conn: &Connection, rel: &str, record: &SeedRecord) -> anyhow::Result<()> {
+  conn.execute(
+    "INSERT INTO seed_manifest (rel, hash, pending_hash) VALUES (?1, ?2, ?3)
+     ON CONFLICT (rel) DO UPDATE SET hash = excluded.hash, pending_hash = excluded.pending_hash",
+    params![rel, record.hash, record.pending_hash],
+  )?;
+  Ok(())
+}
+
+impl SeedSync for SqliteStore {
+  fn sync_seeds(&self) -> anyhow::Result<SeedSyncReport> {
+    let rows = self.seed_rows()?;
+    let mut conn = self.conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let mut report = SeedSyncReport::default();
+    for row in rows {
+      let seed_hash = hash_bytes(row.json.as_bytes());
+      let data_hash = row.stored(&tx)?.map(|json| hash_bytes(json.as_bytes()));
+      let action = plan_seed(&seed_hash, data_hash.as_deref(), load_seed_record(&tx, &row.rel)?.as_ref());
+      let record = match &action {
+        SeedAction::Add | SeedAction::Update => {
+          row.write(&tx)?;
+          SeedRecord { hash: seed_hash, pending_hash: None }
+        }
+        SeedAction::InSync => SeedRecord { hash: seed_hash, pending_hash: None },
+        SeedAction::Defer { hash } => SeedRecord { hash: hash.clone(), pending_hash: Some(seed_hash) },
+      };
+      save_seed_record(&tx, &row.rel, &record)?;
+      match action {
+        SeedAction::Add => report.added.push(row.rel),
+        SeedAction::Update => report.updated.push(row.rel),
+        SeedAction::Defer { .. } => report.pending.push(row.rel),
+        SeedAction::InSync => {}
+      }
+    }
+    tx.commit()?;
+    if !report.added.is_empty() || !report.updated.is_empty() {
+      log::info!(
+        "Seed sync: {} added, {} updated, {} pending",
+        report.added.len(),
+        report.updated.len(),
+        report.pending.len()
+      );
+    }
+    Ok(report)
+  }
+
+  fn pending_seed_updates(&self) -> anyhow::Result<Vec<String>> {
+    let conn = self.conn.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT rel FROM seed_manifest WHERE pending_hash IS NOT NULL ORDER BY rel")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    Ok(rows.collect::<Result<_, _>>()?)
+  }
+
+  /// The replaced row is kept in `seed_backup` so local edits can be recovered.
+  fn accept_seed_update(&self, rel_path: &str) -> anyhow::Result<()> {
+    let row = self
+      .seed_rows()?
+      .into_iter()
+      .find(|r| r.rel == rel_path)
+      .ok_or_else(|| anyhow!("No seed for {}", rel_path))?;
+    let mut conn = self.conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    if load_seed_record(&tx, rel_path)?.and_then(|r| r.pending_hash).is_none() {
+      return Err(anyhow!("No pending seed update for {}", rel_path));
+    }
+    if let Some(previous) = row.stored(&tx)? {
+      tx.execute(
+        "INSERT INTO seed_backup (rel, json) VALUES (?1, ?2) ON CONFLICT (rel) DO UPDATE SET json = excluded.json",
+        params![rel_path, previous],
+      )?;
+    }
+    row.write(&tx)?;
+    let record = SeedRecord { hash: hash_bytes(row.json.as_bytes()), pending_hash: None };
+    save_seed_record(&tx, rel_path, &record)?;
+    tx.commit()?;
+    Ok(())
+  }
+}
+
+fn insert_history(conn: &Connection, device_id: &str, committed: &DeviceState) -> anyhow::Result<()> {
+  conn.execute(
+    "INSERT INTO history (device_id, revision, committed_at, json) VALUES (?1, ?2, ?3, ?4)",
+    params![
+      device_id,
+      committed.revision,
+      committed.meta.as_ref().and_then(|m| m.committed_at).map(|t| t as i64),
+      serde_json::to_string(committed)?
+    ],
+  )?;
+  Ok(())
+}
+
+fn upsert_state(conn: &Connection, device_id: &str, state: &SessionState) -> anyhow::Result<()> {
+  conn.execute(
+    "INSERT INTO session_state (device_id, version, json) VALUES (?1, ?2, ?3)
+     ON CONFLICT (device_id) DO UPDATE SET version = excluded.version, json = excluded.json",
+    params![device_id, state.version, serde_json::to_string(state)?],
+  )?;
+  Ok(())
+}
+
+impl Store for SqliteStore {
+  fn prepare(&self) -> anyhow::Result<()> {
+    // Seeds are imported when the database is opened.
+    Ok(())
+  }
+
+  fn load_devices(&self) -> anyhow::Result<Vec<DeviceInfo>> {
+    let conn = self.conn.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT json FROM devices ORDER BY id")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut devices = Vec::new();
+    for json in rows {
+      devices.push(serde_json::from_str(&json?)?);
+    }
+    Ok(devices)
+  }
+
+  fn load_bundle(&self, device_id: &str) -> anyhow::Result<SeedBundle> {
+    let conn = self.conn.lock().unwrap();
+    let json: String = conn
+      .query_row("SELECT json FROM bundles WHERE device_id = ?1", params![device_id], |row| row.get(0))
+      .optional()?
+      .ok_or_else(|| anyhow!("No bundle stored for device {}", device_id))?;
+    serde_json::from_str(&json).with_context(|| format!("Failed to parse bundle for device {}", device_id))
+  }
+
+  fn load_session_state(&self, device_id: &str) -> anyhow::Result<Option<SessionState>> {
+    let mut conn = self.conn.lock().unwrap();
+    let json: Option<String> = conn
+      .query_row("SELECT json FROM session_state WHERE device_id = ?1", params![device_id], |row| row.get(0))
+      .optional()?;
+    let Some(json) = json else {
+      return Ok(None);
+    };
+
+    let raw: serde_json::Value = serde_json::from_str(&json)
+      .with_context(|| format!("Failed to parse stored state for device {}", device_id))?;
+    let from = migrations::version_of(&raw);
+    let migrated = migrations::migrate_with(raw, self.migrations)
+      .with_context(|| format!("Failed to migrate stored state for device {}", device_id))?;
+    let state: SessionState = serde_json::from_value(migrated)?;
+    if from != state.version {
+      let tx = conn.transaction()?;
+      tx.execute(
+        "INSERT INTO session_state_backup (device_id, version, json) VALUES (?1, ?2, ?3)",
+        params![device_id, from, json],
+      )?;
+      upsert_state(&tx, device_id, &state)?;
+      tx.commit()?;
+      log::info!("Migrated stored state for {} from v{} to v{}", device_id, from, state.version);
+    }
+    Ok(Some(state))
+  }
+
+  fn save_session_state(&self, device_id: &str, state: &SessionState) -> anyhow::Result<()> {
+    let conn = self.conn.lock().unwrap();
+    upsert_state(&conn, device_id, state)
+  }
+
+  fn load_history(&self, device_id: &str) -> anyhow::Result<Vec<DeviceState>> {
+    let conn = self.conn.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT json FROM history WHERE device_id = ?1 ORDER BY id")?;
+    let rows = stmt.query_map(params![device_id], |row| row.get::<_, String>(0))?;
+    let mut history = Vec::new();
+    for json in rows {
+      history.push(serde_json::from_str(&json?)?);
+    }
+    Ok(history)
+  }
+
+  fn append_history(&self, device_id: &str, committed: &DeviceState) -> anyhow::Result<()> {
+    let conn = self.conn.lock().unwrap();
+    insert_history(&conn, device_id, committed)
+  }
+
+  fn load_scripts(&self, device_id: &str) -> anyhow::Result<Option<Vec<Script>>> {
+    let conn = self.conn.lock().unwrap();
+    let json: Option<String> = conn
+      .query_row("SELECT json FROM scripts WHERE device_id = ?1", params![device_id], |row| row.get(0))
+      .optional()?;
+    json
+      .map(|json| serde_json::from_str(&json).with_context(|| format!("Failed to parse scripts for device {}", device_id)))
+      .transpose()
+  }
+
+  fn save_scripts(&self, device_id: &str, scripts: &[Script]) -> anyhow::Result<()> {
+    let conn = self.conn.lock().unwrap();
+    conn.execute(
+      "INSERT INTO scripts (device_id, json) VALUES (?1, ?2)
+       ON CONFLICT (device_id) DO UPDATE SET json = excluded.json",
+      params![device_id, serde_json::to_string(scripts)?],
+    )?;
+    Ok(())
+  }
+
+  fn load_delay_classes(&self, profile_id: &str) -> anyhow::Result<Option<Vec<DelayClass>>> {
+    let conn = self.conn.lock().unwrap();
+    let json: Option<String> = conn
+      .query_row("SELECT json FROM delay_classes WHERE profile_id = ?1", params![profile_id], |row| row.get(0))
+      .optional()?;
+    json
+      .map(|json| serde_json::from_str(&json).with_context(|| format!("Failed to parse delay classes for profile {}", profile_id)))
+      .transpose()
+  }
+
+  fn save_delay_classes(&self, profile_id: &str, classes: &[DelayClass]) -> anyhow::Result<()> {
+    let conn = self.conn.lock().unwrap();
+    conn.execute(
+      "INSERT INTO delay_classes (profile_id, json) VALUES (?1, ?2)
+       ON CONFLICT (profile_id) DO UPDATE SET json = excluded.json",
+      params![profile_id, serde_json::to_string(classes)?],
+    )?;
+    Ok(())
+  }
+
+  fn save_commit(&self, device_id: &str, state: &SessionState, committed: &DeviceState) -> anyhow::Result<()> {
+    let mut conn = self.conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    upsert_state(&tx, device_id, state)?;
+    insert_history(&tx, device_id, committed)?;
+    tx.commit()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{seed_root, TempDir};
+  use crate::backends::{mock::MockBackend, operation::OperationContext, r#trait::DeviceBackend};
+  use crate::models::{
+    binding::{Binding, BindingEntry},
+    state::MutationGuard,
+  };
+  use std::sync::Arc;
+
+  #[test]
+  fn backend_session_flow_on_sqlite() {
+    let seed_root = seed_root();
+    let dir = TempDir::new("sqlite-store-test");
+    let db_path = dir.join("studio.db");
+    let store = Arc::new(SqliteStore::open(seed_root.clone(), &db_path).expect("open db"));
+    let backend = MockBackend::with_store(store);
+
+    let device_id = backend.list_devices().expect("devices")[0].id.clone();
+    let session_id = backend.open_session(device_id.clone(), &OperationContext::new()).expect("open").session_id;
+    let entry = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:1,0".to_string(),
+      binding: Binding::SimpleAction {
+        action: "TAP".to_string(),
+        arg: Some("KC_A".to_string()),
+        meta: None,
+      },
+    };
+    backend.set_binding(session_id.clone(), entry, MutationGuard::default()).expect("set binding");
+    backend
+      .commit(session_id.clone(), Some("first".to_string()), MutationGuard::default(), false, &OperationContext::new())
+      .expect("commit");
+    backend
+      .commit(session_id.clone(), None, MutationGuard::default(), false, &OperationContext::new())
+      .expect("commit");
+    assert_eq!(backend.commit_history(session_id).expect("history").len(), 2);
+
+    let reopened = SqliteStore::open(seed_root, &db_path).expect("reopen db");
+    let state = reopened.load_session_state(&device_id).expect("load").expect("state");
+    assert_eq!(state.committed.and_then(|c| c.revision), Some(2));
+  }
+
+  #[test]
+  fn migrating_backs_up_the_row_before_overwriting_it() {
+    const TWO_STEPS: &[Migration] = &[Ok, Ok];
+    let seed_root = seed_root();
+    let dir = TempDir::new("sqlite-store-test");
+    let store = SqliteStore::open(seed_root, &dir.join("studio.db")).expect("open db").with_migrations(TWO_STEPS);
+    let v1 = r#"{"sessionId":"s1"}"#;
+    let stored = |device_id: &str| -> (u32, String) {
+      let conn = store.conn.lock().unwrap();
+      conn
+        .query_row("SELECT version, json FROM session_state WHERE device_id = ?1", params![device_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+    };
+    for device_id in ["dev", "blocked"] {
+      let conn = store.conn.lock().unwrap();
+      conn
+        .execute("INSERT INTO session_state (device_id, version, json) VALUES (?1, 1, ?2)", params![device_id, v1])
+        .unwrap();
+    }
+
+    assert_eq!(store.load_session_state("dev").expect("load").expect("state").version, 3);
+    assert_eq!(stored("dev").0, 3);
+    let backup: (u32, String) = {
+      let conn = store.conn.lock().unwrap();
+      conn
+        .query_row("SELECT version, json FROM session_state_backup WHERE device_id = 'dev'", [], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+    };
+    assert_eq!(backup, (1, v1.to_string()));
+
+    // A backup that can't be written leaves the row as it was.
+    store.conn.lock().unwrap().execute_batch("DROP TABLE session_state_backup").unwrap();
+    assert!(store.load_session_state("blocked").is_err());
+    assert_eq!(stored("blocked"), (1, v1.to_string()));
+  }
+
+  fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+      let path = entry.unwrap().path();
+      let dest = to.join(path.file_name().unwrap());
+      if path.is_dir() {
+        copy_dir(&path, &dest);
+      } else {
+        std::fs::copy(&path, &dest).unwrap();
+      }
+    }
+  }
+
+  fn rename_in_file(path: &Path, pointer: &str, name: &str) {
+    let mut value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    *value.pointer_mut(pointer).unwrap() = serde_json::Value::from(name);
+    std::fs::write(path, serde_json::to_string_pretty(&value).unwrap()).unwrap();
+  }
+
+  #[test]
+  fn seed_sync_updates_unedited_rows_and_defers_edited_ones() {
+    let dir = TempDir::new("sqlite-seed-test");
+    let seed_root = dir.join("seed");
+    copy_dir(&crate::testing::seed_root(), &seed_root);
+    let store = SqliteStore::open(seed_root.clone(), &dir.join("studio.db")).expect("open db");
+    let device_id = read_json::<Vec<DeviceInfo>>(&seed_root.join("devices.json")).unwrap()[0].id.clone();
+    let bundle_rel = format!("profiles/{}/bundle.json", device_id);
+
+    // The user edits the bundle row, then new seeds ship for the device and its bundle.
+    let mut edited = store.load_bundle(&device_id).expect("bundle");
+    edited.device.name = "Mine".to_string();
+    store
+      .conn
+      .lock()
+      .unwrap()
+      .execute(SeedTable::Bundles.upsert(), params![device_id, serde_json::to_string(&edited).unwrap()])
+      .unwrap();
+    rename_in_file(&seed_root.join("devices.json"), "/0/name", "Shipped");
+    rename_in_file(&seed_root.join(&bundle_rel), "/device/name", "Shipped");
+
+    let report = store.sync_seeds().expect("sync");
+    assert!(report.added.is_empty());
+    assert_eq!(report.updated, vec![format!("devices/{}", device_id)]);
+    assert_eq!(report.pending, vec![bundle_rel.clone()]);
+    let device = store.load_devices().unwrap().into_iter().find(|d| d.id == device_id).unwrap();
+    assert_eq!(device.name, "Shipped");
+    assert_eq!(store.load_bundle(&device_id).unwrap().device.name, "Mine");
+    assert_eq!(store.pending_seed_updates().unwrap(), vec![bundle_rel.clone()]);
+
+    store.accept_seed_update(&bundle_rel).expect("accept");
+    assert_eq!(store.load_bundle(&device_id).unwrap().device.name, "Shipped");
+    assert!(store.pending_seed_updates().unwrap().is_empty());
+    assert!(store.accept_seed_update(&bundle_rel).is_err());
+  }
+}
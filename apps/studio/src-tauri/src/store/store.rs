@@ -1,29 +1,54 @@
 use std::{
   collections::HashMap,
   path::PathBuf,
+  sync::{Arc, Mutex},
 };
 
 use anyhow::Context;
 use crate::models::{
   binding::BindingEntry,
   bundle::{Profile, ProfileBundle},
-  device::{Capabilities, DeviceInfo, DeviceState, LayerState},
+  device::{Capabilities, DeviceInfo, DeviceProbe, DeviceState, LayerState},
   layout::NormalizedLayout,
-  script::Script,
-  state::SessionState,
+  script::{DelayClass, Script},
+  state::{SessionState, StateRecovery},
 };
 
-use super::files::{copy_seed_data_if_missing, ensure_dir, read_json, write_json_atomic};
+use crate::scripts::validate::validate_scripts;
+
+use super::{
+  files::{ensure_dir, read_json, read_json_recovering, write_json_atomic, write_json_generational},
+  migrations::{self, Migration},
+  r#trait::{SeedSync, Store},
+  seeds::{self, SeedSyncReport, SEED_MANIFEST},
+};
+
+/// Good generations kept next to each state file for corruption recovery.
+pub const STATE_GENERATIONS: usize = 3;
 
 #[derive(Clone)]
 pub struct MockStore {
   seed_root: PathBuf,
   data_root: PathBuf,
+  recoveries: Arc<Mutex<HashMap<String, StateRecovery>>>,
+  migrations: &'static [Migration],
 }
 
 impl MockStore {
   pub fn new(seed_root: PathBuf, data_root: PathBuf) -> Self {
-    Self { seed_root, data_root }
+    Self {
+      seed_root,
+      data_root,
+      recoveries: Arc::new(Mutex::new(HashMap::new())),
+      migrations: migrations::MIGRATIONS,
+    }
+  }
+
+  /// Loads state through `migrations` instead of the shipped table.
+  #[cfg(test)]
+  pub fn with_migrations(mut self, migrations: &'static [Migration]) -> Self {
+    self.migrations = migrations;
+    self
   }
 
   pub fn init_dirs(&self) -> anyhow::Result<()> {
@@ -31,17 +56,29 @@ impl MockStore {
     Ok(())
   }
 
+  /// Runs the first seed sync for a fresh data root; later upgrades go through `sync_seeds`.
   pub fn copy_seeds_if_needed(&self) -> anyhow::Result<()> {
-    copy_seed_data_if_missing(&self.seed_root, &self.data_root)?;
+    if !self.data_root.join(SEED_MANIFEST).exists() {
+      self.sync_seeds()?;
+    }
     Ok(())
   }
 
+  /// Prefers the synced copy under `data_root`, falling back to the shipped seed.
+  fn seeded_path(&self, rel: &[&str]) -> PathBuf {
+    let data = rel.iter().fold(self.data_root.clone(), |p, part| p.join(part));
+    if data.exists() {
+      return data;
+    }
+    rel.iter().fold(self.seed_root.clone(), |p, part| p.join(part))
+  }
+
   fn devices_path(&self) -> PathBuf {
-    self.seed_root.join("devices.json")
+    self.seeded_path(&["devices.json"])
   }
 
   fn bundle_path(&self, device_id: &str) -> PathBuf {
-    self.seed_root.join("profiles").join(device_id).join("bundle.json")
+    self.seeded_path(&["profiles", device_id, "bundle.json"])
   }
 
   fn state_path(&self, device_id: &str) -> PathBuf {
@@ -51,60 +88,151 @@ impl MockStore {
       .join(format!("{}.json", device_id))
   }
 
-  pub fn load_devices(&self) -> anyhow::Result<Vec<DeviceInfo>> {
+  fn state_backup_path(&self, device_id: &str, version: u32) -> PathBuf {
+    self
+      .data_root
+      .join("state")
+      .join(format!("{}.v{}.bak.json", device_id, version))
+  }
+
+  fn scripts_path(&self, device_id: &str) -> PathBuf {
+    self
+      .data_root
+      .join("scripts")
+      .join(format!("{}.json", device_id))
+  }
+
+  fn delay_classes_path(&self, profile_id: &str) -> PathBuf {
+    self
+      .data_root
+      .join("delay-classes")
+      .join(format!("{}.json", profile_id))
+  }
+
+  fn history_path(&self, device_id: &str) -> PathBuf {
+    self
+      .data_root
+      .join("history")
+      .join(format!("{}.json", device_id))
+  }
+}
+
+impl SeedSync for MockStore {
+  fn sync_seeds(&self) -> anyhow::Result<SeedSyncReport> {
+    seeds::sync_seed_data(&self.seed_root, &self.data_root)
+  }
+
+  fn pending_seed_updates(&self) -> anyhow::Result<Vec<String>> {
+    seeds::pending_seed_updates(&self.data_root)
+  }
+
+  fn accept_seed_update(&self, rel_path: &str) -> anyhow::Result<()> {
+    seeds::accept_seed_update(&self.seed_root, &self.data_root, rel_path)
+  }
+}
+
+impl Store for MockStore {
+  fn prepare(&self) -> anyhow::Result<()> {
+    self.init_dirs()?;
+    self.copy_seeds_if_needed()
+  }
+
+  fn load_devices(&self) -> anyhow::Result<Vec<DeviceInfo>> {
     read_json(&self.devices_path()).with_context(|| format!("Failed to load devices from {}", self.devices_path().display()))
   }
 
-  pub fn load_bundle(&self, device_id: &str) -> anyhow::Result<SeedBundle> {
+  fn load_bundle(&self, device_id: &str) -> anyhow::Result<SeedBundle> {
     let path = self.bundle_path(device_id);
     let data: SeedBundle = read_json(&path).with_context(|| format!("Failed to load bundle for device {} from {}", device_id, path.display()))?;
     Ok(data)
   }
 
-  pub fn load_session_state(&self, device_id: &str) -> anyhow::Result<Option<SessionState>> {
+  fn load_session_state(&self, device_id: &str) -> anyhow::Result<Option<SessionState>> {
     let path = self.state_path(device_id);
-    if !path.exists() {
+    let recovered = read_json_recovering(&path, STATE_GENERATIONS, |data| {
+      let raw: serde_json::Value = serde_json::from_str(data)?;
+      // Only the outer shape: older schemas are checked after migrating.
+      if !raw.is_object() {
+        anyhow::bail!("State is not a JSON object");
+      }
+      Ok(raw)
+    })
+    .with_context(|| format!("Failed to read state file {}", path.display()))?;
+    let Some(recovered) = recovered else {
       return Ok(None);
+    };
+    if let Some(recovery) = recovered.recovery {
+      let mut guard = self.recoveries.lock().unwrap();
+      guard.insert(device_id.to_string(), recovery);
     }
-    let mut state: SessionState = read_json(&path).with_context(|| format!("Failed to read state file {}", path.display()))?;
-    if state.version != SessionState::current_version() {
-      state.version = SessionState::current_version();
+
+    let raw = recovered.value;
+    let from = migrations::version_of(&raw);
+    let migrated = migrations::migrate_with(raw.clone(), self.migrations)
+      .with_context(|| format!("Failed to migrate state file {}", path.display()))?;
+    let state: SessionState = serde_json::from_value(migrated)
+      .with_context(|| format!("Failed to parse state file {}", path.display()))?;
+    if from != state.version {
+      // Back up what was actually loaded: the primary file may be the
+      // corrupt one an older generation was recovered in place of.
+      let backup = self.state_backup_path(device_id, from);
+      write_json_atomic(&backup, &raw)
+        .with_context(|| format!("Failed to back up {} to {}", path.display(), backup.display()))?;
       self.save_session_state(device_id, &state)?;
-      log::info!("Migrated state {} to version {}", path.display(), state.version);
+      log::info!("Migrated state {} from v{} to v{}", path.display(), from, state.version);
     }
     Ok(Some(state))
   }
 
-  pub fn save_session_state(&self, device_id: &str, state: &SessionState) -> anyhow::Result<()> {
+  fn save_session_state(&self, device_id: &str, state: &SessionState) -> anyhow::Result<()> {
     let path = self.state_path(device_id);
     if let Some(parent) = path.parent() {
       ensure_dir(parent)?;
     }
-    write_json_atomic(&path, state)
+    write_json_generational(&path, state, STATE_GENERATIONS)
   }
 
-  pub fn initial_state_from_bundle(
-    &self,
-    device_id: &str,
-    bundle: &SeedBundle,
-  ) -> SessionState {
-    let base_state = bundle
-      .committed_state
-      .clone()
-      .unwrap_or_else(|| DeviceState {
-        profile_id: bundle.profile.id.clone(),
-        layers: bundle.profile.layers.clone(),
-        revision: Some(0),
-        checksum: Some(0),
-      });
+  fn take_recovery(&self, device_id: &str) -> Option<StateRecovery> {
+    let mut guard = self.recoveries.lock().unwrap();
+    guard.remove(device_id)
+  }
 
-    SessionState {
-      version: SessionState::current_version(),
-      session_id: format!("coldstart-{}", device_id),
-      staged: Some(base_state.clone()),
-      applied: Some(base_state.clone()),
-      committed: Some(base_state),
+  fn load_history(&self, device_id: &str) -> anyhow::Result<Vec<DeviceState>> {
+    let path = self.history_path(device_id);
+    if !path.exists() {
+      return Ok(Vec::new());
     }
+    read_json(&path).with_context(|| format!("Failed to read history file {}", path.display()))
+  }
+
+  fn append_history(&self, device_id: &str, committed: &DeviceState) -> anyhow::Result<()> {
+    let mut history = self.load_history(device_id)?;
+    history.push(committed.clone());
+    write_json_atomic(&self.history_path(device_id), &history)
+  }
+
+  fn load_scripts(&self, device_id: &str) -> anyhow::Result<Option<Vec<Script>>> {
+    let path = self.scripts_path(device_id);
+    if !path.exists() {
+      return Ok(None);
+    }
+    read_json(&path).with_context(|| format!("Failed to read scripts file {}", path.display()))
+  }
+
+  fn save_scripts(&self, device_id: &str, scripts: &[Script]) -> anyhow::Result<()> {
+    write_json_atomic(&self.scripts_path(device_id), &scripts)
+  }
+
+  fn load_delay_classes(&self, profile_id: &str) -> anyhow::Result<Option<Vec<DelayClass>>> {
+    let path = self.delay_classes_path(profile_id);
+    if !path.exists() {
+      return Ok(None);
+    }
+    read_json(&path).with_context(|| format!("Failed to read delay classes file {}", path.display()))
+  }
+
+  fn save_delay_classes(&self, profile_id: &str, classes: &[DelayClass]) -> anyhow::Result<()> {
+    write_json_atomic(&self.delay_classes_path(profile_id), &classes)
   }
 }
 
@@ -112,6 +240,9 @@ impl MockStore {
 pub struct SeedBundle {
   pub device: DeviceInfo,
   pub capabilities: Capabilities,
+  /// Probe answers the mock device gives on `open_session`.
+  #[serde(default)]
+  pub probe: Option<DeviceProbe>,
   pub profile: Profile,
   pub layout: Option<NormalizedLayout>,
   #[serde(default)]
@@ -124,6 +255,27 @@ pub struct SeedBundle {
 }
 
 impl SeedBundle {
+  pub fn initial_state(&self, device_id: &str) -> SessionState {
+    let base_state = self
+      .committed_state
+      .clone()
+      .unwrap_or_else(|| DeviceState {
+        profile_id: self.profile.id.clone(),
+        layers: self.profile.layers.clone(),
+        revision: Some(0),
+        checksum: Some(0),
+        meta: None,
+      });
+
+    SessionState {
+      version: SessionState::current_version(),
+      session_id: format!("coldstart-{}", device_id),
+      staged: Some(base_state.clone()),
+      applied: Some(base_state.clone()),
+      committed: Some(base_state),
+    }
+  }
+
   pub fn to_profile_bundle(
     &self,
     session_id: String,
@@ -137,6 +289,7 @@ impl SeedBundle {
       .or_else(|| state.committed.as_ref().map(|c| c.layers.clone()))
       .unwrap_or_else(|| self.profile.layers.clone());
 
+    let script_warnings = validate_scripts(&self.scripts, &layers);
     let mut profile = self.profile.clone();
     profile.layers = layers;
 
@@ -144,6 +297,7 @@ impl SeedBundle {
       session_id,
       device: self.device.clone(),
       capabilities: self.capabilities.clone(),
+      probe: None,
       profile,
       layout: self.layout.clone(),
       targets: if self.targets.is_empty() {
@@ -152,10 +306,12 @@ impl SeedBundle {
         self.targets.clone()
       },
       scripts: self.scripts.clone(),
+      script_warnings,
       committed_state: state.committed.clone(),
       applied_state: state.applied.clone(),
       staged_state: state.staged.clone(),
       bindings: self.bindings.clone(),
+      recovery: None,
     }
   }
 
@@ -0,0 +1,48 @@
+use crate::models::{
+  device::{DeviceInfo, DeviceState},
+  script::{DelayClass, Script},
+  state::{SessionState, StateRecovery},
+};
+
+use super::{seeds::SeedSyncReport, store::SeedBundle};
+
+/// Persistence used by the mock backend: device list, seed bundles,
+/// per-device session state and commit history.
+pub trait Store {
+  /// Creates the storage and imports seed data if this is the first run.
+  fn prepare(&self) -> anyhow::Result<()>;
+  fn load_devices(&self) -> anyhow::Result<Vec<DeviceInfo>>;
+  fn load_bundle(&self, device_id: &str) -> anyhow::Result<SeedBundle>;
+  fn load_session_state(&self, device_id: &str) -> anyhow::Result<Option<SessionState>>;
+  fn save_session_state(&self, device_id: &str, state: &SessionState) -> anyhow::Result<()>;
+  fn load_history(&self, device_id: &str) -> anyhow::Result<Vec<DeviceState>>;
+  fn append_history(&self, device_id: &str, committed: &DeviceState) -> anyhow::Result<()>;
+  /// The device's edited scripts, or `None` while it still uses the seed's.
+  fn load_scripts(&self, device_id: &str) -> anyhow::Result<Option<Vec<Script>>>;
+  fn save_scripts(&self, device_id: &str, scripts: &[Script]) -> anyhow::Result<()>;
+  /// The profile's edited delay classes, or `None` while it still uses the seed's.
+  fn load_delay_classes(&self, profile_id: &str) -> anyhow::Result<Option<Vec<DelayClass>>>;
+  fn save_delay_classes(&self, profile_id: &str, classes: &[DelayClass]) -> anyhow::Result<()>;
+
+  /// Saves the committed session together with its history entry. Stores
+  /// with transactions should override this so both land or neither does.
+  fn save_commit(&self, device_id: &str, state: &SessionState, committed: &DeviceState) -> anyhow::Result<()> {
+    self.save_session_state(device_id, state)?;
+    self.append_history(device_id, committed)
+  }
+
+  /// Returns (and clears) the last corruption recovery for a device's state.
+  fn take_recovery(&self, _device_id: &str) -> Option<StateRecovery> {
+    None
+  }
+}
+
+/// Brings seeded data up to newer shipped seeds without overwriting what the
+/// user edited; edited items wait as pending until accepted.
+pub trait SeedSync {
+  fn sync_seeds(&self) -> anyhow::Result<SeedSyncReport>;
+  fn pending_seed_updates(&self) -> anyhow::Result<Vec<String>>;
+  /// Replaces the user's copy of a pending item with the shipped seed,
+  /// keeping the old copy as a backup.
+  fn accept_seed_update(&self, rel_path: &str) -> anyhow::Result<()>;
+}
@@ -0,0 +1,37 @@
+//! Fixtures shared by unit tests.
+
+use std::{
+  ops::Deref,
+  path::{Path, PathBuf},
+};
+
+use uuid::Uuid;
+
+/// The seed data shipped under `mock/`.
+pub fn seed_root() -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("mock")
+}
+
+/// A unique path under the system temp dir, removed when dropped so a
+/// failing assertion doesn't leave it behind. The directory is not created.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+  pub fn new(prefix: &str) -> Self {
+    Self(std::env::temp_dir().join(format!("{}-{}", prefix, Uuid::new_v4())))
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
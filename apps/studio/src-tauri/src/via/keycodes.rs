@@ -0,0 +1,71 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde::Deserialize;
+
+/// The keycode catalog the editor uses, so both sides agree on names.
+const CATALOG: &str = include_str!("../../../src/assets/catalogs/default.json");
+
+#[derive(Deserialize)]
+struct CatalogEntry {
+  id: String,
+  #[serde(default)]
+  code: Option<u16>,
+  #[serde(default)]
+  aliases: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Catalog {
+  keycodes: Vec<CatalogEntry>,
+}
+
+/// QMK keycode names and values from the shared catalog.
+pub struct KeycodeTable {
+  by_name: HashMap<String, u16>,
+  by_code: HashMap<u16, String>,
+}
+
+impl KeycodeTable {
+  pub fn get() -> &'static KeycodeTable {
+    static TABLE: OnceLock<KeycodeTable> = OnceLock::new();
+    TABLE.get_or_init(|| {
+      let catalog: Catalog = serde_json::from_str(CATALOG).expect("bundled keycode catalog is valid");
+      let mut by_name = HashMap::new();
+      let mut by_code = HashMap::new();
+      for entry in catalog.keycodes {
+        let Some(code) = entry.code else {
+          continue;
+        };
+        for alias in &entry.aliases {
+          by_name.entry(alias.clone()).or_insert(code);
+        }
+        by_code.entry(code).or_insert_with(|| entry.id.clone());
+        by_name.insert(entry.id, code);
+      }
+      KeycodeTable { by_name, by_code }
+    })
+  }
+
+  /// Resolves a catalog id (`KC_ENTER`), alias (`ENTER`, `KC_ENT`), id
+  /// without its prefix (`LSFT`) or hex literal (`0x5220`).
+  pub fn code(&self, name: &str) -> Option<u16> {
+    if let Some(hex) = name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+      return u16::from_str_radix(hex, 16).ok();
+    }
+    self
+      .by_name
+      .get(name)
+      .or_else(|| name.strip_prefix("KC_").and_then(|n| self.by_name.get(n)))
+      .or_else(|| self.by_name.get(&format!("KC_{}", name)))
+      .copied()
+  }
+
+  /// Catalog id for `code`, or a hex literal when the catalog has none.
+  pub fn name(&self, code: u16) -> String {
+    self
+      .by_code
+      .get(&code)
+      .cloned()
+      .unwrap_or_else(|| format!("0x{:04X}", code))
+  }
+}
@@ -0,0 +1,345 @@
+//! VIA dynamic macro buffer: NUL-separated macros made of plain ASCII text and
+//! `SS_QMK_PREFIX` sequences, as read by QMK's `dynamic_keymap_macro_send`.
+
+use std::fmt;
+
+use anyhow::{anyhow, bail};
+
+use crate::{
+  models::script::{ExecutionTarget, Script, Step},
+  scripts::validate::repeat_count,
+};
+
+use super::keycodes::KeycodeTable;
+
+pub const SS_QMK_PREFIX: u8 = 0x01;
+pub const SS_TAP_CODE: u8 = 0x01;
+pub const SS_DOWN_CODE: u8 = 0x02;
+pub const SS_UP_CODE: u8 = 0x03;
+pub const SS_DELAY_CODE: u8 = 0x04;
+/// Added to TAP/DOWN/UP for the 16-bit keycode forms.
+const SS_WIDE_OFFSET: u8 = 0x04;
+const DELAY_TERMINATOR: u8 = b'|';
+
+/// Scripts did not fit the device's macro slots or buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacroOverflow {
+  pub script_id: String,
+  pub script_name: String,
+  /// Buffer bytes needed up to and including this script. A lower bound
+  /// when encoding stopped as soon as the script outgrew the buffer.
+  pub needed: usize,
+  pub capacity: usize,
+  /// Set when the device ran out of macro slots rather than bytes.
+  pub slot_limit: Option<usize>,
+}
+
+impl fmt::Display for MacroOverflow {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.slot_limit {
+      Some(limit) => write!(
+        f,
+        "Script \"{}\" ({}) does not fit: the device holds {} macros",
+        self.script_name, self.script_id, limit
+      ),
+      None => write!(
+        f,
+        "Script \"{}\" ({}) overflows the macro buffer: needs {} of {} bytes",
+        self.script_name, self.script_id, self.needed, self.capacity
+      ),
+    }
+  }
+}
+
+impl std::error::Error for MacroOverflow {}
+
+/// Packs `scripts` into one buffer of exactly `capacity` bytes, one macro
+/// slot per script in order.
+pub fn encode_buffer(scripts: &[Script], capacity: usize, max_macros: usize) -> anyhow::Result<Vec<u8>> {
+  let mut buffer = Vec::with_capacity(capacity);
+  for (index, script) in scripts.iter().enumerate() {
+    let room = capacity.saturating_sub(buffer.len() + 1);
+    let (encoded, needed) = match encode_script(script, room) {
+      Ok(encoded) => {
+        let needed = buffer.len() + encoded.len() + 1;
+        (encoded, needed)
+      }
+      Err(e) => match e.downcast::<MacroOverflow>() {
+        Ok(overflow) => (Vec::new(), buffer.len() + overflow.needed + 1),
+        Err(e) => return Err(e),
+      },
+    };
+    if index >= max_macros || needed > capacity {
+      return Err(
+        MacroOverflow {
+          script_id: script.id.clone(),
+          script_name: script.name.clone(),
+          needed,
+          capacity,
+          slot_limit: (index >= max_macros).then_some(max_macros),
+        }
+        .into(),
+      );
+    }
+    buffer.extend_from_slice(&encoded);
+    buffer.push(0);
+  }
+  buffer.resize(capacity, 0);
+  Ok(buffer)
+}
+
+/// Encodes one script without its NUL terminator. `REPEAT` bodies are
+/// unrolled, since the firmware has no loops; unrolling fails with a
+/// [`MacroOverflow`] as soon as the output passes `limit` bytes.
+pub fn encode_script(script: &Script, limit: usize) -> anyhow::Result<Vec<u8>> {
+  let mut out = Vec::new();
+  encode_steps(&mut out, script, &script.steps, limit)?;
+  Ok(out)
+}
+
+fn encode_steps(out: &mut Vec<u8>, script: &Script, steps: &[Step], limit: usize) -> anyhow::Result<()> {
+  let table = KeycodeTable::get();
+  for step in steps {
+    let fail = |why: String| anyhow!("Script {} step {} ({}): {}", script.id, step.id, step.name, why);
+    let arg = step.arg.as_deref().unwrap_or("");
+    match step.op.as_str() {
+      "TAP" | "DOWN" | "UP" => {
+        let code = table.code(arg).ok_or_else(|| fail(format!("unknown keycode {:?}", arg)))?;
+        let action = match step.op.as_str() {
+          "TAP" => SS_TAP_CODE,
+          "DOWN" => SS_DOWN_CODE,
+          _ => SS_UP_CODE,
+        };
+        encode_key(out, action, code);
+      }
+      "WAIT" => {
+        let ms: u32 = arg.parse().map_err(|_| fail(format!("invalid delay {:?}", arg)))?;
+        out.extend_from_slice(&[SS_QMK_PREFIX, SS_DELAY_CODE]);
+        out.extend_from_slice(ms.to_string().as_bytes());
+        out.push(DELAY_TERMINATOR);
+      }
+      "TEXT" => {
+        if let Some(c) = arg.chars().find(|c| !c.is_ascii() || *c == '\0' || *c as u8 == SS_QMK_PREFIX) {
+          return Err(fail(format!("character {:?} cannot be sent as text", c)));
+        }
+        out.extend_from_slice(arg.as_bytes());
+      }
+      "REPEAT" => {
+        let count = repeat_count(step).map_err(|e| fail(e.to_string()))?.ok_or_else(|| fail("cannot repeat forever".to_string()))?;
+        for _ in 0..count {
+          encode_steps(out, script, &step.steps, limit)?;
+          if out.len() > limit {
+            return Err(
+              MacroOverflow {
+                script_id: script.id.clone(),
+                script_name: script.name.clone(),
+                needed: out.len(),
+                capacity: limit,
+                slot_limit: None,
+              }
+              .into(),
+            );
+          }
+        }
+      }
+      other => return Err(fail(format!("{} has no VIA macro form", other))),
+    }
+  }
+  Ok(())
+}
+
+fn encode_key(out: &mut Vec<u8>, action: u8, code: u16) {
+  if code > 0 && code <= 0xFF {
+    out.extend_from_slice(&[SS_QMK_PREFIX, action, code as u8]);
+    return;
+  }
+  // Little-endian, with zero bytes sent as 0xFF so they don't end the macro.
+  let [lo, hi] = code.to_le_bytes();
+  let nonzero = |b: u8| if b == 0 { 0xFF } else { b };
+  out.extend_from_slice(&[SS_QMK_PREFIX, action + SS_WIDE_OFFSET, nonzero(lo), nonzero(hi)]);
+}
+
+/// Splits a device buffer into scripts, one per non-empty macro slot among
+/// the first `max_macros`. Slot `n` becomes script `via-macro-n`.
+pub fn decode_buffer(buffer: &[u8], max_macros: usize, profile_id: &str) -> anyhow::Result<Vec<Script>> {
+  let mut scripts = Vec::new();
+  for (index, bytes) in buffer.split(|b| *b == 0).take(max_macros).enumerate() {
+    if bytes.is_empty() {
+      continue;
+    }
+    let steps = decode_steps(bytes).map_err(|e| anyhow!("Macro {}: {}", index, e))?;
+    scripts.push(Script {
+      id: format!("via-macro-{}", index),
+      profile_id: profile_id.to_string(),
+      name: format!("Macro {}", index),
+      target: ExecutionTarget::Device,
+      steps,
+      meta: None,
+    });
+  }
+  Ok(scripts)
+}
+
+fn decode_steps(bytes: &[u8]) -> anyhow::Result<Vec<Step>> {
+  let table = KeycodeTable::get();
+  let mut steps: Vec<Step> = Vec::new();
+  let mut text = String::new();
+  let mut i = 0;
+  let push = |steps: &mut Vec<Step>, op: &str, name: String, arg: String| {
+    steps.push(Step {
+      id: steps.len() as u32 + 1,
+      name,
+      op: op.to_string(),
+      arg: Some(arg),
+      class: None,
+      ..Default::default()
+    });
+  };
+  while i < bytes.len() {
+    if bytes[i] != SS_QMK_PREFIX {
+      text.push(bytes[i] as char);
+      i += 1;
+      continue;
+    }
+    if !text.is_empty() {
+      let t = std::mem::take(&mut text);
+      push(&mut steps, "TEXT", format!("Type {:?}", t), t);
+    }
+    let Some(&action) = bytes.get(i + 1) else {
+      bail!("truncated sequence at byte {}", i);
+    };
+    match action {
+      SS_TAP_CODE | SS_DOWN_CODE | SS_UP_CODE => {
+        let code = *bytes.get(i + 2).ok_or_else(|| anyhow!("truncated keycode at byte {}", i))?;
+        push_key(&mut steps, table, action, u16::from(code));
+        i += 3;
+      }
+      a if (SS_TAP_CODE + SS_WIDE_OFFSET..=SS_UP_CODE + SS_WIDE_OFFSET).contains(&a) => {
+        let (lo, hi) = match (bytes.get(i + 2), bytes.get(i + 3)) {
+          (Some(lo), Some(hi)) => (*lo, *hi),
+          _ => bail!("truncated keycode at byte {}", i),
+        };
+        let zeroed = |b: u8| if b == 0xFF { 0 } else { b };
+        push_key(&mut steps, table, a - SS_WIDE_OFFSET, u16::from_le_bytes([zeroed(lo), zeroed(hi)]));
+        i += 4;
+      }
+      SS_DELAY_CODE => {
+        let start = i + 2;
+        let len = bytes[start..]
+          .iter()
+          .position(|b| *b == DELAY_TERMINATOR)
+          .ok_or_else(|| anyhow!("unterminated delay at byte {}", i))?;
+        let ms = std::str::from_utf8(&bytes[start..start + len])?;
+        let ms: u32 = ms.parse().map_err(|_| anyhow!("invalid delay {:?} at byte {}", ms, i))?;
+        push(&mut steps, "WAIT", format!("Wait {}ms", ms), ms.to_string());
+        i = start + len + 1;
+      }
+      other => bail!("unknown action 0x{:02X} at byte {}", other, i),
+    }
+  }
+  if !text.is_empty() {
+    push(&mut steps, "TEXT", format!("Type {:?}", text), text);
+  }
+  Ok(steps)
+}
+
+fn push_key(steps: &mut Vec<Step>, table: &KeycodeTable, action: u8, code: u16) {
+  let (op, verb) = match action {
+    SS_TAP_CODE => ("TAP", "Tap"),
+    SS_DOWN_CODE => ("DOWN", "Down"),
+    _ => ("UP", "Up"),
+  };
+  let key = table.name(code);
+  steps.push(Step {
+    id: steps.len() as u32 + 1,
+    name: format!("{} {}", verb, key.trim_start_matches("KC_")),
+    op: op.to_string(),
+    arg: Some(key),
+    class: None,
+    ..Default::default()
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn step(id: u32, op: &str, arg: &str) -> Step {
+    Step {
+      id,
+      name: format!("{} {}", op, arg),
+      op: op.to_string(),
+      arg: Some(arg.to_string()),
+      class: None,
+      ..Default::default()
+    }
+  }
+
+  fn script(id: &str, steps: Vec<Step>) -> Script {
+    Script {
+      id: id.to_string(),
+      profile_id: "p-default".to_string(),
+      name: id.to_uppercase(),
+      target: ExecutionTarget::Device,
+      steps,
+      meta: None,
+    }
+  }
+
+  #[test]
+  fn round_trips_and_names_the_overflowing_script() {
+    let copy = script(
+      "s-copy",
+      vec![
+        step(1, "DOWN", "KC_LCTL"),
+        step(2, "TAP", "KC_C"),
+        step(3, "WAIT", "50"),
+        step(4, "UP", "KC_LCTL"),
+        step(5, "TAP", "0x5221"),
+        step(6, "TEXT", "ok"),
+      ],
+    );
+    let bytes = encode_script(&copy, 64).expect("encode");
+    assert_eq!(
+      bytes,
+      [1, 2, 0xE0, 1, 1, 0x06, 1, 4, b'5', b'0', b'|', 1, 3, 0xE0, 1, 5, 0x21, 0x52, b'o', b'k']
+    );
+
+    let buffer = encode_buffer(std::slice::from_ref(&copy), 64, 4).expect("fits");
+    assert_eq!(buffer.len(), 64);
+    let decoded = decode_buffer(&buffer, 4, "p-default").expect("decode");
+    assert_eq!(decoded.len(), 1);
+    let ops: Vec<(&str, &str)> = decoded[0]
+      .steps
+      .iter()
+      .map(|s| (s.op.as_str(), s.arg.as_deref().unwrap()))
+      .collect();
+    assert_eq!(
+      ops,
+      [("DOWN", "KC_LCTL"), ("TAP", "KC_C"), ("WAIT", "50"), ("UP", "KC_LCTL"), ("TAP", "0x5221"), ("TEXT", "ok")]
+    );
+
+    let long = script("s-long", (1..=20).map(|i| step(i, "TAP", "KC_A")).collect());
+    let err = encode_buffer(&[copy.clone(), long], 64, 4).expect_err("overflow");
+    let overflow = err.downcast::<MacroOverflow>().expect("typed overflow");
+    assert_eq!(overflow.script_id, "s-long");
+    assert_eq!(overflow.needed, bytes.len() + 1 + 61);
+
+    let err = encode_buffer(&[copy.clone(), copy], 64, 1).expect_err("slots");
+    assert_eq!(err.downcast::<MacroOverflow>().unwrap().slot_limit, Some(1));
+  }
+
+  #[test]
+  fn nested_repeats_stop_at_the_buffer_size() {
+    let mut inner = step(2, "REPEAT", "10000");
+    inner.steps = vec![step(3, "TAP", "KC_A")];
+    let mut outer = step(1, "REPEAT", "10000");
+    outer.steps = vec![inner];
+    let nested = script("s-nested", vec![outer]);
+
+    let err = encode_buffer(&[nested], 1024, 4).expect_err("overflow");
+    let overflow = err.downcast::<MacroOverflow>().expect("typed overflow");
+    assert_eq!((overflow.script_id.as_str(), overflow.capacity), ("s-nested", 1024));
+    assert!(overflow.needed > 1024 && overflow.needed < 1024 + 8, "{}", overflow.needed);
+  }
+}
@@ -0,0 +1,165 @@
+use std::{
+  collections::HashMap,
+  time::Duration,
+};
+
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::{
+  backends::r#trait::DeviceBackend,
+  models::device::DeviceInfo,
+  AppState,
+};
+
+pub const DEVICE_ATTACHED: &str = "device-attached";
+pub const DEVICE_DETACHED: &str = "device-detached";
+
+pub trait DeviceEventSink {
+  fn emit_device_event(&self, event: &str, device: &DeviceInfo);
+}
+
+impl<R: Runtime> DeviceEventSink for AppHandle<R> {
+  fn emit_device_event(&self, event: &str, device: &DeviceInfo) {
+    if let Err(e) = self.emit(event, device.clone()) {
+      log::warn!("Failed to emit {} for {}: {}", event, device.id, e);
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+pub struct DeviceChanges {
+  pub attached: Vec<DeviceInfo>,
+  pub detached: Vec<DeviceInfo>,
+}
+
+/// Diffs successive `list_devices` snapshots and reports attach/detach.
+#[derive(Default)]
+pub struct DeviceWatcher {
+  known: HashMap<String, DeviceInfo>,
+}
+
+impl DeviceWatcher {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Starts from the devices present right now, without emitting for them.
+  pub fn primed(backend: &dyn DeviceBackend) -> Self {
+    let known = backend
+      .list_devices()
+      .map(|devices| devices.into_iter().map(|d| (d.id.clone(), d)).collect())
+      .unwrap_or_default();
+    Self { known }
+  }
+
+  pub fn poll(&mut self, backend: &dyn DeviceBackend, sink: &dyn DeviceEventSink) -> anyhow::Result<DeviceChanges> {
+    let current: HashMap<String, DeviceInfo> = backend
+      .list_devices()?
+      .into_iter()
+      .map(|d| (d.id.clone(), d))
+      .collect();
+
+    let mut changes = DeviceChanges::default();
+    for (id, device) in &current {
+      if !self.known.contains_key(id) {
+        changes.attached.push(device.clone());
+      }
+    }
+    for (id, device) in &self.known {
+      if !current.contains_key(id) {
+        changes.detached.push(device.clone());
+      }
+    }
+
+    // A failed invalidation is logged rather than returned, so `known` still
+    // advances and the next poll doesn't emit this pass's events again.
+    for device in &changes.detached {
+      match backend.invalidate_device(device.id.clone()) {
+        Ok(dropped) if !dropped.is_empty() => {
+          log::info!("Device {} detached, invalidated {} session(s)", device.id, dropped.len());
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("Failed to invalidate sessions of detached device {}: {e}", device.id),
+      }
+      sink.emit_device_event(DEVICE_DETACHED, device);
+    }
+    for device in &changes.attached {
+      sink.emit_device_event(DEVICE_ATTACHED, device);
+    }
+
+    self.known = current;
+    Ok(changes)
+  }
+}
+
+pub fn spawn<R: Runtime>(app: AppHandle<R>, interval: Duration) {
+  std::thread::spawn(move || {
+    let state = app.state::<AppState>();
+    let mut watcher = DeviceWatcher::primed(state.backend.as_ref());
+    loop {
+      std::thread::sleep(interval);
+      if let Err(e) = watcher.poll(state.backend.as_ref(), &app) {
+        log::warn!("Device poll failed: {e}");
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{seed_root, TempDir};
+  use crate::backends::{operation::OperationContext, simulated::SimulatedBackend};
+  use crate::models::{
+    binding::{Binding, BindingEntry},
+    state::MutationGuard,
+  };
+  use std::sync::Mutex;
+
+  #[derive(Default)]
+  struct RecordingSink {
+    events: Mutex<Vec<(String, String)>>,
+  }
+
+  impl DeviceEventSink for RecordingSink {
+    fn emit_device_event(&self, event: &str, device: &DeviceInfo) {
+      self.events.lock().unwrap().push((event.to_string(), device.id.clone()));
+    }
+  }
+
+  #[test]
+  fn attach_detach_emits_events_and_drops_sessions() {
+    let seed_root = seed_root();
+    let data_root = TempDir::new("watcher-test");
+    let backend = SimulatedBackend::new(seed_root, data_root.to_path_buf());
+    let sink = RecordingSink::default();
+    let mut watcher = DeviceWatcher::new();
+
+    let device = backend.available_devices().expect("devices")[0].clone();
+    backend.attach(device.clone());
+    let changes = watcher.poll(&backend, &sink).expect("poll");
+    assert_eq!(changes.attached.len(), 1);
+
+    let session_id = backend.open_session(device.id.clone(), &OperationContext::new()).expect("open").session_id;
+
+    backend.detach(&device.id);
+    let changes = watcher.poll(&backend, &sink).expect("poll");
+    assert_eq!(changes.detached.len(), 1);
+
+    let events = sink.events.lock().unwrap().clone();
+    assert_eq!(
+      events,
+      vec![
+        (DEVICE_ATTACHED.to_string(), device.id.clone()),
+        (DEVICE_DETACHED.to_string(), device.id.clone()),
+      ]
+    );
+
+    let entry = BindingEntry {
+      layer_id: Some(1),
+      target_id: "key:0,1".to_string(),
+      binding: Binding::None,
+    };
+    assert!(backend.set_binding(session_id, entry, MutationGuard::default()).is_err(), "session invalidated");
+  }
+}
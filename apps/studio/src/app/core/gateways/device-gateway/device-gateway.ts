@@ -2,6 +2,7 @@ import { InjectionToken } from '@angular/core';
 import {
   BindingEntry,
   DeviceInfo,
+  DeviceState,
   ProfileBundle,
   ViaProbe,
   ViaState,
@@ -20,7 +21,8 @@ export interface DeviceGateway {
   importViaBundle(content: string): Promise<ProfileBundle>;
   applyToRam(sessionId: string): Promise<void>;
   revertRam(sessionId: string): Promise<void>;
-  commit(sessionId: string): Promise<void>;
+  commit(sessionId: string, message?: string): Promise<void>;
+  commitHistory(sessionId: string): Promise<DeviceState[]>;
   run(sessionId: string, sequenceId: string): Promise<void>;
   stopAll(sessionId: string): Promise<void>;
   getStatus(sessionId: string): Promise<import('@shared/models/device').StatusSnapshot>;
@@ -1,5 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
-import { BindingEntry, DeviceInfo, ProfileBundle, StatusSnapshot, ViaProbe, ViaState } from '@shared/models/device';
+import { BindingEntry, DeviceInfo, DeviceState, ProfileBundle, StatusSnapshot, ViaProbe, ViaState } from '@shared/models/device';
 import { NormalizedLayout } from '@shared/utils/layout/models';
 import { ApiResult, GatewayError } from '@shared/models/api';
 import { DeviceGateway } from './device-gateway';
@@ -68,8 +68,12 @@ export class TauriDeviceGateway implements DeviceGateway {
     return unwrap(tauriInvoke<ApiResult<void>>('revert_ram', { sessionId }));
   }
 
-  commit(sessionId: string): Promise<void> {
-    return unwrap(tauriInvoke<ApiResult<void>>('commit', { sessionId }));
+  commit(sessionId: string, message?: string): Promise<void> {
+    return unwrap(tauriInvoke<ApiResult<void>>('commit', { sessionId, message }));
+  }
+
+  commitHistory(sessionId: string): Promise<DeviceState[]> {
+    return unwrap(tauriInvoke<ApiResult<DeviceState[]>>('commit_history', { sessionId }));
   }
 
   run(sessionId: string, sequenceId: string): Promise<void> {
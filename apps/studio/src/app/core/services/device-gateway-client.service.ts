@@ -36,8 +36,12 @@ export class DeviceGatewayClientService {
     return this.gateway.revertRam(sessionId);
   }
 
-  commit(sessionId: string) {
-    return this.gateway.commit(sessionId);
+  commit(sessionId: string, message?: string) {
+    return this.gateway.commit(sessionId, message);
+  }
+
+  commitHistory(sessionId: string) {
+    return this.gateway.commitHistory(sessionId);
   }
 
   run(sessionId: string, sequenceId: string) {
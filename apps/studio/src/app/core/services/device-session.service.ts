@@ -71,9 +71,14 @@ export class DeviceSessionService {
     return this.gateway.revertRam(this.sessionId);
   }
 
-  async commit() {
+  async commit(message?: string) {
     if (!this.sessionId) return;
-    return this.gateway.commit(this.sessionId);
+    return this.gateway.commit(this.sessionId, message);
+  }
+
+  async commitHistory() {
+    if (!this.sessionId) return [];
+    return this.gateway.commitHistory(this.sessionId);
   }
 
   async run(sequenceId: string) {
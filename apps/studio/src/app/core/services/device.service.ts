@@ -356,11 +356,11 @@ export class DeviceService {
     }
   }
 
-  async commitToFlash() {
+  async commitToFlash(message?: string) {
     if (!this._connected() || this._busy() || !this.session.sessionIdValue) return;
     this._busy.set(true);
     try {
-      await this.session.commit();
+      await this.session.commit(message);
       await this.refreshSession();
       this._lastError.set(null);
       this._lastSuccess.set('Committed');
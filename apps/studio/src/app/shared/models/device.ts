@@ -73,6 +73,8 @@ export interface Step {
   op: string;
   arg?: string;
   class?: number;
+  jitter?: number;
+  steps?: Step[];
 }
 
 export type Binding =
@@ -101,17 +103,35 @@ export interface LayerState {
   bindings: BindingEntry[];
 }
 
+export interface CommitMeta {
+  message?: string | null;
+  author?: string | null;
+  host?: string | null;
+  appVersion?: string | null;
+  committedAt?: number | null;
+}
+
 export interface DeviceState {
   profileId: string;
   layers: LayerState[];
   revision?: number;
   checksum?: number;
+  meta?: CommitMeta | null;
+}
+
+export interface DelayClass {
+  id: number;
+  name: string;
+  baseMs: number;
+  jitter?: number;
+  scale?: number;
 }
 
 export interface Profile {
   id: string;
   name: string;
   layers: LayerState[];
+  delayClasses?: DelayClass[];
 }
 
 export interface ProfileBundle {